@@ -0,0 +1,61 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use vulkan_engine::vulkan::render_order::{self, SortKey};
+
+/// Simple xorshift generator so the benchmark does not depend on a rng crate.
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+/// Generates renderables spread over a realistic amount of pipelines, materials and meshes.
+fn generate_renderables(count: usize) -> Vec<(u32, u32, u32, f32)> {
+    let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+
+    (0..count)
+        .map(|_| {
+            let pipeline = (rng.next() % 8) as u32;
+            let material = (rng.next() % 256) as u32;
+            let mesh = (rng.next() % 1024) as u32;
+            let depth = (rng.next() % 10_000) as f32 / 10_000.0;
+            (pipeline, material, mesh, depth)
+        })
+        .collect()
+}
+
+fn bench_render_order(c: &mut Criterion) {
+    let mut group = c.benchmark_group("render_order");
+
+    for count in [1_000, 10_000, 50_000, 100_000] {
+        let renderables = generate_renderables(count);
+
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(count),
+            &renderables,
+            |b, renderables| {
+                b.iter(|| {
+                    let mut keyed: Vec<(SortKey, usize)> = renderables
+                        .iter()
+                        .enumerate()
+                        .map(|(i, &(pipeline, material, mesh, depth))| {
+                            (SortKey::new(pipeline, material, mesh, depth), i)
+                        })
+                        .collect();
+                    render_order::sort(&mut keyed);
+                    black_box(keyed)
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_render_order);
+criterion_main!(benches);
@@ -1,6 +1,12 @@
 use std::env;
 use std::fs;
-use std::process::Command;
+use std::path::Path;
+
+use ve_shader_reflect::{codegen, BlockLayout, BlockMemberType, SetBindingData, ShaderInfo};
+
+// shared with the engine, which recompiles changed shaders at runtime
+#[path = "src/assets/glslc.rs"]
+mod glslc;
 
 fn main() {
     // Tell Cargo that if the given file changes, to rerun this build script.
@@ -26,36 +32,119 @@ fn main() {
                     &path.file_name().unwrap().to_str().unwrap()
                 );
 
-                let _ = compile_shader(&shader_target_dir, &path, "vert");
-                let _ = compile_shader(&shader_target_dir, &path, "frag");
+                for stage in glslc::stages(&path) {
+                    compile_shader(&shader_target_dir, &path, stage);
+                }
             }
         }
     }
+
+    generate_shader_structs(&shader_target_dir);
+}
+
+/// Generates the structs of all blocks that are filled from Rust into `$OUT_DIR/shader_structs.rs`,
+/// so that their layouts always match the shaders.
+fn generate_shader_structs(shader_dir: &Path) {
+    let point_light = reflect_shader(shader_dir, "deferred_point_brdf-frag");
+    let directional_light = reflect_shader(shader_dir, "deferred_directional_brdf-frag");
+    let gpass = reflect_shader(shader_dir, "material_solid_color-vert");
+
+    let options = codegen::RustStructOptions {
+        derives: vec![String::from("Clone"), String::from("Copy")],
+        types: vec![
+            (
+                BlockMemberType::FloatVector(2),
+                String::from("gfx_maths::Vec2"),
+            ),
+            (
+                BlockMemberType::FloatVector(3),
+                String::from("gfx_maths::Vec3"),
+            ),
+            (
+                BlockMemberType::FloatVector(4),
+                String::from("gfx_maths::Vec4"),
+            ),
+            (
+                BlockMemberType::FloatMatrix(4),
+                String::from("gfx_maths::Mat4"),
+            ),
+        ],
+    };
+    let structs = [
+        ("CameraUniformData", uniform_buffer(&point_light, "u_Cam")),
+        (
+            "PointLightData",
+            point_light.push_constants[0].layout.clone(),
+        ),
+        (
+            "DirectionalLightData",
+            directional_light.push_constants[0].layout.clone(),
+        ),
+        (
+            "TransformData",
+            structured_buffer_element(&gpass, "u_Transforms"),
+        ),
+    ];
+
+    let code: Vec<_> = structs
+        .iter()
+        .map(|(name, layout)| codegen::rust_struct(name, layout, &options))
+        .collect();
+    let out_dir = env::var("OUT_DIR").unwrap();
+    fs::write(
+        Path::new(&out_dir).join("shader_structs.rs"),
+        code.join("\n"),
+    )
+    .unwrap();
+}
+
+fn reflect_shader(shader_dir: &Path, name: &str) -> ShaderInfo {
+    let spv = fs::read(shader_dir.join(format!("{name}.spv"))).unwrap();
+    let code: Vec<u32> = spv
+        .chunks_exact(4)
+        .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        .collect();
+    ve_shader_reflect::reflect_shader(&code).unwrap()
+}
+
+fn uniform_buffer(info: &ShaderInfo, var_name: &str) -> BlockLayout {
+    match info.set_bindings.iter().find(|b| b.var_name == var_name) {
+        Some(binding) => match &binding.data {
+            SetBindingData::UniformBuffer { layout } => layout.clone(),
+            data => panic!("{var_name} is not a uniform buffer: {data:?}"),
+        },
+        None => panic!("Shader has no binding {var_name}"),
+    }
 }
 
-fn compile_shader(
-    target_dir: &std::path::Path,
-    source_path: &std::path::Path,
-    shader_type: &str,
-) -> std::process::Output {
-    let output_path = target_dir.join(format!(
-        "{}-{}.spv",
-        &source_path.file_stem().unwrap().to_str().unwrap(),
-        shader_type
-    ));
-
-    let output = Command::new("glslc")
-        .arg("--target-env=vulkan1.2")
-        .arg("-fauto-combined-image-sampler")
-        .arg(format!("-fshader-stage={shader_type}"))
-        .arg(format!("-fentry-point={shader_type}"))
-        .arg(&source_path.display().to_string())
-        .arg(format!("-o{}", output_path.to_string_lossy()))
-        .output()
+/// Returns the layout of the elements of a `StructuredBuffer` of structs.
+fn structured_buffer_element(info: &ShaderInfo, var_name: &str) -> BlockLayout {
+    let binding = info
+        .set_bindings
+        .iter()
+        .find(|b| b.var_name == var_name)
+        .unwrap_or_else(|| panic!("Shader has no binding {var_name}"));
+    let element = match &binding.data {
+        SetBindingData::StorageBuffer { layout } => layout.members.first().map(|m| &m.kind),
+        _ => None,
+    };
+    match element {
+        Some(BlockMemberType::RuntimeArray { element, stride }) => match element.as_ref() {
+            BlockMemberType::Struct { members } => BlockLayout {
+                members: members.clone(),
+                block_name: format!("{var_name}[]"),
+                total_size: *stride,
+            },
+            kind => panic!("Elements of {var_name} are not structs: {kind:?}"),
+        },
+        _ => panic!("{var_name} is not a StructuredBuffer"),
+    }
+}
+
+fn compile_shader(target_dir: &Path, source_path: &Path, stage: &str) {
+    let output = glslc::compile_stage(source_path, target_dir, stage)
         .expect("failed to compile shaders using glslc");
 
     println!("Shader Compiler Output: {output:#?}");
     assert!(output.status.success());
-
-    output
 }
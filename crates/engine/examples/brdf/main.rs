@@ -1,7 +1,8 @@
 use gfx_maths::*;
 /// Renders a brdf example
-use std::{path::Path, rc::Rc};
+use std::rc::Rc;
 use vulkan_engine::{
+    assets,
     core::engine::Engine,
     scene::{
         component::{
@@ -24,7 +25,7 @@ fn main() {
     vulkan_engine::run_engine(1920, 1080, "BRDF Example", setup);
 }
 
-fn setup(engine: &mut Engine) {
+pub fn setup(engine: &mut Engine) {
     let scene = &mut engine.scene;
 
     // pipeline setup
@@ -57,16 +58,18 @@ fn setup(engine: &mut Engine) {
         "material_solid_color",
         engine.vulkan_manager.desc_layout_frame_data,
         engine.vulkan_manager.renderpass,
+        engine.vulkan_manager.depth_mode,
         brdf_lighting.as_ref(),
+        &[],
     )
     .unwrap();
 
     let mesh_data_sphere_smooth =
-        ve_format::mesh::MeshData::from_file(Path::new("./assets/models/sphere_smooth.vem"))
+        ve_format::mesh::MeshData::from_file(&assets::root().join("models/sphere_smooth.vem"))
             .expect("Model sphere_smooth.vem not found!");
     let mesh_sphere_smooth = Mesh::bake(
         mesh_data_sphere_smooth,
-        (*engine.vulkan_manager.allocator).clone(),
+        (*engine.vulkan_manager.mesh_pool).clone(),
         &mut engine.vulkan_manager.uploader,
     )
     .unwrap();
@@ -93,10 +96,7 @@ fn setup(engine: &mut Engine) {
             material.set_float("metallic", (x as f32) * 0.1).unwrap();
             material.set_float("roughness", (y as f32) * 0.1).unwrap();
 
-            let model = Model {
-                material,
-                mesh: mesh_sphere_smooth.clone(),
-            };
+            let model = Model::new(material, mesh_sphere_smooth.clone()).unwrap();
 
             // println!("start");
             let entity = scene.new_entity_with_transform(
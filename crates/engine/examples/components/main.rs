@@ -1,13 +1,13 @@
 /// Showcases the component system
 use std::{
     cell::Cell,
-    path::Path,
     rc::{Rc, Weak},
 };
 
 use egui::Slider;
 use gfx_maths::*;
 use vulkan_engine::{
+    assets,
     core::{engine::Engine, input::Input},
     scene::{
         component::{
@@ -66,16 +66,18 @@ fn setup(engine: &mut Engine) {
         "material_solid_color",
         engine.vulkan_manager.desc_layout_frame_data,
         engine.vulkan_manager.renderpass,
+        engine.vulkan_manager.depth_mode,
         brdf_lighting.as_ref(),
+        &[],
     )
     .unwrap();
 
     let mesh_data_sphere_smooth =
-        ve_format::mesh::MeshData::from_file(Path::new("./assets/models/sphere_smooth.vem"))
+        ve_format::mesh::MeshData::from_file(&assets::root().join("models/sphere_smooth.vem"))
             .expect("Model sphere_smooth.vem not found!");
     let mesh_sphere_smooth = Mesh::bake(
         mesh_data_sphere_smooth,
-        (*engine.vulkan_manager.allocator).clone(),
+        (*engine.vulkan_manager.mesh_pool).clone(),
         &mut engine.vulkan_manager.uploader,
     )
     .unwrap();
@@ -86,10 +88,7 @@ fn setup(engine: &mut Engine) {
         .unwrap();
     material_red.set_float("metallic", 0.1).unwrap();
     material_red.set_float("roughness", 0.7).unwrap();
-    let model_red = Rc::new(Model {
-        material: material_red,
-        mesh: mesh_sphere_smooth.clone(),
-    });
+    let model_red = Rc::new(Model::new(material_red, mesh_sphere_smooth.clone()).unwrap());
 
     let material_silver = brdf_pipeline.create_material().unwrap();
     material_silver
@@ -97,10 +96,7 @@ fn setup(engine: &mut Engine) {
         .unwrap();
     material_silver.set_float("metallic", 0.5).unwrap();
     material_silver.set_float("roughness", 0.3).unwrap();
-    let model_silver = Rc::new(Model {
-        material: material_silver,
-        mesh: mesh_sphere_smooth,
-    });
+    let model_silver = Rc::new(Model::new(material_silver, mesh_sphere_smooth).unwrap());
 
     let entity_cam = scene.new_entity_with_transform(
         "Main Camera".to_owned(),
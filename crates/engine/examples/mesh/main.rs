@@ -1,7 +1,8 @@
 use gfx_maths::*;
 /// Loads and renders a custom mesh.
-use std::{path::Path, rc::Rc};
+use std::rc::Rc;
 use vulkan_engine::{
+    assets,
     core::engine::Engine,
     scene::{
         component::{
@@ -24,7 +25,7 @@ fn main() {
     vulkan_engine::run_engine(1920, 1080, "Mesh Example", setup);
 }
 
-fn setup(engine: &mut Engine) {
+pub fn setup(engine: &mut Engine) {
     let scene = &mut engine.scene;
 
     let pp_tonemap = PPEffect::new(
@@ -56,7 +57,9 @@ fn setup(engine: &mut Engine) {
         "material_solid_color",
         engine.vulkan_manager.desc_layout_frame_data,
         engine.vulkan_manager.renderpass,
+        engine.vulkan_manager.depth_mode,
         brdf_resolve_pipeline.as_ref(),
+        &[],
     )
     .unwrap();
     let brdf_material0 = brdf_pipeline.create_material().unwrap();
@@ -67,20 +70,18 @@ fn setup(engine: &mut Engine) {
     brdf_material0.set_float("metallic", 0.0).unwrap();
     brdf_material0.set_float("roughness", 0.1).unwrap();
 
-    let mesh_data = ve_format::mesh::MeshData::from_file(Path::new("./assets/models/suzanne.vem"))
-        .expect("Model suzanne.vem not found!");
+    let mesh_data =
+        ve_format::mesh::MeshData::from_file(&assets::root().join("models/suzanne.vem"))
+            .expect("Model suzanne.vem not found!");
 
     let mesh = Mesh::bake(
         mesh_data,
-        (*engine.vulkan_manager.allocator).clone(),
+        (*engine.vulkan_manager.mesh_pool).clone(),
         &mut engine.vulkan_manager.uploader,
     )
     .expect("Error baking mesh!");
 
-    let model = Model {
-        material: brdf_material0,
-        mesh,
-    };
+    let model = Model::new(brdf_material0, mesh).unwrap();
 
     let entity = scene.new_entity_with_transform(
         "Suzanne".to_string(),
@@ -44,7 +44,9 @@ fn setup(engine: &mut Engine) {
         "vertex_unlit",
         engine.vulkan_manager.desc_layout_frame_data,
         engine.vulkan_manager.renderpass,
+        engine.vulkan_manager.depth_mode,
         lighting_pipeline.as_ref(),
+        &[],
     )
     .unwrap();
     let material0 = pipeline.create_material().unwrap();
@@ -78,15 +80,12 @@ fn setup(engine: &mut Engine) {
 
     let mesh = Mesh::bake(
         mesh_data,
-        (*engine.vulkan_manager.allocator).clone(),
+        (*engine.vulkan_manager.mesh_pool).clone(),
         &mut engine.vulkan_manager.uploader,
     )
     .unwrap();
 
-    let model = Model {
-        material: material0,
-        mesh,
-    };
+    let model = Model::new(material0, mesh).unwrap();
 
     let entity = scene.new_entity_with_transform(
         "Quad".to_owned(),
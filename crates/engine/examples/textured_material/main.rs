@@ -24,7 +24,7 @@ fn main() {
     vulkan_engine::run_engine(1920, 1080, "Textured Material Example", setup);
 }
 
-fn setup(engine: &mut Engine) {
+pub fn setup(engine: &mut Engine) {
     let scene = &mut engine.scene;
 
     let pp_tonemap = PPEffect::new(
@@ -56,7 +56,9 @@ fn setup(engine: &mut Engine) {
         "material_albedo_tex",
         engine.vulkan_manager.desc_layout_frame_data,
         engine.vulkan_manager.renderpass,
+        engine.vulkan_manager.depth_mode,
         lighting_pipeline.as_ref(),
+        &[],
     )
     .unwrap();
 
@@ -108,15 +110,12 @@ fn setup(engine: &mut Engine) {
 
     let mesh = Mesh::bake(
         mesh_data,
-        (*engine.vulkan_manager.allocator).clone(),
+        (*engine.vulkan_manager.mesh_pool).clone(),
         &mut engine.vulkan_manager.uploader,
     )
     .unwrap();
 
-    let model = Model {
-        material: material0,
-        mesh,
-    };
+    let model = Model::new(material0, mesh).unwrap();
 
     let entity = scene.new_entity_with_transform(
         "Quad".to_owned(),
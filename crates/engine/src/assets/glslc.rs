@@ -0,0 +1,62 @@
+//! Compilation of the HLSL shader sources with glslc.
+//!
+//! This module is shared with the build script, so that shaders recompiled at runtime are identical to the ones
+//! compiled during the build. It must only depend on `std`.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+/// Compute shader sources are named `<name>.comp.hlsl`, all others `<name>.hlsl`.
+pub(crate) const COMPUTE_SUFFIX: &str = ".comp";
+
+/// Returns the name of the shader compiled from the source at `path`, e.g. `gpu_cull` for `gpu_cull.comp.hlsl`.
+pub(crate) fn source_name(path: &Path) -> Option<&str> {
+    let stem = path.file_stem()?.to_str()?;
+    Some(stem.strip_suffix(COMPUTE_SUFFIX).unwrap_or(stem))
+}
+
+/// Returns the stages compiled from the source at `path`.
+///
+/// Compute shaders only contain a single stage, everything else is a vertex + fragment shader pair.
+/// The stage names are also the names of the entry points.
+pub(crate) fn stages(path: &Path) -> &'static [&'static str] {
+    let is_compute = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .is_some_and(|stem| stem.ends_with(COMPUTE_SUFFIX));
+    if is_compute {
+        &["comp"]
+    } else {
+        &["vert", "frag"]
+    }
+}
+
+/// Returns the path of the SPIR-V file `stage` of shader `name` is compiled to.
+pub(crate) fn output_path(target_dir: &Path, name: &str, stage: &str) -> PathBuf {
+    target_dir.join(format!("{name}-{stage}.spv"))
+}
+
+/// Compiles `stage` of the source at `source_path` into `target_dir`.
+///
+/// Returns an error if glslc can't be run, the compiler output otherwise, which has to be checked for success.
+pub(crate) fn compile_stage(
+    source_path: &Path,
+    target_dir: &Path,
+    stage: &str,
+) -> io::Result<Output> {
+    let name = source_name(source_path).unwrap_or_default();
+    Command::new("glslc")
+        .arg("--target-env=vulkan1.2")
+        .arg("-fauto-combined-image-sampler")
+        .arg(format!("-fshader-stage={stage}"))
+        .arg(format!("-fentry-point={stage}"))
+        .arg(source_path)
+        .arg(format!(
+            "-o{}",
+            output_path(target_dir, name, stage).to_string_lossy()
+        ))
+        .output()
+}
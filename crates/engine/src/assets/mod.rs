@@ -1 +1,54 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+mod glslc;
 pub(crate) mod shader;
+pub(crate) mod shader_watcher;
+
+/// The asset directory of the source checkout, used if no other asset directory is found.
+const SOURCE_ROOT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../assets");
+
+/// Shared by all threads, so that assets loaded outside of the main thread come from the same directory.
+static ROOT: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Returns the directory all assets are loaded from, see [`find_root`].
+pub fn root() -> PathBuf {
+    if let Some(root) = &*ROOT.read().unwrap() {
+        return root.clone();
+    }
+    ROOT.write()
+        .unwrap()
+        .get_or_insert_with(|| find_root(None))
+        .clone()
+}
+
+/// Sets the directory all assets are loaded from.
+///
+/// The engine sets it on startup, from `asset_root` in `engine.toml` or by searching with [`find_root`].
+pub fn set_root(path: impl Into<PathBuf>) {
+    let path = path.into();
+    log::info!("Loading assets from {}", path.display());
+    *ROOT.write().unwrap() = Some(path);
+}
+
+/// Finds the asset directory, checking in order:
+/// - `configured`, if given
+/// - `assets` next to the executable, as created by `make package`
+/// - `assets` in the working directory
+/// - `assets` in the source checkout the engine was built from
+pub fn find_root(configured: Option<&Path>) -> PathBuf {
+    if let Some(configured) = configured {
+        return configured.to_owned();
+    }
+
+    let next_to_executable = std::env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.parent()?.join("assets")));
+    next_to_executable
+        .into_iter()
+        .chain(std::iter::once(PathBuf::from("assets")))
+        .find(|candidate| candidate.join("shaders").is_dir())
+        .unwrap_or_else(|| PathBuf::from(SOURCE_ROOT))
+}
@@ -5,13 +5,16 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use ash::vk;
+use super::glslc::{self, COMPUTE_SUFFIX};
+use crate::vulkan::error::{GraphicsError, GraphicsResult};
 
-const FOLDER: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../assets/shaders");
+/// The HLSL sources that are compiled into the shader folder of the asset root, by the build script or [`compile`].
+pub(crate) const SOURCE_FOLDER: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../shaders");
 
 pub enum ShaderKind {
     Vertex,
     Fragment,
+    Compute,
 }
 
 impl ShaderKind {
@@ -20,51 +23,114 @@ impl ShaderKind {
         match self {
             Vertex => "vert",
             Fragment => "frag",
+            Compute => "comp",
         }
     }
 }
 
-pub fn load(name: &str, kind: ShaderKind, code_ref: &mut Vec<u32>) -> vk::ShaderModuleCreateInfo {
-    let folder = Path::new(FOLDER);
-    let filename = format!("{}-{}.spv", name, kind.extension());
-    let file = folder.join(filename);
+/// Compiles the HLSL shader `name` from [`SOURCE_FOLDER`] into the [asset root](super::root) with glslc, the same way the build script does.
+///
+/// Compute shaders are compiled to a single stage, everything else to a vertex and fragment stage.
+/// Returns the compiler output on failure.
+pub(crate) fn compile(name: &str) -> Result<(), String> {
+    let compute_path = Path::new(SOURCE_FOLDER).join(format!("{}{}.hlsl", name, COMPUTE_SUFFIX));
+    let source_path = if compute_path.is_file() {
+        compute_path
+    } else {
+        Path::new(SOURCE_FOLDER).join(format!("{}.hlsl", name))
+    };
+    if !source_path.is_file() {
+        return Err(format!("Couldn't find shader {}", source_path.display()));
+    }
+    for stage in glslc::stages(&source_path) {
+        let output = glslc::compile_stage(&source_path, &folder(), stage)
+            .map_err(|e| format!("Couldn't run glslc: {}", e))?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).into_owned());
+        }
+    }
+    Ok(())
+}
 
-    get_file_as_bytes(file, code_ref);
+/// The first word of every SPIR-V module.
+const SPIRV_MAGIC: u32 = 0x0723_0203;
 
-    *vk::ShaderModuleCreateInfo::builder().code(code_ref)
+/// Returns the directory compiled shaders are loaded from, inside the [asset root](super::root).
+fn folder() -> PathBuf {
+    super::root().join("shaders")
 }
 
-fn get_file_as_bytes(file: PathBuf, dst: &mut Vec<u32>) {
-    let len = file
-        .metadata()
-        .unwrap_or_else(|_| panic!("Couldn't read file metadata of shader {}", file.display()))
-        .len()
-        .try_into()
-        .unwrap_or_else(|_| {
-            panic!(
-                "Couldn't read file size into a usize of shader {}",
-                file.display()
-            )
-        });
-
-    let u32_len = std::mem::size_of::<u32>();
-    assert!(len % u32_len == 0, "Parsed shader file wrong length.");
-
-    *dst = vec![0u32; len / u32_len]; // overwrite array stored in pointer with the one we actually need
-
-    let buf = unsafe {
-        std::slice::from_raw_parts_mut(dst.as_mut_ptr() as *mut u8, len) // assumes little endian, convert later if neccessary
+/// Loads the SPIR-V code of the given stage of shader `name` from the [asset root](super::root).
+///
+/// # Errors
+/// - [`GraphicsError::InvalidShader`] when the file can't be read or doesn't contain SPIR-V
+pub fn load(name: &str, kind: ShaderKind) -> GraphicsResult<Vec<u32>> {
+    let file = glslc::output_path(&folder(), name, kind.extension());
+    let invalid = |reason: String| GraphicsError::InvalidShader {
+        path: file.clone(),
+        reason,
     };
 
-    let mut f = File::open(&file)
-        .unwrap_or_else(|_| panic!("Couldn't read shader file {}", file.display()));
-    f.read_exact(buf)
-        .unwrap_or_else(|_| panic!("Couldn't read shader into the buffer {}", file.display()));
+    let mut bytes = Vec::new();
+    File::open(&file)
+        .and_then(|mut f| f.read_to_end(&mut bytes))
+        .map_err(|e| invalid(e.to_string()))?;
+    if bytes.len() % 4 != 0 {
+        return Err(invalid(format!(
+            "size of {} bytes is not a multiple of 4",
+            bytes.len()
+        )));
+    }
+
+    // SPIR-V files are little endian
+    let code: Vec<u32> = bytes
+        .chunks_exact(4)
+        .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+        .collect();
+    if code.first() != Some(&SPIRV_MAGIC) {
+        return Err(invalid("missing SPIR-V magic number".to_owned()));
+    }
+
+    Ok(code)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{load, ShaderKind, SPIRV_MAGIC};
+    use crate::vulkan::error::GraphicsError;
 
-    // convert to big endian, if the host system uses big endian
-    if cfg!(target_endian = "big") {
-        for n in dst {
-            *n = n.to_le();
+    #[test]
+    fn test_load_invalid_spirv() {
+        let root = std::env::temp_dir().join(format!("ve_shader_test_{}", std::process::id()));
+        let folder = root.join("shaders");
+        std::fs::create_dir_all(&folder).unwrap();
+        std::fs::write(folder.join("valid-vert.spv"), SPIRV_MAGIC.to_le_bytes()).unwrap();
+        std::fs::write(folder.join("truncated-vert.spv"), [0x03, 0x02, 0x23]).unwrap();
+        std::fs::write(folder.join("text-vert.spv"), b"void main() {}\n\n").unwrap();
+        // a valid header followed by an OpName that is missing its operands
+        let header = [SPIRV_MAGIC, 0x0001_0500, 0, 16, 0, (3 << 16) | 5];
+        let header: Vec<u8> = header.iter().flat_map(|word| word.to_le_bytes()).collect();
+        std::fs::write(folder.join("truncated_contents-vert.spv"), header).unwrap();
+        crate::assets::set_root(&root);
+
+        assert_eq!(
+            load("valid", ShaderKind::Vertex).unwrap(),
+            vec![SPIRV_MAGIC]
+        );
+        for name in ["missing", "truncated", "text"] {
+            assert!(matches!(
+                load(name, ShaderKind::Vertex),
+                Err(GraphicsError::InvalidShader { .. })
+            ));
         }
+
+        // the header is checked on load, the contents when reflecting the shader
+        let code = load("truncated_contents", ShaderKind::Vertex).unwrap();
+        assert!(matches!(
+            crate::vulkan::pipeline::reflect_shader_modules(&code, &code),
+            Err(GraphicsError::ShaderReflect(_))
+        ));
+
+        std::fs::remove_dir_all(root).unwrap();
     }
 }
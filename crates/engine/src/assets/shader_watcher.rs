@@ -0,0 +1,182 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use super::{glslc::source_name, shader::SOURCE_FOLDER};
+
+/// The shader sources are checked for changes at most this often.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Detects changed shader sources by polling their modification times.
+pub(crate) struct ShaderWatcher {
+    modified: HashMap<PathBuf, SystemTime>,
+    last_poll: Instant,
+}
+
+impl ShaderWatcher {
+    pub(crate) fn new() -> Self {
+        let mut watcher = Self {
+            modified: HashMap::new(),
+            last_poll: Instant::now(),
+        };
+        watcher.scan();
+        watcher
+    }
+
+    /// Returns the names of all shaders whose source or one of its included headers changed since the last poll.
+    pub(crate) fn poll(&mut self) -> Vec<String> {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return Vec::new();
+        }
+        self.last_poll = Instant::now();
+
+        let changed = self.scan();
+        if changed.is_empty() {
+            return Vec::new();
+        }
+        let changed_headers = self.affected_headers(&changed);
+
+        let mut names: Vec<String> = self
+            .shader_paths()
+            .filter(|path| changed.contains(path) || includes_any(path, &changed_headers))
+            .filter_map(|path| Some(source_name(path)?.to_owned()))
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Returns the file names of all changed headers and the headers that include them, directly or indirectly.
+    fn affected_headers(&self, changed: &[PathBuf]) -> Vec<String> {
+        let headers: Vec<&PathBuf> = self
+            .modified
+            .keys()
+            .filter(|path| is_header(path))
+            .collect();
+
+        let mut affected: Vec<String> = changed
+            .iter()
+            .filter(|path| is_header(path))
+            .filter_map(|path| Some(path.file_name()?.to_str()?.to_owned()))
+            .collect();
+        loop {
+            let includers: Vec<String> = headers
+                .iter()
+                .filter(|path| includes_any(path, &affected))
+                .filter_map(|path| Some(path.file_name()?.to_str()?.to_owned()))
+                .filter(|name| !affected.contains(name))
+                .collect();
+            if includers.is_empty() {
+                return affected;
+            }
+            affected.extend(includers);
+        }
+    }
+
+    /// Returns the names of all known shaders.
+    pub(crate) fn all_shaders(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .shader_paths()
+            .filter_map(|path| Some(source_name(path)?.to_owned()))
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn shader_paths(&self) -> impl Iterator<Item = &PathBuf> {
+        self.modified
+            .keys()
+            .filter(|path| path.extension().is_some_and(|ext| ext == "hlsl"))
+    }
+
+    /// Updates the modification times of all sources and headers, returning the paths that changed.
+    fn scan(&mut self) -> Vec<PathBuf> {
+        let entries = match std::fs::read_dir(SOURCE_FOLDER) {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+
+        let mut changed = Vec::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if !path
+                .extension()
+                .is_some_and(|ext| ext == "hlsl" || ext == "hlslh")
+            {
+                continue;
+            }
+            let modified = match entry.metadata().and_then(|m| m.modified()) {
+                Ok(modified) => modified,
+                Err(_) => continue,
+            };
+            if self.modified.insert(path.clone(), modified) != Some(modified) {
+                changed.push(path);
+            }
+        }
+        changed
+    }
+}
+
+fn is_header(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "hlslh")
+}
+
+/// Returns true if the source at `path` includes any of the given file names.
+fn includes_any(path: &Path, headers: &[String]) -> bool {
+    if headers.is_empty() {
+        return false;
+    }
+    std::fs::read_to_string(path).is_ok_and(|source| {
+        source
+            .lines()
+            .filter(|line| line.trim_start().starts_with("#include"))
+            .any(|line| headers.iter().any(|header| line.contains(header.as_str())))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, path::PathBuf, time::Instant};
+
+    use super::{includes_any, ShaderWatcher};
+
+    #[test]
+    fn test_affected_headers() {
+        let dir = std::env::temp_dir().join(format!("ve_watcher_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let files = [
+            ("common.hlslh", "float4 f();\n"),
+            ("lighting.hlslh", "#include \"common.hlslh\"\n"),
+            ("other.hlslh", "float4 g();\n"),
+            ("lit.hlsl", "  #include \"lighting.hlslh\"\n"),
+            // mentioning a header outside of an include doesn't count
+            ("unlit.hlsl", "// common.hlslh\n#include \"other.hlslh\"\n"),
+        ];
+        for (name, source) in files {
+            std::fs::write(dir.join(name), source).unwrap();
+        }
+        let path = |name: &str| dir.join(name);
+
+        let watcher = ShaderWatcher {
+            modified: files
+                .iter()
+                .map(|(name, _)| (path(name), std::time::SystemTime::UNIX_EPOCH))
+                .collect::<HashMap<PathBuf, _>>(),
+            last_poll: Instant::now(),
+        };
+
+        let mut affected = watcher.affected_headers(&[path("common.hlslh")]);
+        affected.sort();
+        assert_eq!(affected, ["common.hlslh", "lighting.hlslh"]);
+        assert!(includes_any(&path("lit.hlsl"), &affected));
+        assert!(!includes_any(&path("unlit.hlsl"), &affected));
+
+        // changed sources are not headers
+        assert!(watcher.affected_headers(&[path("lit.hlsl")]).is_empty());
+        assert!(!includes_any(&path("lit.hlsl"), &[]));
+        assert!(!includes_any(&path("missing.hlsl"), &affected));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
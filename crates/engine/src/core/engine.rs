@@ -1,20 +1,22 @@
 use std::{
     cell::RefCell,
     ffi::CStr,
+    path::PathBuf,
     rc::Rc,
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use ash::vk;
 use egui::{
     plot::{Legend, Line, Plot, Value, Values},
     CollapsingHeader, Color32, ComboBox, CtxRef, DragValue, ProgressBar, RichText, ScrollArea,
-    SidePanel,
+    SidePanel, Slider,
 };
 use gfx_maths::Quaternion;
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    assets,
     core::{gameloop::GameLoop, input::Input, window},
     scene::{
         component::{
@@ -24,11 +26,20 @@ use crate::{
         entity::Entity,
         Scene,
     },
-    vulkan::{self, RendererConfig, VulkanManager},
+    vulkan::{
+        self,
+        capture::CaptureKind,
+        present::{PresentMode, MAX_FRAMES_IN_FLIGHT},
+        resolution::ResolutionScale,
+        DepthMode, RendererConfig, VulkanManager,
+    },
 };
 
 use super::window::Window;
 
+/// Every new render scale recreates the render targets, so it is updated at most this often.
+const RENDER_SCALE_INTERVAL: Duration = Duration::from_millis(500);
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct EngineInfo {
     pub window_info: window::InitialWindowInfo,
@@ -42,6 +53,10 @@ pub struct EngineInit {
 
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub(crate) struct EngineConfig {
+    /// Directory assets are loaded from, see [`assets::find_root`] for the default.
+    ///
+    /// Declared before all tables, as TOML can't serialize values after them.
+    pub(crate) asset_root: Option<PathBuf>,
     pub(crate) renderer: Option<vulkan::RendererConfig>,
 }
 
@@ -60,61 +75,24 @@ pub(crate) fn write_config(config: &EngineConfig) {
     }
 }
 
+/// Starts a new instance of the application and exits this one, e.g. to apply a changed config.
+fn restart() -> ! {
+    let args = std::env::args().collect::<Vec<_>>();
+    std::process::Command::new(&args[0])
+        .args(args)
+        .spawn()
+        .unwrap();
+    std::process::exit(0);
+}
+
 impl EngineInit {
     pub fn new(info: EngineInfo) -> Result<Self, Box<dyn std::error::Error>> {
-        let config = read_config();
-
-        let scene = Scene::new();
         let eventloop = winit::event_loop::EventLoop::new();
         let window = info.window_info.build(&eventloop)?;
 
-        let vulkan_manager =
-            VulkanManager::new(info, &window.winit_window, 3, config.renderer.as_ref())?;
-        let input = Rc::new(RefCell::new(Input::new()));
-        let gameloop = GameLoop::new(input.clone());
-
-        let gui_context = egui::CtxRef::default();
-        let gui_state = egui_winit::State::new(&window.winit_window);
-
         Ok(Self {
             eventloop,
-            engine: Engine {
-                info,
-                gameloop,
-                input,
-                scene,
-                vulkan_manager,
-                window,
-                gui_context,
-                gui_state,
-
-                fps_time: Instant::now(),
-                fps_count: 0,
-                fps: 0,
-                last_frame: Instant::now(),
-                frame_time_last_sample: Instant::now(),
-                frame_time_max: 0.0,
-                render_time_max: 0.0,
-                ui_time_max: 0.0,
-                last_render_time: 0.0,
-                last_ui_time: 0.0,
-                frame_time_history: Vec::with_capacity(5000),
-                render_time_history: Vec::with_capacity(5000),
-                ui_time_history: Vec::with_capacity(5000),
-
-                ui_vertex_count: 0,
-                ui_index_count: 0,
-                ui_mesh_count: 0,
-
-                scene_graph_visible: false,
-                #[cfg(feature = "profiler")]
-                profiler_visible: false,
-                config: read_config(),
-
-                component_factories: Vec::new(),
-                selected_entity: None,
-                selected_factory: 0,
-            },
+            engine: Engine::new(info, Some(window))?,
         })
     }
 
@@ -134,15 +112,23 @@ impl EngineInit {
 
 pub type ComponentFactoryFn = fn(&Rc<Entity>) -> Rc<dyn Component>;
 
+/// Captures every frame to a numbered image, see [`Engine::start_capture_sequence`].
+struct CaptureSequence {
+    directory: PathBuf,
+    delta: f32,
+    frame: usize,
+}
+
 pub struct Engine {
     pub info: EngineInfo,
     pub(crate) gameloop: GameLoop,
     pub input: Rc<RefCell<Input>>,
     pub scene: Rc<Scene>,
     pub vulkan_manager: VulkanManager,
-    pub window: Window,
+    /// `None` when running headless
+    pub window: Option<Window>,
     pub gui_context: egui::CtxRef,
-    pub gui_state: egui_winit::State,
+    pub gui_state: Option<egui_winit::State>,
 
     fps_time: Instant,
     fps_count: usize,
@@ -155,6 +141,9 @@ pub struct Engine {
     last_render_time: f32,
     last_ui_time: f32,
     frame_time_history: Vec<Value>,
+    /// all frame times since the last update of the render scale
+    scale_frame_times: Vec<f32>,
+    last_scale_update: Instant,
     render_time_history: Vec<Value>,
     ui_time_history: Vec<Value>,
 
@@ -170,9 +159,82 @@ pub struct Engine {
     component_factories: Vec<(String, ComponentFactoryFn)>,
     selected_entity: Option<Rc<Entity>>,
     selected_factory: usize,
+
+    capture_sequence: Option<CaptureSequence>,
 }
 
 impl Engine {
+    fn new(info: EngineInfo, window: Option<Window>) -> Result<Self, Box<dyn std::error::Error>> {
+        let config = read_config();
+
+        assets::set_root(assets::find_root(config.asset_root.as_deref()));
+
+        let scene = Scene::new();
+
+        let vulkan_manager = VulkanManager::new(
+            info,
+            window.as_ref().map(|w| &w.winit_window),
+            config.renderer.as_ref(),
+        )?;
+        let input = Rc::new(RefCell::new(Input::new()));
+        let gameloop = GameLoop::new(input.clone());
+
+        let gui_context = egui::CtxRef::default();
+        let gui_state = window
+            .as_ref()
+            .map(|w| egui_winit::State::new(&w.winit_window));
+
+        Ok(Self {
+            info,
+            gameloop,
+            input,
+            scene,
+            vulkan_manager,
+            window,
+            gui_context,
+            gui_state,
+
+            fps_time: Instant::now(),
+            fps_count: 0,
+            fps: 0,
+            last_frame: Instant::now(),
+            frame_time_last_sample: Instant::now(),
+            frame_time_max: 0.0,
+            render_time_max: 0.0,
+            ui_time_max: 0.0,
+            last_render_time: 0.0,
+            last_ui_time: 0.0,
+            frame_time_history: Vec::with_capacity(5000),
+            scale_frame_times: Vec::new(),
+            last_scale_update: Instant::now(),
+            render_time_history: Vec::with_capacity(5000),
+            ui_time_history: Vec::with_capacity(5000),
+
+            ui_vertex_count: 0,
+            ui_index_count: 0,
+            ui_mesh_count: 0,
+
+            scene_graph_visible: false,
+            #[cfg(feature = "profiler")]
+            profiler_visible: false,
+            config: read_config(),
+
+            component_factories: Vec::new(),
+            selected_entity: None,
+            selected_factory: 0,
+
+            capture_sequence: None,
+        })
+    }
+
+    /// Creates an engine without a window, e.g. for CI or automated screenshots.
+    ///
+    /// Frames are rendered into an offscreen image with the initial window dimensions of `info`.
+    /// There is no event loop, frames have to be rendered with [`Engine::step`].
+    pub fn new_headless(info: EngineInfo) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::new(info, None)
+    }
+
     pub fn init(&self) {
         self.gameloop.init();
     }
@@ -304,9 +366,79 @@ impl Engine {
         );
     }
 
+    /// Updates the scene by `delta` seconds and renders a single frame.
+    ///
+    /// While an image sequence is captured, `delta` is replaced by the fixed timestep of the sequence.
+    pub fn step(&mut self, delta: f32) {
+        let delta = match &mut self.capture_sequence {
+            Some(sequence) => {
+                self.vulkan_manager.request_capture(
+                    CaptureKind::Final,
+                    sequence
+                        .directory
+                        .join(format!("frame_{:05}.png", sequence.frame)),
+                );
+                sequence.frame += 1;
+                sequence.delta
+            }
+            None => delta,
+        };
+
+        self.gameloop.update(&self.scene, delta);
+        self.render();
+    }
+
+    /// Saves the next frame to a timestamped PNG file in the working directory.
+    pub fn take_screenshot(&mut self) {
+        self.capture_timestamped(CaptureKind::Final, "png");
+    }
+
+    /// Saves the HDR scene of the next frame, before post processing and UI, to a timestamped OpenEXR file.
+    pub fn take_hdr_capture(&mut self) {
+        self.capture_timestamped(CaptureKind::Hdr, "exr");
+    }
+
+    fn capture_timestamped(&mut self, kind: CaptureKind, extension: &str) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        self.vulkan_manager
+            .request_capture(kind, format!("screenshot_{timestamp}.{extension}"));
+    }
+
+    /// Saves every following frame to `directory/frame_NNNNN.png`, until [`Engine::stop_capture_sequence`] is called.
+    ///
+    /// The scene is updated with a fixed timestep of `1 / fps` seconds per frame,
+    /// so the sequence plays back at `fps` regardless of how long rendering takes.
+    pub fn start_capture_sequence(
+        &mut self,
+        directory: impl Into<PathBuf>,
+        fps: f32,
+    ) -> std::io::Result<()> {
+        let directory = directory.into();
+        std::fs::create_dir_all(&directory)?;
+        log::info!("Capturing image sequence to {}", directory.display());
+        self.capture_sequence = Some(CaptureSequence {
+            directory,
+            delta: 1.0 / fps,
+            frame: 0,
+        });
+        Ok(())
+    }
+
+    /// Stops the image sequence started with [`Engine::start_capture_sequence`].
+    pub fn stop_capture_sequence(&mut self) {
+        if let Some(sequence) = self.capture_sequence.take() {
+            log::info!("Captured {} frames", sequence.frame);
+        }
+    }
+
     pub(crate) fn render(&mut self) {
         profile_function!();
 
+        self.vulkan_manager.reload_changed_shaders();
+
         let frame_time = self.update_frame_stats();
 
         let gui_meshes = self.render_debug_ui(frame_time);
@@ -324,6 +456,13 @@ impl Engine {
 
         let frame_time = self.last_frame.elapsed().as_secs_f32() * 1000.0;
         self.last_frame = Instant::now();
+        self.scale_frame_times.push(frame_time);
+        if self.last_scale_update.elapsed() >= RENDER_SCALE_INTERVAL {
+            self.vulkan_manager
+                .update_render_scale(&self.scale_frame_times);
+            self.scale_frame_times.clear();
+            self.last_scale_update = Instant::now();
+        }
 
         if frame_time > self.frame_time_max {
             self.frame_time_max = frame_time;
@@ -404,6 +543,10 @@ impl Engine {
             // finalize renderpass
             vk.submit();
             vk.present(image_index);
+
+            if let Err(e) = vk.finish_captures() {
+                log::error!("Failed to write frame capture: {}", e);
+            }
         }
 
         let render_time = render_start_time.elapsed().as_secs_f32() * 1000.0;
@@ -496,8 +639,161 @@ impl Engine {
 
                 ui.checkbox(&mut self.vulkan_manager.enable_wireframe, "Wireframe");
 
+                ui.checkbox(
+                    &mut self.vulkan_manager.enable_frustum_culling,
+                    "Frustum culling",
+                );
+                ui.add_enabled(
+                    self.vulkan_manager.enable_frustum_culling
+                        && self.vulkan_manager.indirect_draw_supported,
+                    egui::Checkbox::new(&mut self.vulkan_manager.enable_gpu_culling, "Cull on GPU"),
+                );
+                let mut objects = format!(
+                    "Objects: {} drawn, {} culled",
+                    self.vulkan_manager.drawn_object_count, self.vulkan_manager.culled_object_count
+                );
+                // GPU culling is counted once the frame has finished
+                if self.vulkan_manager.object_count_age > 0 {
+                    objects += &format!(" ({} frames ago)", self.vulkan_manager.object_count_age);
+                }
+                ui.label(objects);
+
+                ui.separator();
+                ui.heading("Resolution");
+
+                let resolution = &mut self.vulkan_manager.resolution;
+                ui.horizontal(|ui| {
+                    let dynamic = matches!(resolution, ResolutionScale::Dynamic { .. });
+                    if ui.radio(!dynamic, "Fixed").clicked() && dynamic {
+                        *resolution = ResolutionScale::Fixed { scale: 1.0 };
+                    }
+                    if ui.radio(dynamic, "Dynamic").clicked() && !dynamic {
+                        *resolution = ResolutionScale::Dynamic {
+                            target_frame_time: 1000.0 / 60.0,
+                            min_scale: 0.5,
+                            max_scale: 1.0,
+                        };
+                    }
+                });
+                match resolution {
+                    ResolutionScale::Fixed { scale } => {
+                        ui.add(Slider::new(scale, 0.25..=2.0).text("Scale"));
+                    }
+                    ResolutionScale::Dynamic {
+                        target_frame_time,
+                        min_scale,
+                        max_scale,
+                    } => {
+                        ui.add(
+                            Slider::new(target_frame_time, 4.0..=50.0)
+                                .text("Target frame time (ms)"),
+                        );
+                        ui.add(Slider::new(min_scale, 0.25..=1.0).text("Min scale"));
+                        ui.add(Slider::new(max_scale, *min_scale..=2.0).text("Max scale"));
+                    }
+                }
+                // raising the minimum above the maximum moves the maximum along
+                *resolution = resolution.clamped();
+                if let ResolutionScale::Fixed { .. } = self.vulkan_manager.resolution {
+                    self.vulkan_manager.update_render_scale(&[]);
+                }
+                ui.label(format!(
+                    "Render scale: {:.2}",
+                    self.vulkan_manager.render_scale()
+                ));
+
+                ui.separator();
+                ui.heading("Presentation");
+
+                let mut present = self.vulkan_manager.present_config();
+                ComboBox::from_label("Present mode")
+                    .selected_text(format!("{:?}", present.present_mode))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut present.present_mode, PresentMode::Fifo, "Fifo");
+                        ui.selectable_value(
+                            &mut present.present_mode,
+                            PresentMode::Mailbox,
+                            "Mailbox",
+                        );
+                        ui.selectable_value(
+                            &mut present.present_mode,
+                            PresentMode::Immediate,
+                            "Immediate",
+                        );
+                    });
+                ui.add(
+                    Slider::new(&mut present.swapchain_image_count, 1..=8).text("Swapchain images"),
+                );
+                ui.add(
+                    Slider::new(&mut present.frames_in_flight, 1..=MAX_FRAMES_IN_FLIGHT)
+                        .text("Frames in flight"),
+                );
+                ui.horizontal(|ui| {
+                    let mut limited = present.frame_rate_limit.is_some();
+                    ui.checkbox(&mut limited, "Limit frame rate");
+                    let mut limit = present.frame_rate_limit.unwrap_or(60.0);
+                    ui.add_enabled(limited, Slider::new(&mut limit, 10.0..=300.0).text("fps"));
+                    present.frame_rate_limit = limited.then_some(limit);
+                });
+                if present != self.vulkan_manager.present_config() {
+                    log::info!("Changing present config to {:?}", present);
+                    if let Err(e) = self.vulkan_manager.set_present_config(present) {
+                        log::error!("Failed to apply present config: {}", e);
+                    }
+                    self.config
+                        .renderer
+                        .get_or_insert_with(RendererConfig::default)
+                        .present = self.vulkan_manager.present_config();
+                    write_config(&self.config);
+                }
+                if let (Some(mode), Some(images)) = (
+                    self.vulkan_manager.active_present_mode(),
+                    self.vulkan_manager.swapchain_image_count(),
+                ) {
+                    ui.label(format!("Active: {:?} with {} images", mode, images));
+                }
+
+                ui.separator();
+                ui.heading("Shaders");
+
+                ui.horizontal(|ui| {
+                    ui.checkbox(
+                        &mut self.vulkan_manager.enable_shader_hot_reload,
+                        "Hot reload",
+                    );
+                    if ui.button("Reload all").clicked() {
+                        self.vulkan_manager.reload_all_shaders();
+                    }
+                });
+                for (shader, error) in &self.vulkan_manager.shader_errors {
+                    ui.colored_label(Color32::RED, format!("{}:", shader));
+                    ui.label(RichText::new(error).monospace());
+                }
+                ui.separator();
                 ui.checkbox(&mut self.scene_graph_visible, "Show scene graph");
 
+                ui.horizontal(|ui| {
+                    if ui.button("Screenshot").clicked() {
+                        self.take_screenshot();
+                    }
+                    if ui.button("HDR capture").clicked() {
+                        self.take_hdr_capture();
+                    }
+                });
+                let mut recording = self.capture_sequence.is_some();
+                if ui
+                    .checkbox(&mut recording, "Record image sequence (60 fps)")
+                    .changed()
+                {
+                    if recording {
+                        if let Err(e) = self.start_capture_sequence("capture", 60.0) {
+                            log::error!("Failed to start image sequence: {}", e);
+                        }
+                    } else {
+                        self.stop_capture_sequence();
+                    }
+                }
+
                 CollapsingHeader::new("UI Debugging").show(ui, |ui| {
                     ui.checkbox(&mut self.vulkan_manager.enable_ui_wireframe, "UI Triangles");
 
@@ -550,15 +846,13 @@ impl Engine {
                     .clicked()
                 {
                     log::info!("Clearing GPU override");
-                    self.config.renderer = None;
+                    if let Some(renderer) = &mut self.config.renderer {
+                        renderer.gpu_vendor_id = None;
+                        renderer.gpu_device_id = None;
+                    }
                     write_config(&self.config);
 
-                    let args = std::env::args().collect::<Vec<_>>();
-                    std::process::Command::new(&args[0])
-                        .args(args)
-                        .spawn()
-                        .unwrap();
-                    std::process::exit(0);
+                    restart();
                 }
                 for (_, props, _) in &self.vulkan_manager.supported_devices {
                     let name = unsafe { CStr::from_ptr(props.device_name.as_ptr()) }
@@ -586,15 +880,36 @@ impl Engine {
                         self.config.renderer = Some(RendererConfig {
                             gpu_vendor_id: Some(props.vendor_id),
                             gpu_device_id: Some(props.device_id),
+                            ..self.config.renderer.take().unwrap_or_default()
                         });
                         write_config(&self.config);
 
-                        let args = std::env::args().collect::<Vec<_>>();
-                        std::process::Command::new(&args[0])
-                            .args(args)
-                            .spawn()
-                            .unwrap();
-                        std::process::exit(0);
+                        restart();
+                    }
+                }
+
+                ui.separator();
+                ui.heading("Depth Buffer");
+                ui.label("Changing the depth mode restarts the engine");
+                for mode in [DepthMode::Standard, DepthMode::ReversedInfinite] {
+                    let label = match mode {
+                        DepthMode::Standard => "Standard (24 bit)",
+                        DepthMode::ReversedInfinite => {
+                            "Reversed-Z, infinite far plane (32 bit float)"
+                        }
+                    };
+                    if ui
+                        .selectable_label(self.vulkan_manager.depth_mode == mode, label)
+                        .clicked()
+                        && self.vulkan_manager.depth_mode != mode
+                    {
+                        log::info!("Changing depth mode to {:?}", mode);
+                        self.config
+                            .renderer
+                            .get_or_insert_with(RendererConfig::default)
+                            .depth_mode = mode;
+                        write_config(&self.config);
+                        restart();
                     }
                 }
             });
@@ -623,8 +938,17 @@ impl Engine {
 
         let mut gui_context = self.gui_context.clone();
 
-        let gui_input = self.gui_state.take_egui_input(&self.window.winit_window);
+        let gui_input = match (&mut self.gui_state, &self.window) {
+            (Some(gui_state), Some(window)) => gui_state.take_egui_input(&window.winit_window),
+            _ => egui::RawInput::default(),
+        };
+        let headless = self.window.is_none();
         let (output, shapes) = gui_context.run(gui_input, |ctx| {
+            // the debug ui can't be used without a window and would only clutter the rendered frames
+            if headless {
+                return;
+            }
+
             self.render_debug_tools_window(ctx, frame_time);
 
             self.render_scene_graph(ctx);
@@ -652,8 +976,9 @@ impl Engine {
         let gui_meshes;
         {
             profile_scope!("Ui tesselation");
-            self.gui_state
-                .handle_output(&self.window.winit_window, &self.gui_context, output);
+            if let (Some(gui_state), Some(window)) = (&mut self.gui_state, &self.window) {
+                gui_state.handle_output(&window.winit_window, &self.gui_context, output);
+            }
             gui_meshes = self.gui_context.tessellate(shapes);
 
             self.ui_vertex_count = gui_meshes.iter().map(|m| m.1.vertices.len() as u32).sum();
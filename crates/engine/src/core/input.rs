@@ -61,7 +61,8 @@ impl Input {
 
     pub(crate) fn update(&mut self, event: &Event<()>, engine: &Engine) {
         // match other events only if the window is focused
-        if engine.window.is_focused() || self.events_during_unfocus {
+        let focused = engine.window.as_ref().is_some_and(|w| w.is_focused());
+        if focused || self.events_during_unfocus {
             self.handle_input(event);
         }
     }
@@ -1,7 +1,7 @@
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use crate::core::engine::EngineInit;
-use winit::event::Event;
+use winit::event::{Event, VirtualKeyCode};
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Dimensions {
@@ -154,7 +154,11 @@ pub fn start(engine_init: EngineInit) -> ! {
     let mut last_time = Instant::now();
 
     let mut engine = engine_init.engine;
-    engine.window.on_start();
+    engine
+        .window
+        .as_mut()
+        .expect("EngineInit always creates a window")
+        .on_start();
     engine_init.eventloop.run(move |event, _, controlflow| {
         *controlflow = winit::event_loop::ControlFlow::Poll;
         engine.input.borrow_mut().update(&event, &engine);
@@ -167,11 +171,15 @@ pub fn start(engine_init: EngineInit) -> ! {
                     | winit::event::WindowEvent::CursorMoved { .. }
                     | winit::event::WindowEvent::KeyboardInput { .. } => {
                         if !engine.input.borrow().get_cursor_captured() {
-                            engine.gui_state.on_event(&engine.gui_context, &event);
+                            if let Some(gui_state) = &mut engine.gui_state {
+                                gui_state.on_event(&engine.gui_context, &event);
+                            }
                         }
                     }
                     _ => {
-                        engine.gui_state.on_event(&engine.gui_context, &event);
+                        if let Some(gui_state) = &mut engine.gui_state {
+                            gui_state.on_event(&engine.gui_context, &event);
+                        }
                     }
                 }
 
@@ -180,7 +188,9 @@ pub fn start(engine_init: EngineInit) -> ! {
                         *controlflow = winit::event_loop::ControlFlow::Exit
                     }
                     winit::event::WindowEvent::Focused(state) => {
-                        engine.window.on_focus(state);
+                        if let Some(window) = &mut engine.window {
+                            window.on_focus(state);
+                        }
                     }
                     _ => {}
                 }
@@ -190,14 +200,30 @@ pub fn start(engine_init: EngineInit) -> ! {
                 #[cfg(feature = "profiler")]
                 puffin::GlobalProfiler::lock().new_frame();
 
-                engine.input.borrow_mut().handle_builtin(&mut engine.window);
+                if let Some(window) = &mut engine.window {
+                    engine.input.borrow_mut().handle_builtin(window);
+                }
+                if engine
+                    .input
+                    .borrow()
+                    .get_button_was_down(VirtualKeyCode::F12)
+                {
+                    engine.take_screenshot();
+                }
+
+                if let Some(limit) = engine.vulkan_manager.present_config().frame_rate_limit {
+                    let frame_time = Duration::from_secs_f32(1.0 / limit);
+                    let elapsed = last_time.elapsed();
+                    if elapsed < frame_time {
+                        std::thread::sleep(frame_time - elapsed);
+                    }
+                }
 
                 let now = Instant::now();
                 let delta = (now - last_time).as_secs_f32();
                 last_time = now;
 
-                engine.gameloop.update(&engine.scene, delta);
-                engine.render();
+                engine.step(delta);
                 engine.input.borrow_mut().rollover_state();
             }
             _ => {}
@@ -0,0 +1,250 @@
+use gfx_maths::*;
+
+/// An axis aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    /// Computes the smallest [`Aabb`] that contains all given points.
+    ///
+    /// Returns an empty box at the origin if no points are given.
+    pub fn from_points<'a>(points: impl IntoIterator<Item = &'a Vec3>) -> Self {
+        let mut points = points.into_iter();
+
+        let first = match points.next() {
+            Some(p) => *p,
+            None => {
+                return Self {
+                    min: Vec3::zero(),
+                    max: Vec3::zero(),
+                }
+            }
+        };
+
+        let mut min = first;
+        let mut max = first;
+        for p in points {
+            min = Vec3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z));
+            max = Vec3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z));
+        }
+
+        Self { min, max }
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+}
+
+/// A bounding sphere described by its center and radius.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    /// Computes a [`BoundingSphere`] around the center of `aabb` that contains all given points.
+    ///
+    /// The result is not the minimal bounding sphere, but it is cheap to compute and usually close enough.
+    pub fn from_points<'a>(aabb: &Aabb, points: impl IntoIterator<Item = &'a Vec3>) -> Self {
+        let center = aabb.center();
+        let radius = points
+            .into_iter()
+            .map(|p| (*p - center).sqr_magnitude())
+            .fold(0.0f32, f32::max)
+            .sqrt();
+
+        Self { center, radius }
+    }
+
+    /// Transforms the sphere with the given matrix.
+    ///
+    /// Non-uniform scaling is handled conservatively by using the largest scale factor for the radius.
+    pub fn transform(&self, matrix: Mat4) -> Self {
+        let center = matrix * Vec4::new(self.center.x, self.center.y, self.center.z, 1.0);
+
+        let scale = [
+            matrix * Vec4::new(1.0, 0.0, 0.0, 0.0),
+            matrix * Vec4::new(0.0, 1.0, 0.0, 0.0),
+            matrix * Vec4::new(0.0, 0.0, 1.0, 0.0),
+        ]
+        .iter()
+        .map(|axis| axis.x * axis.x + axis.y * axis.y + axis.z * axis.z)
+        .fold(0.0f32, f32::max)
+        .sqrt();
+
+        Self {
+            center: Vec3::new(center.x, center.y, center.z),
+            radius: self.radius * scale,
+        }
+    }
+}
+
+/// A view frustum described by six planes in view space.
+///
+/// Each plane is stored as `(normal, distance)` with the normal pointing into the frustum.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    pub planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Builds the frustum of a perspective projection looking along +Z.
+    ///
+    /// `fovy` is the vertical field of view in radians.
+    pub fn perspective(fovy: f32, aspect: f32, near: f32, far: f32) -> Self {
+        let tan_y = (fovy * 0.5).tan();
+        let tan_x = tan_y * aspect;
+
+        let len_x = (1.0 + tan_x * tan_x).sqrt();
+        let len_y = (1.0 + tan_y * tan_y).sqrt();
+
+        Self {
+            planes: [
+                // near
+                Vec4::new(0.0, 0.0, 1.0, -near),
+                // far
+                Vec4::new(0.0, 0.0, -1.0, far),
+                // left
+                Vec4::new(1.0 / len_x, 0.0, tan_x / len_x, 0.0),
+                // right
+                Vec4::new(-1.0 / len_x, 0.0, tan_x / len_x, 0.0),
+                // bottom
+                Vec4::new(0.0, 1.0 / len_y, tan_y / len_y, 0.0),
+                // top
+                Vec4::new(0.0, -1.0 / len_y, tan_y / len_y, 0.0),
+            ],
+        }
+    }
+
+    /// Builds the frustum of an orthographic projection looking along +Z.
+    ///
+    /// `width` and `height` are the extents of the view volume, which is centered around the Z axis.
+    pub fn orthographic(width: f32, height: f32, near: f32, far: f32) -> Self {
+        Self {
+            planes: [
+                // near
+                Vec4::new(0.0, 0.0, 1.0, -near),
+                // far
+                Vec4::new(0.0, 0.0, -1.0, far),
+                // left
+                Vec4::new(1.0, 0.0, 0.0, width * 0.5),
+                // right
+                Vec4::new(-1.0, 0.0, 0.0, width * 0.5),
+                // bottom
+                Vec4::new(0.0, 1.0, 0.0, height * 0.5),
+                // top
+                Vec4::new(0.0, -1.0, 0.0, height * 0.5),
+            ],
+        }
+    }
+
+    /// Transforms a view space frustum into the space that `view_matrix` maps into view space, usually world space.
+    pub fn to_world_space(&self, view_matrix: Mat4) -> Self {
+        // planes transform with the transpose of the view matrix, so each component of a world space plane
+        // is its dot product with a column of the view matrix, i.e. with a unit axis transformed into view space
+        let axes = [
+            view_matrix * Vec4::new(1.0, 0.0, 0.0, 0.0),
+            view_matrix * Vec4::new(0.0, 1.0, 0.0, 0.0),
+            view_matrix * Vec4::new(0.0, 0.0, 1.0, 0.0),
+            view_matrix * Vec4::new(0.0, 0.0, 0.0, 1.0),
+        ];
+
+        let mut planes = self.planes;
+        for plane in &mut planes {
+            let dot = |a: &Vec4| plane.x * a.x + plane.y * a.y + plane.z * a.z + plane.w * a.w;
+            *plane = Vec4::new(dot(&axes[0]), dot(&axes[1]), dot(&axes[2]), dot(&axes[3]));
+        }
+
+        Self { planes }
+    }
+
+    /// Returns true if the given view space sphere is at least partially inside the frustum.
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes.iter().all(|plane| {
+            let distance = plane.x * sphere.center.x
+                + plane.y * sphere.center.y
+                + plane.z * sphere.center.z
+                + plane.w;
+            distance >= -sphere.radius
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use gfx_maths::Vec3;
+
+    use super::{Aabb, BoundingSphere, Frustum};
+
+    #[test]
+    fn test_bounds_from_points() {
+        let points = [
+            Vec3::new(-1.0, 0.0, 2.0),
+            Vec3::new(1.0, 2.0, 4.0),
+            Vec3::new(0.0, 1.0, 3.0),
+        ];
+
+        let aabb = Aabb::from_points(&points);
+        assert_eq!(aabb.min, Vec3::new(-1.0, 0.0, 2.0));
+        assert_eq!(aabb.max, Vec3::new(1.0, 2.0, 4.0));
+
+        let sphere = BoundingSphere::from_points(&aabb, &points);
+        assert_eq!(sphere.center, Vec3::new(0.0, 1.0, 3.0));
+        assert!((sphere.radius - 3.0f32.sqrt()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_frustum_sphere() {
+        let frustum = Frustum::perspective(90.0f32.to_radians(), 1.0, 0.1, 100.0);
+
+        let inside = BoundingSphere {
+            center: Vec3::new(0.0, 0.0, 10.0),
+            radius: 1.0,
+        };
+        let behind = BoundingSphere {
+            center: Vec3::new(0.0, 0.0, -10.0),
+            radius: 1.0,
+        };
+        let beyond_far = BoundingSphere {
+            center: Vec3::new(0.0, 0.0, 105.0),
+            radius: 1.0,
+        };
+        let left = BoundingSphere {
+            center: Vec3::new(-20.0, 0.0, 10.0),
+            radius: 1.0,
+        };
+        let touching_left = BoundingSphere {
+            center: Vec3::new(-10.5, 0.0, 10.0),
+            radius: 1.0,
+        };
+
+        assert!(frustum.intersects_sphere(&inside));
+        assert!(!frustum.intersects_sphere(&behind));
+        assert!(!frustum.intersects_sphere(&beyond_far));
+        assert!(!frustum.intersects_sphere(&left));
+        assert!(frustum.intersects_sphere(&touching_left));
+    }
+
+    #[test]
+    fn test_orthographic_frustum_sphere() {
+        let frustum = Frustum::orthographic(8.0, 4.0, 0.1, 100.0);
+
+        let sphere = |x: f32, y: f32, z: f32| BoundingSphere {
+            center: Vec3::new(x, y, z),
+            radius: 0.5,
+        };
+
+        // unlike perspective frustums, the extent does not grow with the distance
+        assert!(frustum.intersects_sphere(&sphere(3.0, 1.5, 1.0)));
+        assert!(frustum.intersects_sphere(&sphere(3.0, 1.5, 90.0)));
+        assert!(frustum.intersects_sphere(&sphere(-4.4, 0.0, 50.0)));
+        assert!(!frustum.intersects_sphere(&sphere(-4.6, 0.0, 50.0)));
+        assert!(!frustum.intersects_sphere(&sphere(0.0, 2.6, 50.0)));
+        assert!(!frustum.intersects_sphere(&sphere(0.0, 0.0, -1.0)));
+    }
+}
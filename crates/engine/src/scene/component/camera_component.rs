@@ -1,18 +1,98 @@
-use std::rc::{Rc, Weak};
+use std::{
+    cell::{Cell, RefCell},
+    fmt,
+    rc::{Rc, Weak},
+};
 
-use egui::TextEdit;
-use gfx_maths::{Mat4, Vec3};
+use egui::{ComboBox, DragValue, Slider};
+use gfx_maths::{Mat4, Quaternion, Vec3};
 
-use crate::scene::entity::Entity;
+use crate::{
+    scene::{bounds::Frustum, entity::Entity},
+    vulkan::{
+        pp_effect::PPEffect, shader_structs::CameraUniformData, texture::Texture2D, DepthMode,
+    },
+};
 
 use super::Component;
 
-#[derive(Debug)]
+/// The smallest allowed distance of the near clipping plane.
+const MIN_NEAR: f32 = 0.001;
+/// The far clipping plane is at least this factor farther away than the near plane.
+const MIN_FAR_RATIO: f32 = 1.001;
+
+/// Moves the clipping planes to the closest valid configuration with `MIN_NEAR <= near < far`.
+fn clamp_clip_planes(near: f32, far: f32) -> (f32, f32) {
+    // written so that NaN is replaced as well
+    let near = if near >= MIN_NEAR {
+        near.min(f32::MAX / MIN_FAR_RATIO)
+    } else {
+        MIN_NEAR
+    };
+    let far = if far >= near * MIN_FAR_RATIO {
+        far.min(f32::MAX)
+    } else {
+        near * MIN_FAR_RATIO
+    };
+    (near, far)
+}
+
+/// The region of the window a camera renders into, in normalized coordinates with the origin at the top left corner.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Viewport {
+    /// Covers the whole window.
+    pub const FULL: Self = Self {
+        x: 0.0,
+        y: 0.0,
+        width: 1.0,
+        height: 1.0,
+    };
+
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self::FULL
+    }
+}
+
+/// How a camera projects the scene onto its image.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Projection {
+    /// Objects get smaller with distance, uses the vertical field of view of the camera
+    Perspective,
+    /// Objects keep their size regardless of the distance, uses the orthographic size of the camera
+    Orthographic,
+}
+
 pub struct CameraComponent {
     entity: Weak<Entity>,
-    near: f32,
-    far: f32,
-    fovy: f32,
+    projection: Cell<Projection>,
+    near: Cell<f32>,
+    far: Cell<f32>,
+    /// vertical field of view in degrees
+    fovy: Cell<f32>,
+    /// height of the orthographic view volume in world units
+    orthographic_size: Cell<f32>,
+    render_target: RefCell<Option<Rc<Texture2D>>>,
+    pp_effects: RefCell<Option<Vec<Rc<PPEffect>>>>,
+    viewport: Cell<Viewport>,
+    priority: Cell<i32>,
 }
 
 impl Component for CameraComponent {
@@ -22,13 +102,19 @@ impl Component for CameraComponent {
     {
         let res = Rc::new(Self {
             entity: Rc::downgrade(entity),
-            near: 0.01,
-            far: 1000.0,
-            fovy: 60.0,
+            projection: Cell::new(Projection::Perspective),
+            near: Cell::new(0.01),
+            far: Cell::new(1000.0),
+            fovy: Cell::new(60.0),
+            orthographic_size: Cell::new(10.0),
+            render_target: RefCell::new(None),
+            pp_effects: RefCell::new(None),
+            viewport: Cell::new(Viewport::FULL),
+            priority: Cell::new(0),
         });
 
         if let Some(scene) = entity.scene.upgrade() {
-            scene.set_main_camera(Rc::downgrade(&res));
+            scene.add_camera(Rc::downgrade(&res));
         }
 
         res
@@ -43,55 +129,311 @@ impl Component for CameraComponent {
     }
 
     fn render_inspector(&self, ui: &mut egui::Ui) {
-        ui.horizontal(|ui| {
-            ui.label("Near");
-            let mut text = self.near.to_string();
-            ui.add_enabled(false, TextEdit::singleline(&mut text));
-        });
+        let mut projection = self.projection.get();
+        ComboBox::from_label("Projection")
+            .selected_text(format!("{:?}", projection))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut projection, Projection::Perspective, "Perspective");
+                ui.selectable_value(&mut projection, Projection::Orthographic, "Orthographic");
+            });
+        self.projection.set(projection);
 
-        ui.horizontal(|ui| {
-            ui.label("Far");
-            let mut text = self.far.to_string();
-            ui.add_enabled(false, TextEdit::singleline(&mut text));
-        });
+        match projection {
+            Projection::Perspective => {
+                let mut fovy = self.fovy.get();
+                ui.add(Slider::new(&mut fovy, 1.0..=179.0).text("Vertical FOV"));
+                self.fovy.set(fovy);
+            }
+            Projection::Orthographic => {
+                let mut size = self.orthographic_size.get();
+                ui.add(
+                    DragValue::new(&mut size)
+                        .speed(0.1)
+                        .clamp_range(0.01..=f32::MAX)
+                        .prefix("Size: "),
+                );
+                self.orthographic_size.set(size);
+            }
+        }
 
+        // the near plane has to stay in front of the far plane
+        let (mut near, mut far) = (self.near.get(), self.far.get());
         ui.horizontal(|ui| {
-            ui.label("Vertical FOV");
-            let mut text = self.fovy.to_string();
-            ui.add_enabled(false, TextEdit::singleline(&mut text));
+            ui.add(
+                DragValue::new(&mut near)
+                    .speed(0.01)
+                    .clamp_range(MIN_NEAR..=far / MIN_FAR_RATIO)
+                    .prefix("Near: "),
+            );
+            ui.add(
+                DragValue::new(&mut far)
+                    .clamp_range(near * MIN_FAR_RATIO..=f32::MAX)
+                    .prefix("Far: "),
+            );
         });
+        self.set_clip_planes(near, far);
+
+        if let Some(target) = &*self.render_target.borrow() {
+            ui.label(format!(
+                "Renders to a {}x{} texture",
+                target.width, target.height
+            ));
+        } else {
+            let mut vp = self.viewport.get();
+            ui.label("Viewport");
+            ui.horizontal(|ui| {
+                for (value, prefix) in [
+                    (&mut vp.x, "X: "),
+                    (&mut vp.y, "Y: "),
+                    (&mut vp.width, "W: "),
+                    (&mut vp.height, "H: "),
+                ] {
+                    ui.add(
+                        DragValue::new(value)
+                            .speed(0.01)
+                            .clamp_range(0.0..=1.0)
+                            .prefix(prefix),
+                    );
+                }
+            });
+            self.viewport.set(vp);
+
+            let mut priority = self.priority.get();
+            if ui
+                .add(DragValue::new(&mut priority).prefix("Priority: "))
+                .changed()
+            {
+                self.set_priority(priority);
+            }
+        }
     }
 }
 
-#[repr(C)]
-pub(crate) struct CameraUniformData {
-    pub view_matrix: Mat4,
-    pub projection_matrix: Mat4,
-    pub inv_view_matrix: Mat4,
-    pub inv_projection_matrix: Mat4,
-    pub pos: Vec3,
+impl fmt::Debug for CameraComponent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CameraComponent")
+            .field("projection", &self.projection.get())
+            .field("near", &self.near.get())
+            .field("far", &self.far.get())
+            .field("fovy", &self.fovy.get())
+            .field("orthographic_size", &self.orthographic_size.get())
+            .field("viewport", &self.viewport.get())
+            .field("priority", &self.priority.get())
+            .finish()
+    }
 }
 
 impl CameraComponent {
-    pub(crate) fn get_cam_data(&self, aspect: f32) -> CameraUniformData {
+    pub(crate) fn get_cam_data(&self, aspect: f32, depth_mode: DepthMode) -> CameraUniformData {
         let entity = self.entity.upgrade().unwrap();
 
+        let (near, far) = (self.near.get(), self.far.get());
+        let (mut projection_matrix, mut inv_projection_matrix) = match self.projection.get() {
+            Projection::Perspective => {
+                let fovy = self.fovy.get().to_radians();
+                (
+                    Mat4::perspective_vulkan(fovy, near, far, aspect),
+                    Mat4::inverse_perspective_vulkan(fovy, near, far, aspect),
+                )
+            }
+            Projection::Orthographic => {
+                let half_height = self.orthographic_size.get() * 0.5;
+                let half_width = half_height * aspect;
+                (
+                    Mat4::orthographic_vulkan(
+                        -half_width,
+                        half_width,
+                        -half_height,
+                        half_height,
+                        near,
+                        far,
+                    ),
+                    Mat4::inverse_orthographic_vulkan(
+                        -half_width,
+                        half_width,
+                        -half_height,
+                        half_height,
+                        near,
+                        far,
+                    ),
+                )
+            }
+        };
+
+        if depth_mode == DepthMode::ReversedInfinite {
+            // clip space depth z becomes w - scale * z, which maps [0, 1] to [1, 0] for a scale of 1.
+            // For perspective projections, z / w is 1 - near / view_z at this scale, so the far plane
+            // moves to infinity and depth becomes near / view_z.
+            let scale = match self.projection.get() {
+                Projection::Perspective => (far - near) / far,
+                Projection::Orthographic => 1.0,
+            };
+            let remap = Mat4::local_to_world(
+                Vec3::new(0.0, 0.0, 1.0),
+                Quaternion::identity(),
+                Vec3::new(1.0, 1.0, -scale),
+            );
+            let inv_remap = Mat4::local_to_world(
+                Vec3::new(0.0, 0.0, 1.0 / scale),
+                Quaternion::identity(),
+                Vec3::new(1.0, 1.0, -1.0 / scale),
+            );
+            projection_matrix = remap * projection_matrix;
+            inv_projection_matrix = inv_projection_matrix * inv_remap;
+        }
+
         CameraUniformData {
             view_matrix: entity.get_view_matrix(),
-            projection_matrix: Mat4::perspective_vulkan(
-                self.fovy.to_radians(),
-                self.near,
-                self.far,
-                aspect,
-            ),
+            proj_matrix: projection_matrix,
             inv_view_matrix: entity.get_inverse_view_matrix(),
-            inv_projection_matrix: Mat4::inverse_perspective_vulkan(
-                self.fovy.to_radians(),
-                self.near,
-                self.far,
-                aspect,
-            ),
-            pos: entity.get_global_position(),
+            inv_proj_matrix: inv_projection_matrix,
+            position: entity.get_global_position(),
+        }
+    }
+
+    /// Returns the view space frustum of this camera.
+    pub(crate) fn get_frustum(&self, aspect: f32, depth_mode: DepthMode) -> Frustum {
+        let (near, far) = (self.near.get(), self.far.get());
+        match self.projection.get() {
+            Projection::Perspective => {
+                // an infinite distance would turn into NaN when transforming the frustum
+                let far = match depth_mode {
+                    DepthMode::Standard => far,
+                    DepthMode::ReversedInfinite => f32::MAX,
+                };
+                Frustum::perspective(self.fovy.get().to_radians(), aspect, near, far)
+            }
+            Projection::Orthographic => {
+                let size = self.orthographic_size.get();
+                Frustum::orthographic(size * aspect, size, near, far)
+            }
+        }
+    }
+
+    pub fn set_projection(&self, projection: Projection) {
+        self.projection.set(projection);
+    }
+
+    pub fn get_projection(&self) -> Projection {
+        self.projection.get()
+    }
+
+    /// Sets the distances of the near and far clipping planes.
+    ///
+    /// `near` is clamped to at least 0.001 and `far` is moved behind `near` if necessary.
+    pub fn set_clip_planes(&self, near: f32, far: f32) {
+        let (near, far) = clamp_clip_planes(near, far);
+        self.near.set(near);
+        self.far.set(far);
+    }
+
+    pub fn get_near(&self) -> f32 {
+        self.near.get()
+    }
+
+    /// Returns the distance of the far clipping plane, which perspective cameras ignore with
+    /// [`DepthMode::ReversedInfinite`].
+    pub fn get_far(&self) -> f32 {
+        self.far.get()
+    }
+
+    /// Sets the vertical field of view in degrees, which is used by [`Projection::Perspective`].
+    pub fn set_fovy(&self, fovy: f32) {
+        self.fovy.set(fovy);
+    }
+
+    pub fn get_fovy(&self) -> f32 {
+        self.fovy.get()
+    }
+
+    /// Sets the height of the visible area in world units, which is used by [`Projection::Orthographic`].
+    ///
+    /// The width follows from the aspect ratio of the camera's viewport or render target.
+    pub fn set_orthographic_size(&self, size: f32) {
+        self.orthographic_size.set(size);
+    }
+
+    pub fn get_orthographic_size(&self) -> f32 {
+        self.orthographic_size.get()
+    }
+
+    /// Renders this camera into `target` instead of the window, or into the window again for `None`.
+    ///
+    /// The texture is rendered at its own resolution and can be used by materials like any other texture,
+    /// e.g. with [`Material::set_texture`](crate::scene::material::Material::set_texture).
+    /// [`Texture2D::new_render_target`] creates a texture that is black until it was rendered to.
+    pub fn set_render_target(&self, target: Option<Rc<Texture2D>>) {
+        *self.render_target.borrow_mut() = target;
+
+        self.update_main_camera();
+    }
+
+    pub fn get_render_target(&self) -> Option<Rc<Texture2D>> {
+        self.render_target.borrow().clone()
+    }
+
+    /// Sets the post processing effects of this camera, which are applied in order.
+    ///
+    /// With `None`, the effects registered with
+    /// [`VulkanManager::register_pp_effect`](crate::vulkan::VulkanManager::register_pp_effect) are used.
+    pub fn set_pp_effects(&self, effects: Option<Vec<Rc<PPEffect>>>) {
+        *self.pp_effects.borrow_mut() = effects;
+    }
+
+    pub fn get_pp_effects(&self) -> Option<Vec<Rc<PPEffect>>> {
+        self.pp_effects.borrow().clone()
+    }
+
+    /// Sets the region of the window this camera renders into, which is ignored while it has a render target.
+    ///
+    /// The aspect ratio of the camera follows the size of the region in pixels.
+    pub fn set_viewport(&self, viewport: Viewport) {
+        self.viewport.set(viewport);
+    }
+
+    pub fn get_viewport(&self) -> Viewport {
+        self.viewport.get()
+    }
+
+    /// Sets the order in which cameras are drawn into the window, higher priorities are drawn on top of lower ones.
+    ///
+    /// Cameras with the same priority are drawn in the order they were created.
+    /// The camera with the highest priority is the main camera, e.g. for HDR captures.
+    pub fn set_priority(&self, priority: i32) {
+        self.priority.set(priority);
+        self.update_main_camera();
+    }
+
+    pub fn get_priority(&self) -> i32 {
+        self.priority.get()
+    }
+
+    fn update_main_camera(&self) {
+        if let Some(scene) = self.entity.upgrade().and_then(|e| e.scene.upgrade()) {
+            scene.update_main_camera();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{clamp_clip_planes, MIN_NEAR};
+
+    #[test]
+    fn test_clamp_clip_planes() {
+        assert_eq!(clamp_clip_planes(0.1, 100.0), (0.1, 100.0));
+
+        for (near, far) in [
+            (0.0, 100.0),
+            (-1.0, -2.0),
+            (10.0, 10.0),
+            (10.0, 1.0),
+            (f32::NAN, f32::NAN),
+            (f32::INFINITY, f32::INFINITY),
+            (f32::MAX, f32::MAX),
+        ] {
+            let (near, far) = clamp_clip_planes(near, far);
+            assert!(MIN_NEAR <= near && near < far && far.is_finite());
         }
     }
 }
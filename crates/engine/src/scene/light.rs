@@ -1,5 +1,7 @@
 use gfx_maths::*;
 
+use crate::vulkan::shader_structs::{DirectionalLightData, PointLightData};
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct DirectionalLight {
@@ -31,3 +33,21 @@ impl From<DirectionalLight> for Light {
         Light::Directional(value)
     }
 }
+
+impl From<&PointLight> for PointLightData {
+    fn from(light: &PointLight) -> Self {
+        PointLightData {
+            position: light.position,
+            luminous_flux: light.luminous_flux,
+        }
+    }
+}
+
+impl From<&DirectionalLight> for DirectionalLightData {
+    fn from(light: &DirectionalLight) -> Self {
+        DirectionalLightData {
+            direction_to_light: light.direction,
+            irradiance: light.illuminance,
+        }
+    }
+}
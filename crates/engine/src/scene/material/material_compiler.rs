@@ -1,8 +1,12 @@
 use ash::vk;
 use gpu_allocator::{vulkan::Allocation, MemoryLocation};
 
+use super::SpecializationValue;
 use crate::vulkan::{
-    allocator::Allocator, descriptor_manager::DescriptorData, error::GraphicsResult,
+    allocator::Allocator,
+    descriptor_manager::DescriptorData,
+    error::{GraphicsError, GraphicsResult},
+    pipeline,
 };
 
 pub(crate) fn compile_descriptor_set_layout(
@@ -11,15 +15,9 @@ pub(crate) fn compile_descriptor_set_layout(
 ) -> Result<vk::DescriptorSetLayout, vk::Result> {
     let mut bindings = Vec::with_capacity(resources.len());
     for (i, r) in resources.iter().enumerate() {
-        let vk_type = match r {
-            DescriptorData::None => continue,
-            DescriptorData::UniformBuffer { .. } => vk::DescriptorType::UNIFORM_BUFFER,
-            DescriptorData::DynamicUniformBuffer { .. } => {
-                vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC
-            }
-            DescriptorData::ImageSampler { .. } => vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-            DescriptorData::StorageBuffer { .. } => vk::DescriptorType::STORAGE_BUFFER,
-            DescriptorData::InputAttachment { .. } => vk::DescriptorType::INPUT_ATTACHMENT,
+        let vk_type = match r.descriptor_type() {
+            Some(vk_type) => vk_type,
+            None => continue,
         };
 
         bindings.push(
@@ -43,20 +41,53 @@ pub(crate) fn compile_descriptor_set_layout(
 pub(crate) fn compile_pipeline_layout(
     device: &ash::Device,
     layouts: &[vk::DescriptorSetLayout],
+    push_constants: &[ve_shader_reflect::PushConstantRange],
 ) -> Result<vk::PipelineLayout, vk::Result> {
-    // all devices must at least support 128 bytes of push constants, so this is safe
-    let push_constants = [vk::PushConstantRange::builder()
-        .stage_flags(vk::ShaderStageFlags::VERTEX)
-        .offset(0)
-        .size(128)
-        .build()];
+    let push_constant_ranges = pipeline::push_constant_ranges(push_constants);
     let layout_info = vk::PipelineLayoutCreateInfo::builder()
         .set_layouts(layouts)
-        .push_constant_ranges(&push_constants)
+        .push_constant_ranges(&push_constant_ranges)
         .build();
     unsafe { device.create_pipeline_layout(&layout_info, None) }
 }
 
+/// Builds the map entries and data of a [`vk::SpecializationInfo`] from the values of named specialization constants.
+///
+/// Constants without a value keep the default declared in the shader.
+pub(crate) fn compile_specialization(
+    constants: &[ve_shader_reflect::SpecializationConstant],
+    values: &[(String, SpecializationValue)],
+) -> GraphicsResult<(Vec<vk::SpecializationMapEntry>, Vec<u8>)> {
+    let mut entries = Vec::with_capacity(values.len());
+    let mut data = Vec::with_capacity(values.len() * 4);
+
+    for (name, value) in values {
+        let constant = constants
+            .iter()
+            .find(|c| &c.name == name)
+            .ok_or_else(|| GraphicsError::InvalidSpecializationConstant(name.clone()))?;
+        let bits = match (&constant.kind, value) {
+            (ve_shader_reflect::BlockMemberType::Bool, SpecializationValue::Bool(v)) => *v as u32,
+            (ve_shader_reflect::BlockMemberType::Int, SpecializationValue::Int(v)) => *v as u32,
+            (ve_shader_reflect::BlockMemberType::UInt, SpecializationValue::UInt(v)) => *v,
+            (ve_shader_reflect::BlockMemberType::Float, SpecializationValue::Float(v)) => {
+                v.to_bits()
+            }
+            _ => return Err(GraphicsError::InvalidSpecializationConstant(name.clone())),
+        };
+
+        // all supported types, including VkBool32, are 4 bytes large
+        entries.push(vk::SpecializationMapEntry {
+            constant_id: constant.id,
+            offset: data.len() as u32,
+            size: 4,
+        });
+        data.extend_from_slice(&bits.to_ne_bytes());
+    }
+
+    Ok((entries, data))
+}
+
 pub(crate) fn compile_resources(
     data: &[DescriptorData],
     allocator: &Allocator,
@@ -97,3 +128,52 @@ pub(crate) fn compile_resources(
 
     Ok((resources, allocations))
 }
+
+#[cfg(test)]
+mod test {
+    use ve_shader_reflect::{BlockMemberType, SpecializationConstant};
+
+    use super::{compile_specialization, SpecializationValue};
+    use crate::vulkan::error::GraphicsError;
+
+    fn constant(id: u32, kind: BlockMemberType, name: &str) -> SpecializationConstant {
+        SpecializationConstant {
+            id,
+            kind,
+            default_value: 0,
+            name: name.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_compile_specialization() {
+        let constants = [
+            constant(0, BlockMemberType::Bool, "USE_ALBEDO_TEX"),
+            constant(2, BlockMemberType::Float, "ALPHA_CUTOFF"),
+        ];
+        let values = [
+            (
+                String::from("ALPHA_CUTOFF"),
+                SpecializationValue::Float(0.25),
+            ),
+            (
+                String::from("USE_ALBEDO_TEX"),
+                SpecializationValue::Bool(true),
+            ),
+        ];
+        let (entries, data) = compile_specialization(&constants, &values).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!((entries[0].constant_id, entries[0].offset), (2, 0));
+        assert_eq!((entries[1].constant_id, entries[1].offset), (0, 4));
+        assert_eq!(data[0..4], 0.25f32.to_ne_bytes());
+        assert_eq!(data[4..8], 1u32.to_ne_bytes());
+
+        let wrong_type = [(String::from("ALPHA_CUTOFF"), SpecializationValue::Int(1))];
+        assert!(matches!(
+            compile_specialization(&constants, &wrong_type),
+            Err(GraphicsError::InvalidSpecializationConstant(name)) if name == "ALPHA_CUTOFF"
+        ));
+        let unknown = [(String::from("LAYER"), SpecializationValue::UInt(1))];
+        assert!(compile_specialization(&constants, &unknown).is_err());
+    }
+}
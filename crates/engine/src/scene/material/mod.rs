@@ -1,27 +1,157 @@
 use ash::vk;
 use gfx_maths::*;
 use gpu_allocator::vulkan::Allocation;
-use std::{cell::RefCell, collections::HashMap, mem::size_of, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    mem::{size_of, size_of_val},
+    rc::{Rc, Weak},
+};
 
 use crate::vulkan::{
     allocator::Allocator,
     descriptor_manager::DescriptorData,
     error::{GraphicsError, GraphicsResult},
     lighting_pipeline::LightingPipeline,
-    pipeline,
+    pipeline::{self, VertexLayout},
+    render_order::SortIdCounter,
     texture::Texture2D,
+    DepthMode,
 };
 
 mod material_compiler;
 
+static PIPELINE_SORT_IDS: SortIdCounter = SortIdCounter::new();
+static MATERIAL_SORT_IDS: SortIdCounter = SortIdCounter::new();
+
+thread_local! {
+    /// All MaterialPipelines that are alive, so that they can be reloaded when their shader changes.
+    static LIVE_PIPELINES: RefCell<Vec<Weak<MaterialPipeline>>> = const { RefCell::new(Vec::new()) };
+}
+
 /// Description of a single named property in a shader
+#[derive(PartialEq)]
 enum MaterialProperty {
     Unsupported,
-    Float { binding: u32, offset: u32 },
-    Vec2 { binding: u32, offset: u32 },
-    Vec3 { binding: u32, offset: u32 },
-    Vec4 { binding: u32, offset: u32 },
-    Sampler2D { binding: u32 },
+    Float {
+        binding: u32,
+        offset: u32,
+    },
+    Vec2 {
+        binding: u32,
+        offset: u32,
+    },
+    Vec3 {
+        binding: u32,
+        offset: u32,
+    },
+    Vec4 {
+        binding: u32,
+        offset: u32,
+    },
+    Int {
+        binding: u32,
+        offset: u32,
+    },
+    UInt {
+        binding: u32,
+        offset: u32,
+    },
+    /// An ivec2, ivec3 or ivec4, indicated by `count`
+    IntVector {
+        binding: u32,
+        offset: u32,
+        count: u32,
+    },
+    /// A uvec2, uvec3 or uvec4, indicated by `count`
+    UIntVector {
+        binding: u32,
+        offset: u32,
+        count: u32,
+    },
+    Sampler2D {
+        binding: u32,
+    },
+}
+
+impl MaterialProperty {
+    /// Returns the binding, offset and size of a property stored in a uniform buffer.
+    fn uniform_range(&self) -> Option<(u32, u32, usize)> {
+        match *self {
+            MaterialProperty::Float { binding, offset } => {
+                Some((binding, offset, size_of::<f32>()))
+            }
+            MaterialProperty::Vec2 { binding, offset } => {
+                Some((binding, offset, size_of::<Vec2>()))
+            }
+            MaterialProperty::Vec3 { binding, offset } => {
+                Some((binding, offset, size_of::<Vec3>()))
+            }
+            MaterialProperty::Vec4 { binding, offset } => {
+                Some((binding, offset, size_of::<Vec4>()))
+            }
+            MaterialProperty::Int { binding, offset } => Some((binding, offset, size_of::<i32>())),
+            MaterialProperty::UInt { binding, offset } => Some((binding, offset, size_of::<u32>())),
+            MaterialProperty::IntVector {
+                binding,
+                offset,
+                count,
+            } => Some((binding, offset, count as usize * size_of::<i32>())),
+            MaterialProperty::UIntVector {
+                binding,
+                offset,
+                count,
+            } => Some((binding, offset, count as usize * size_of::<u32>())),
+            MaterialProperty::Unsupported | MaterialProperty::Sampler2D { .. } => None,
+        }
+    }
+
+    /// Returns true if both properties have the same type, regardless of where they are stored.
+    fn same_type(&self, other: &MaterialProperty) -> bool {
+        match (self, other) {
+            (
+                MaterialProperty::IntVector { count, .. },
+                MaterialProperty::IntVector { count: other, .. },
+            )
+            | (
+                MaterialProperty::UIntVector { count, .. },
+                MaterialProperty::UIntVector { count: other, .. },
+            ) => count == other,
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
+
+/// The pipelines created by [`MaterialPipeline::reload`], and the new layouts if the properties changed.
+struct Rebuilt {
+    pipeline: vk::Pipeline,
+    pipeline_wireframe: vk::Pipeline,
+    layout: Option<RebuiltLayout>,
+}
+
+struct RebuiltLayout {
+    pipeline_layout: vk::PipelineLayout,
+    set_layout: vk::DescriptorSetLayout,
+    properties: HashMap<String, MaterialProperty>,
+    resource_infos: Vec<DescriptorData>,
+}
+
+impl RebuiltLayout {
+    fn destroy(self, device: &ash::Device) {
+        unsafe {
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+            device.destroy_descriptor_set_layout(self.set_layout, None);
+        }
+    }
+}
+
+/// The value of a specialization constant, e.g. `layout (constant_id = 0) const bool USE_ALBEDO_TEX = true;`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpecializationValue {
+    Bool(bool),
+    Int(i32),
+    UInt(u32),
+    Float(f32),
 }
 
 /// A MaterialPipeline represents a GPass shader and its corresponding material properties.
@@ -43,15 +173,42 @@ enum MaterialProperty {
 /// ```
 /// In the above code, a MaterialProperty named "albedo" will be exposed.
 /// This means, multiple variables with the same name in different uniform blocks will clash in the material properties.
+///
+/// Members of nested structs are named after their path, e.g. `light.color` or `lights[1].color`,
+/// and elements of arrays after their index, e.g. `weights[1]`.
+///
+/// # Vertex inputs
+/// The vertex shader may read any of the mesh attributes `position`, `color`, `normal` and `uv`,
+/// which are matched to its inputs by name.
+///
+/// # Specialization constants
+/// Shader variants, e.g. with or without an albedo texture, are created from the same shader
+/// by passing different values for its specialization constants, which are identified by their names.
+/// Constants without a value keep the default declared in the shader.
 pub struct MaterialPipeline {
     device: Rc<ash::Device>,
     allocator: Rc<Allocator>,
-    pipeline: vk::Pipeline,
-    pipeline_wireframe: vk::Pipeline,
-    pipeline_layout: vk::PipelineLayout,
-    descriptor_set_layout: vk::DescriptorSetLayout,
-    properties: HashMap<String, MaterialProperty>,
-    resource_infos: Vec<DescriptorData>,
+    pipeline: Cell<vk::Pipeline>,
+    pipeline_wireframe: Cell<vk::Pipeline>,
+    pipeline_layout: Cell<vk::PipelineLayout>,
+    descriptor_set_layout: Cell<vk::DescriptorSetLayout>,
+    /// descriptor set layouts replaced by [`Self::reload`], kept alive so that their handles aren't reused
+    /// while the descriptor manager still caches sets allocated from them
+    retired_set_layouts: RefCell<Vec<vk::DescriptorSetLayout>>,
+    frame_data_layout: vk::DescriptorSetLayout,
+    properties: RefCell<HashMap<String, MaterialProperty>>,
+    resource_infos: RefCell<Vec<DescriptorData>>,
+    /// all Materials created from this pipeline that are alive, so that their values can be migrated on reload
+    materials: RefCell<Vec<Weak<Material>>>,
+    push_constants: Vec<ve_shader_reflect::PushConstantRange>,
+    specialization: Vec<(String, SpecializationValue)>,
+    sort_id: u32,
+    shader: String,
+    renderpass: vk::RenderPass,
+    depth_mode: DepthMode,
+    stencil_id: u8,
+    /// the layout of the vertices of meshes drawn with this pipeline
+    vertex_layout: &'static VertexLayout,
 }
 
 impl MaterialPipeline {
@@ -65,15 +222,30 @@ impl MaterialPipeline {
     /// - `shader`: Name of the shader to use for this pipeline (minus the .glsl extension)
     /// - `frame_data_layout`: A DescriptorSetLayout describing the layout of descriptor set 0 of the pipeline (currently used for Camera matrices)
     /// - `renderpass`: The Deferred RenderPass (of which subpass 0 will be used for this pipeline)
+    /// - `depth_mode`: The [`DepthMode`] of the Deferred RenderPass, see [`VulkanManager::depth_mode`](crate::vulkan::VulkanManager::depth_mode)
     /// - `lighing_pipeline`: The [LightingPipeline] which will be used in the Deferred Resolve Pass for Materials created with this MaterialPipeline
+    /// - `specialization`: Values of the shaders specialization constants by name, see [`MaterialPipeline`]
+    ///
+    /// # Errors
+    /// - [`GraphicsError::MissingVertexAttribute`] when the vertex shader has an input that meshes do not provide
+    /// - [`GraphicsError::InvalidVertexAttributeType`] when an input of the vertex shader has a different type than the mesh attribute
+    /// - [`GraphicsError::InvalidSpecializationConstant`] when the shader has no specialization constant with a given name or it has a different type
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         device: Rc<ash::Device>,
         allocator: Rc<Allocator>,
         shader: &str,
         frame_data_layout: vk::DescriptorSetLayout,
         renderpass: vk::RenderPass,
+        depth_mode: DepthMode,
         lighting_pipeline: &LightingPipeline,
+        specialization: &[(&str, SpecializationValue)],
     ) -> GraphicsResult<Rc<MaterialPipeline>> {
+        let specialization: Vec<_> = specialization
+            .iter()
+            .map(|(name, value)| ((*name).to_owned(), *value))
+            .collect();
+
         let mut vertexshader_code = Vec::new();
         let mut fragmentshader_code = Vec::new();
         let (vertex_shader, fragment_shader) = pipeline::create_shader_modules(
@@ -83,10 +255,78 @@ impl MaterialPipeline {
             &mut fragmentshader_code,
         )?;
 
-        let refl_vertex = ve_shader_reflect::reflect_shader(&vertexshader_code)?;
-        let refl_fragment = ve_shader_reflect::reflect_shader(&fragmentshader_code)?;
-        let refl = ve_shader_reflect::merge(refl_vertex, &refl_fragment, false)?;
+        let refl = pipeline::reflect_shader_modules(&vertexshader_code, &fragmentshader_code)?;
+        let (properties, resource_infos) = Self::reflect_properties(&refl);
+
+        let descriptor_set_layout =
+            material_compiler::compile_descriptor_set_layout(device.as_ref(), &resource_infos)?;
+        let pipeline_layout = material_compiler::compile_pipeline_layout(
+            device.as_ref(),
+            &[frame_data_layout, descriptor_set_layout],
+            &refl.push_constants,
+        )?;
+
+        let vertex_attributes =
+            pipeline::vertex_attributes(shader, &refl.inputs, &VertexLayout::MESH)?;
+        let (map_entries, data) = material_compiler::compile_specialization(
+            &refl.specialization_constants,
+            &specialization,
+        )?;
+        let specialization_info = vk::SpecializationInfo::builder()
+            .map_entries(&map_entries)
+            .data(&data);
+        let (pipeline, pipeline_wireframe) = Self::create_pipelines(
+            &device,
+            pipeline_layout,
+            renderpass,
+            depth_mode,
+            lighting_pipeline.stencil_id,
+            &vertex_attributes,
+            &VertexLayout::MESH,
+            &specialization_info,
+            vertex_shader,
+            fragment_shader,
+        )?;
+
+        unsafe {
+            device.destroy_shader_module(vertex_shader, None);
+            device.destroy_shader_module(fragment_shader, None);
+        }
+
+        let material_pipeline = Rc::new(MaterialPipeline {
+            device,
+            allocator,
+            pipeline: Cell::new(pipeline),
+            pipeline_wireframe: Cell::new(pipeline_wireframe),
+            pipeline_layout: Cell::new(pipeline_layout),
+            descriptor_set_layout: Cell::new(descriptor_set_layout),
+            retired_set_layouts: RefCell::new(Vec::new()),
+            frame_data_layout,
+            properties: RefCell::new(properties),
+            resource_infos: RefCell::new(resource_infos),
+            materials: RefCell::new(Vec::new()),
+            push_constants: refl.push_constants,
+            specialization,
+            sort_id: PIPELINE_SORT_IDS.next(),
+            shader: shader.to_owned(),
+            renderpass,
+            depth_mode,
+            stencil_id: lighting_pipeline.stencil_id,
+            vertex_layout: &VertexLayout::MESH,
+        });
+        LIVE_PIPELINES.with(|pipelines| {
+            pipelines
+                .borrow_mut()
+                .push(Rc::downgrade(&material_pipeline));
+        });
+
+        Ok(material_pipeline)
+    }
 
+    /// Reflects the material properties of set 1 and the descriptors they are stored in.
+    fn reflect_properties(
+        refl: &ve_shader_reflect::ShaderInfo,
+    ) -> (HashMap<String, MaterialProperty>, Vec<DescriptorData>) {
         let mut properties = HashMap::new();
         let mut resource_infos = Vec::new();
         for binding in &refl.set_bindings {
@@ -95,40 +335,16 @@ impl MaterialPipeline {
             }
 
             let res_info = match &binding.data {
+                _ if binding.count != 1 => DescriptorData::None,
                 ve_shader_reflect::SetBindingData::UniformBuffer { layout } => {
                     for property in &layout.members {
-                        let prop = match property.kind {
-                            ve_shader_reflect::BlockMemberType::Unsupported => {
-                                MaterialProperty::Unsupported
-                            }
-                            ve_shader_reflect::BlockMemberType::Float => MaterialProperty::Float {
-                                binding: binding.binding,
-                                offset: property.offset,
-                            },
-                            ve_shader_reflect::BlockMemberType::FloatVector(2) => {
-                                MaterialProperty::Vec2 {
-                                    binding: binding.binding,
-                                    offset: property.offset,
-                                }
-                            }
-                            ve_shader_reflect::BlockMemberType::FloatVector(3) => {
-                                MaterialProperty::Vec3 {
-                                    binding: binding.binding,
-                                    offset: property.offset,
-                                }
-                            }
-                            ve_shader_reflect::BlockMemberType::FloatVector(4) => {
-                                MaterialProperty::Vec4 {
-                                    binding: binding.binding,
-                                    offset: property.offset,
-                                }
-                            }
-                            ve_shader_reflect::BlockMemberType::FloatMatrix(_) => {
-                                MaterialProperty::Unsupported
-                            }
-                            _ => MaterialProperty::Unsupported,
-                        };
-                        properties.insert(property.name.clone(), prop);
+                        Self::insert_uniform_property(
+                            &mut properties,
+                            property.name.clone(),
+                            &property.kind,
+                            binding.binding,
+                            property.offset,
+                        );
                     }
 
                     DescriptorData::UniformBuffer {
@@ -162,13 +378,73 @@ impl MaterialPipeline {
             resource_infos[binding.binding as usize] = res_info;
         }
 
-        let descriptor_set_layout =
-            material_compiler::compile_descriptor_set_layout(device.as_ref(), &resource_infos)?;
-        let pipeline_layout = material_compiler::compile_pipeline_layout(
-            device.as_ref(),
-            &[frame_data_layout, descriptor_set_layout],
-        )?;
+        (properties, resource_infos)
+    }
+
+    /// Adds the property `name` stored at `offset` in the uniform buffer `binding`.
+    ///
+    /// Arrays are added as one property per element, named like the elements of struct arrays, e.g. `weights[1]`.
+    fn insert_uniform_property(
+        properties: &mut HashMap<String, MaterialProperty>,
+        name: String,
+        kind: &ve_shader_reflect::BlockMemberType,
+        binding: u32,
+        offset: u32,
+    ) {
+        use ve_shader_reflect::BlockMemberType;
+
+        let property = match *kind {
+            BlockMemberType::Float => MaterialProperty::Float { binding, offset },
+            BlockMemberType::FloatVector(2) => MaterialProperty::Vec2 { binding, offset },
+            BlockMemberType::FloatVector(3) => MaterialProperty::Vec3 { binding, offset },
+            BlockMemberType::FloatVector(4) => MaterialProperty::Vec4 { binding, offset },
+            BlockMemberType::Int => MaterialProperty::Int { binding, offset },
+            BlockMemberType::UInt => MaterialProperty::UInt { binding, offset },
+            BlockMemberType::IntVector(count @ 2..=4) => MaterialProperty::IntVector {
+                binding,
+                offset,
+                count,
+            },
+            BlockMemberType::UIntVector(count @ 2..=4) => MaterialProperty::UIntVector {
+                binding,
+                offset,
+                count,
+            },
+            BlockMemberType::Array {
+                ref element,
+                length,
+                stride,
+            } => {
+                for i in 0..length {
+                    Self::insert_uniform_property(
+                        properties,
+                        format!("{name}[{i}]"),
+                        element,
+                        binding,
+                        offset + i * stride,
+                    );
+                }
+                return;
+            }
+            _ => MaterialProperty::Unsupported,
+        };
+        properties.insert(name, property);
+    }
 
+    /// Creates the regular and the wireframe pipeline from the given shader modules.
+    #[allow(clippy::too_many_arguments)]
+    fn create_pipelines(
+        device: &ash::Device,
+        pipeline_layout: vk::PipelineLayout,
+        renderpass: vk::RenderPass,
+        depth_mode: DepthMode,
+        stencil_id: u8,
+        vertex_attributes: &[vk::VertexInputAttributeDescription],
+        vertex_layout: &VertexLayout,
+        specialization_info: &vk::SpecializationInfo,
+        vertex_shader: vk::ShaderModule,
+        fragment_shader: vk::ShaderModule,
+    ) -> GraphicsResult<(vk::Pipeline, vk::Pipeline)> {
         let blend_func = vk::PipelineColorBlendAttachmentState::builder()
             .blend_enable(false)
             .color_write_mask(
@@ -185,77 +461,270 @@ impl MaterialPipeline {
             .compare_op(vk::CompareOp::ALWAYS)
             .write_mask(0xFF)
             .compare_mask(0xFF)
-            .reference(lighting_pipeline.stencil_id as u32)
+            .reference(stencil_id as u32)
             .build();
         let pipeline = pipeline::create_pipeline(
             pipeline_layout,
             renderpass,
             0,
-            true,
+            vertex_attributes,
+            vertex_layout,
             2,
             blend_func,
-            true,
+            Some(depth_mode.compare_op()),
             Some(stencil_func),
-            &device,
+            device,
             vertex_shader,
             fragment_shader,
+            Some(specialization_info),
             false,
         )?;
         let pipeline_wireframe = pipeline::create_pipeline(
             pipeline_layout,
             renderpass,
             0,
-            true,
+            vertex_attributes,
+            vertex_layout,
             2,
             blend_func,
-            true,
+            Some(depth_mode.compare_op()),
             Some(stencil_func),
-            &device,
+            device,
             vertex_shader,
             fragment_shader,
+            Some(specialization_info),
             true,
         )?;
 
+        Ok((pipeline, pipeline_wireframe))
+    }
+
+    /// Returns all MaterialPipelines that are still in use.
+    pub(crate) fn live_pipelines() -> Vec<Rc<MaterialPipeline>> {
+        LIVE_PIPELINES.with(|pipelines| {
+            let mut pipelines = pipelines.borrow_mut();
+            pipelines.retain(|pipeline| pipeline.strong_count() > 0);
+            pipelines.iter().filter_map(Weak::upgrade).collect()
+        })
+    }
+
+    /// Returns true if this MaterialPipeline was created with the shader `name`.
+    pub(crate) fn uses_shader(&self, name: &str) -> bool {
+        self.shader == name
+    }
+
+    /// Recreates the pipelines from the current shader code, keeping the values of all [`Materials`](Material).
+    ///
+    /// If the shader declares different properties than before, the Materials get new resources
+    /// and keep the values of all properties that still exist with the same name and type.
+    /// The old pipelines and values are kept if this fails. They must not be in use by the GPU.
+    ///
+    /// # Errors
+    /// - [`GraphicsError::PushConstantsChanged`] when the shader declares different push constants than before
+    /// - [`GraphicsError::InvalidSpecializationConstant`] when the shader no longer declares a specialization constant this MaterialPipeline was created with
+    pub(crate) fn reload(&self) -> GraphicsResult<()> {
+        let mut vertexshader_code = Vec::new();
+        let mut fragmentshader_code = Vec::new();
+        let (vertex_shader, fragment_shader) = pipeline::create_shader_modules(
+            &self.shader,
+            &self.device,
+            &mut vertexshader_code,
+            &mut fragmentshader_code,
+        )?;
+
+        let rebuilt = pipeline::reflect_shader_modules(&vertexshader_code, &fragmentshader_code)
+            .and_then(|refl| self.rebuild(&refl, vertex_shader, fragment_shader));
+
         unsafe {
-            device.destroy_shader_module(vertex_shader, None);
-            device.destroy_shader_module(fragment_shader, None);
+            self.device.destroy_shader_module(vertex_shader, None);
+            self.device.destroy_shader_module(fragment_shader, None);
         }
 
-        Ok(Rc::new(MaterialPipeline {
-            device,
-            allocator,
+        let Rebuilt {
             pipeline,
             pipeline_wireframe,
-            pipeline_layout,
-            descriptor_set_layout,
-            properties,
-            resource_infos,
-        }))
+            layout,
+        } = rebuilt?;
+        if let Some(layout) = layout {
+            let materials = self.live_materials();
+            // allocate everything first, so that a failure leaves all materials untouched
+            let mut migrated = Vec::with_capacity(materials.len());
+            for _ in &materials {
+                match material_compiler::compile_resources(&layout.resource_infos, &self.allocator)
+                {
+                    Ok(resources) => migrated.push(resources),
+                    Err(e) => {
+                        for (resources, allocations) in migrated {
+                            self.free_resources(&resources, allocations);
+                        }
+                        unsafe {
+                            self.device.destroy_pipeline(pipeline, None);
+                            self.device.destroy_pipeline(pipeline_wireframe, None);
+                        }
+                        layout.destroy(&self.device);
+                        return Err(e);
+                    }
+                }
+            }
+
+            for (material, (resources, allocations)) in materials.iter().zip(migrated) {
+                material.migrate(
+                    &self.properties.borrow(),
+                    &layout.properties,
+                    resources,
+                    allocations,
+                );
+            }
+
+            unsafe {
+                self.device.destroy_pipeline_layout(
+                    self.pipeline_layout.replace(layout.pipeline_layout),
+                    None,
+                );
+            }
+            self.retired_set_layouts
+                .borrow_mut()
+                .push(self.descriptor_set_layout.replace(layout.set_layout));
+            self.properties.replace(layout.properties);
+            self.resource_infos.replace(layout.resource_infos);
+        }
+
+        unsafe {
+            self.device
+                .destroy_pipeline(self.pipeline.replace(pipeline), None);
+            self.device
+                .destroy_pipeline(self.pipeline_wireframe.replace(pipeline_wireframe), None);
+        }
+        Ok(())
+    }
+
+    /// Creates the pipelines for reflected shader code, and new layouts if its properties changed.
+    fn rebuild(
+        &self,
+        refl: &ve_shader_reflect::ShaderInfo,
+        vertex_shader: vk::ShaderModule,
+        fragment_shader: vk::ShaderModule,
+    ) -> GraphicsResult<Rebuilt> {
+        // push constants are filled by the renderer, which can't adapt to a different layout
+        if refl.push_constants != self.push_constants {
+            return Err(GraphicsError::PushConstantsChanged(self.shader.clone()));
+        }
+        let vertex_attributes =
+            pipeline::vertex_attributes(&self.shader, &refl.inputs, self.vertex_layout)?;
+        let (map_entries, data) = material_compiler::compile_specialization(
+            &refl.specialization_constants,
+            &self.specialization,
+        )?;
+        let specialization_info = vk::SpecializationInfo::builder()
+            .map_entries(&map_entries)
+            .data(&data);
+
+        let (properties, resource_infos) = Self::reflect_properties(refl);
+        let layout = if properties != *self.properties.borrow()
+            || resource_infos != *self.resource_infos.borrow()
+        {
+            let set_layout =
+                material_compiler::compile_descriptor_set_layout(&self.device, &resource_infos)?;
+            let pipeline_layout = material_compiler::compile_pipeline_layout(
+                &self.device,
+                &[self.frame_data_layout, set_layout],
+                &refl.push_constants,
+            )
+            .inspect_err(|_| unsafe {
+                self.device.destroy_descriptor_set_layout(set_layout, None)
+            })?;
+            Some(RebuiltLayout {
+                pipeline_layout,
+                set_layout,
+                properties,
+                resource_infos,
+            })
+        } else {
+            None
+        };
+
+        let pipelines = Self::create_pipelines(
+            &self.device,
+            layout
+                .as_ref()
+                .map_or(self.pipeline_layout.get(), |layout| layout.pipeline_layout),
+            self.renderpass,
+            self.depth_mode,
+            self.stencil_id,
+            &vertex_attributes,
+            self.vertex_layout,
+            &specialization_info,
+            vertex_shader,
+            fragment_shader,
+        );
+        match pipelines {
+            Ok((pipeline, pipeline_wireframe)) => Ok(Rebuilt {
+                pipeline,
+                pipeline_wireframe,
+                layout,
+            }),
+            Err(e) => {
+                if let Some(layout) = layout {
+                    layout.destroy(&self.device);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Returns all Materials created from this pipeline that are still in use.
+    fn live_materials(&self) -> Vec<Rc<Material>> {
+        let mut materials = self.materials.borrow_mut();
+        materials.retain(|material| material.strong_count() > 0);
+        materials.iter().filter_map(Weak::upgrade).collect()
+    }
+
+    /// Destroys the uniform buffers of a Material and frees their memory.
+    fn free_resources(&self, resources: &[DescriptorData], allocations: Vec<Allocation>) {
+        unsafe {
+            for r in resources {
+                if let DescriptorData::UniformBuffer { buffer, .. } = r {
+                    self.device.destroy_buffer(*buffer, None);
+                }
+            }
+        }
+        for a in allocations {
+            self.allocator.free(a);
+        }
     }
 
     /// Creates a new [`Material`] from the given MaterialPipeline.
     pub fn create_material(self: &Rc<Self>) -> GraphicsResult<Rc<Material>> {
-        let (resources, allocations) =
-            material_compiler::compile_resources(&self.resource_infos, self.allocator.as_ref())?;
+        let (resources, allocations) = material_compiler::compile_resources(
+            &self.resource_infos.borrow(),
+            self.allocator.as_ref(),
+        )?;
 
-        Ok(Rc::new(Material {
+        let material = Rc::new(Material {
             pipeline: self.clone(),
             resources: RefCell::new(resources),
-            allocations,
+            allocations: RefCell::new(allocations),
             textures: RefCell::new(HashMap::new()),
-        }))
+            sort_id: MATERIAL_SORT_IDS.next(),
+        });
+        self.materials.borrow_mut().push(Rc::downgrade(&material));
+        Ok(material)
     }
 }
 
 impl Drop for MaterialPipeline {
     fn drop(&mut self) {
         unsafe {
-            self.device.destroy_pipeline(self.pipeline, None);
-            self.device.destroy_pipeline(self.pipeline_wireframe, None);
+            self.device.destroy_pipeline(self.pipeline.get(), None);
             self.device
-                .destroy_pipeline_layout(self.pipeline_layout, None);
+                .destroy_pipeline(self.pipeline_wireframe.get(), None);
             self.device
-                .destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+                .destroy_pipeline_layout(self.pipeline_layout.get(), None);
+            self.device
+                .destroy_descriptor_set_layout(self.descriptor_set_layout.get(), None);
+            for layout in self.retired_set_layouts.take() {
+                self.device.destroy_descriptor_set_layout(layout, None);
+            }
         }
     }
 }
@@ -267,11 +736,70 @@ impl Drop for MaterialPipeline {
 pub struct Material {
     pipeline: Rc<MaterialPipeline>,
     resources: RefCell<Vec<DescriptorData>>,
-    allocations: Vec<Allocation>,
+    allocations: RefCell<Vec<Allocation>>,
     textures: RefCell<HashMap<String, Rc<Texture2D>>>,
+    sort_id: u32,
 }
 
 impl Material {
+    /// Replaces the resources with `resources` and `allocations`, which are laid out for `new_properties`.
+    ///
+    /// Values of properties that exist in `old_properties` and `new_properties` with the same type are kept,
+    /// all others have to be set again.
+    fn migrate(
+        &self,
+        old_properties: &HashMap<String, MaterialProperty>,
+        new_properties: &HashMap<String, MaterialProperty>,
+        mut resources: Vec<DescriptorData>,
+        allocations: Vec<Allocation>,
+    ) {
+        let old_allocations = self.allocations.borrow();
+        let mut textures = self.textures.borrow_mut();
+        textures.retain(|name, _| {
+            matches!(
+                new_properties.get(name),
+                Some(MaterialProperty::Sampler2D { .. })
+            )
+        });
+
+        for (name, property) in new_properties {
+            match old_properties.get(name) {
+                Some(old) if old.same_type(property) => {}
+                _ => continue,
+            }
+
+            if let (Some((old_binding, old_offset, size)), Some((binding, offset, _))) = (
+                old_properties[name].uniform_range(),
+                property.uniform_range(),
+            ) {
+                let src = old_allocations[old_binding as usize]
+                    .mapped_ptr()
+                    .unwrap()
+                    .as_ptr() as *const u8;
+                let dst = allocations[binding as usize].mapped_ptr().unwrap().as_ptr() as *mut u8;
+                unsafe {
+                    dst.add(offset as usize)
+                        .copy_from_nonoverlapping(src.add(old_offset as usize), size);
+                }
+            }
+            if let (MaterialProperty::Sampler2D { binding }, Some(texture)) =
+                (property, textures.get(name))
+            {
+                resources[*binding as usize] = DescriptorData::ImageSampler {
+                    image: texture.view,
+                    layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    sampler: texture.sampler,
+                };
+            }
+        }
+        drop(old_allocations);
+
+        let old_resources = self.resources.replace(resources);
+        let old_allocations = self.allocations.replace(allocations);
+        self.pipeline
+            .free_resources(&old_resources, old_allocations);
+    }
+
     fn set_uniform_property(
         &self,
         alloc: &Allocation,
@@ -297,15 +825,14 @@ impl Material {
     /// - [`GraphicsError::InvalidMaterialPropertyType`] when property `name` does not have type `float`
     /// - [`GraphicsError::VkMem`]
     pub fn set_float(&self, name: &str, val: f32) -> GraphicsResult<()> {
-        let prop = self
-            .pipeline
-            .properties
+        let properties = self.pipeline.properties.borrow();
+        let prop = properties
             .get(name)
             .ok_or_else(|| GraphicsError::InvalidMaterialProperty(name.to_owned()))?;
         match prop {
             MaterialProperty::Float { binding, offset } => {
                 self.set_uniform_property(
-                    &self.allocations[*binding as usize],
+                    &self.allocations.borrow()[*binding as usize],
                     *offset as u64,
                     size_of::<f32>() as u64,
                     &val as *const f32 as *const u8,
@@ -326,15 +853,14 @@ impl Material {
     /// - [`GraphicsError::InvalidMaterialPropertyType`] when property `name` does not have type `vec2`
     /// - [`GraphicsError::VkMem`]
     pub fn set_vec2(&self, name: &str, val: Vec2) -> GraphicsResult<()> {
-        let prop = self
-            .pipeline
-            .properties
+        let properties = self.pipeline.properties.borrow();
+        let prop = properties
             .get(name)
             .ok_or_else(|| GraphicsError::InvalidMaterialProperty(name.to_owned()))?;
         match prop {
             MaterialProperty::Vec2 { binding, offset } => {
                 self.set_uniform_property(
-                    &self.allocations[*binding as usize],
+                    &self.allocations.borrow()[*binding as usize],
                     *offset as u64,
                     size_of::<Vec2>() as u64,
                     &val as *const Vec2 as *const u8,
@@ -355,15 +881,14 @@ impl Material {
     /// - [`GraphicsError::InvalidMaterialPropertyType`] when property `name` does not have type `vec3`
     /// - [`GraphicsError::VkMem`]
     pub fn set_vec3(&self, name: &str, val: Vec3) -> GraphicsResult<()> {
-        let prop = self
-            .pipeline
-            .properties
+        let properties = self.pipeline.properties.borrow();
+        let prop = properties
             .get(name)
             .ok_or_else(|| GraphicsError::InvalidMaterialProperty(name.to_owned()))?;
         match prop {
             MaterialProperty::Vec3 { binding, offset } => {
                 self.set_uniform_property(
-                    &self.allocations[*binding as usize],
+                    &self.allocations.borrow()[*binding as usize],
                     *offset as u64,
                     size_of::<Vec3>() as u64,
                     &val as *const Vec3 as *const u8,
@@ -384,15 +909,14 @@ impl Material {
     /// - [`GraphicsError::InvalidMaterialPropertyType`] when property `name` does not have type `vec4`
     /// - [`GraphicsError::VkMem`]
     pub fn set_vec4(&self, name: &str, val: Vec4) -> GraphicsResult<()> {
-        let prop = self
-            .pipeline
-            .properties
+        let properties = self.pipeline.properties.borrow();
+        let prop = properties
             .get(name)
             .ok_or_else(|| GraphicsError::InvalidMaterialProperty(name.to_owned()))?;
         match prop {
             MaterialProperty::Vec4 { binding, offset } => {
                 self.set_uniform_property(
-                    &self.allocations[*binding as usize],
+                    &self.allocations.borrow()[*binding as usize],
                     *offset as u64,
                     size_of::<Vec4>() as u64,
                     &val as *const Vec4 as *const u8,
@@ -404,6 +928,128 @@ impl Material {
         Ok(())
     }
 
+    /// Sets a MaterialProperty of type int
+    ///
+    /// For naming scheme, see [`MaterialPipeline`]
+    ///
+    /// # Errors
+    /// - [`GraphicsError::InvalidMaterialProperty`] when no property with name `name` exists
+    /// - [`GraphicsError::InvalidMaterialPropertyType`] when property `name` does not have type `int`
+    /// - [`GraphicsError::VkMem`]
+    pub fn set_int(&self, name: &str, val: i32) -> GraphicsResult<()> {
+        let properties = self.pipeline.properties.borrow();
+        let prop = properties
+            .get(name)
+            .ok_or_else(|| GraphicsError::InvalidMaterialProperty(name.to_owned()))?;
+        match prop {
+            MaterialProperty::Int { binding, offset } => {
+                self.set_uniform_property(
+                    &self.allocations.borrow()[*binding as usize],
+                    *offset as u64,
+                    size_of::<i32>() as u64,
+                    &val as *const i32 as *const u8,
+                )?;
+            }
+            _ => return Err(GraphicsError::InvalidMaterialPropertyType(name.to_owned())),
+        }
+
+        Ok(())
+    }
+
+    /// Sets a MaterialProperty of type uint
+    ///
+    /// Booleans are stored as uint in uniform blocks, so they are set with this function as well.
+    ///
+    /// For naming scheme, see [`MaterialPipeline`]
+    ///
+    /// # Errors
+    /// - [`GraphicsError::InvalidMaterialProperty`] when no property with name `name` exists
+    /// - [`GraphicsError::InvalidMaterialPropertyType`] when property `name` does not have type `uint`
+    /// - [`GraphicsError::VkMem`]
+    pub fn set_uint(&self, name: &str, val: u32) -> GraphicsResult<()> {
+        let properties = self.pipeline.properties.borrow();
+        let prop = properties
+            .get(name)
+            .ok_or_else(|| GraphicsError::InvalidMaterialProperty(name.to_owned()))?;
+        match prop {
+            MaterialProperty::UInt { binding, offset } => {
+                self.set_uniform_property(
+                    &self.allocations.borrow()[*binding as usize],
+                    *offset as u64,
+                    size_of::<u32>() as u64,
+                    &val as *const u32 as *const u8,
+                )?;
+            }
+            _ => return Err(GraphicsError::InvalidMaterialPropertyType(name.to_owned())),
+        }
+
+        Ok(())
+    }
+
+    /// Sets a MaterialProperty of type ivec2, ivec3 or ivec4, with one value per component
+    ///
+    /// For naming scheme, see [`MaterialPipeline`]
+    ///
+    /// # Errors
+    /// - [`GraphicsError::InvalidMaterialProperty`] when no property with name `name` exists
+    /// - [`GraphicsError::InvalidMaterialPropertyType`] when property `name` is not an ivec with `val.len()` components
+    /// - [`GraphicsError::VkMem`]
+    pub fn set_ivec(&self, name: &str, val: &[i32]) -> GraphicsResult<()> {
+        let properties = self.pipeline.properties.borrow();
+        let prop = properties
+            .get(name)
+            .ok_or_else(|| GraphicsError::InvalidMaterialProperty(name.to_owned()))?;
+        match prop {
+            MaterialProperty::IntVector {
+                binding,
+                offset,
+                count,
+            } if *count as usize == val.len() => {
+                self.set_uniform_property(
+                    &self.allocations.borrow()[*binding as usize],
+                    *offset as u64,
+                    size_of_val(val) as u64,
+                    val.as_ptr() as *const u8,
+                )?;
+            }
+            _ => return Err(GraphicsError::InvalidMaterialPropertyType(name.to_owned())),
+        }
+
+        Ok(())
+    }
+
+    /// Sets a MaterialProperty of type uvec2, uvec3 or uvec4, with one value per component
+    ///
+    /// For naming scheme, see [`MaterialPipeline`]
+    ///
+    /// # Errors
+    /// - [`GraphicsError::InvalidMaterialProperty`] when no property with name `name` exists
+    /// - [`GraphicsError::InvalidMaterialPropertyType`] when property `name` is not a uvec with `val.len()` components
+    /// - [`GraphicsError::VkMem`]
+    pub fn set_uvec(&self, name: &str, val: &[u32]) -> GraphicsResult<()> {
+        let properties = self.pipeline.properties.borrow();
+        let prop = properties
+            .get(name)
+            .ok_or_else(|| GraphicsError::InvalidMaterialProperty(name.to_owned()))?;
+        match prop {
+            MaterialProperty::UIntVector {
+                binding,
+                offset,
+                count,
+            } if *count as usize == val.len() => {
+                self.set_uniform_property(
+                    &self.allocations.borrow()[*binding as usize],
+                    *offset as u64,
+                    size_of_val(val) as u64,
+                    val.as_ptr() as *const u8,
+                )?;
+            }
+            _ => return Err(GraphicsError::InvalidMaterialPropertyType(name.to_owned())),
+        }
+
+        Ok(())
+    }
+
     /// Sets a MaterialProperty of type sampler2D
     ///
     /// For naming scheme, see [`MaterialPipeline`]
@@ -412,9 +1058,8 @@ impl Material {
     /// - [`GraphicsError::InvalidMaterialProperty`] when no property with name `name` exists
     /// - [`GraphicsError::InvalidMaterialPropertyType`] when property `name` does not have type `sampler2D`
     pub fn set_texture(&self, name: &str, val: Rc<Texture2D>) -> GraphicsResult<()> {
-        let prop = self
-            .pipeline
-            .properties
+        let properties = self.pipeline.properties.borrow();
+        let prop = properties
             .get(name)
             .ok_or_else(|| GraphicsError::InvalidMaterialProperty(name.to_owned()))?;
         match prop {
@@ -445,23 +1090,43 @@ impl Material {
 
     /// Returns the vk::PipelineLayout associated with this Material
     pub(crate) fn get_pipeline_layout(&self) -> vk::PipelineLayout {
-        self.pipeline.pipeline_layout
+        self.pipeline.pipeline_layout.get()
     }
 
     /// Returns the vk::Pipeline that has to be used with this Material
     pub(crate) fn get_pipeline(&self) -> vk::Pipeline {
-        self.pipeline.pipeline
+        self.pipeline.pipeline.get()
     }
 
     pub(crate) fn get_wireframe_pipeline(&self) -> vk::Pipeline {
-        self.pipeline.pipeline_wireframe
+        self.pipeline.pipeline_wireframe.get()
+    }
+
+    /// Returns the id of this Material's Pipeline used for building render order sort keys
+    pub(crate) fn get_pipeline_sort_id(&self) -> u32 {
+        self.pipeline.sort_id
+    }
+
+    /// Returns the name of the shader of this Material's Pipeline
+    pub(crate) fn get_shader_name(&self) -> &str {
+        &self.pipeline.shader
+    }
+
+    /// Returns the layout of the vertices of meshes this Material can be drawn with
+    pub(crate) fn get_vertex_layout(&self) -> &'static VertexLayout {
+        self.pipeline.vertex_layout
+    }
+
+    /// Returns the id of this Material used for building render order sort keys
+    pub(crate) fn get_sort_id(&self) -> u32 {
+        self.sort_id
     }
 
     /// Returns the vk::DescriptorSetLayout of set #1 of this Material's Pipeline.
     ///
     /// Set #1 should contain all MaterialProperties
     pub(crate) fn get_descriptor_set_layout(&self) -> vk::DescriptorSetLayout {
-        self.pipeline.descriptor_set_layout
+        self.pipeline.descriptor_set_layout.get()
     }
 
     /// Returns the DescriptorData entries that can be used to get a valid DescriptorSet
@@ -473,15 +1138,57 @@ impl Material {
 
 impl Drop for Material {
     fn drop(&mut self) {
-        unsafe {
-            for r in &*self.resources.borrow() {
-                if let DescriptorData::UniformBuffer { buffer, .. } = r {
-                    self.pipeline.device.destroy_buffer(*buffer, None);
+        self.pipeline
+            .free_resources(&self.resources.take(), self.allocations.take());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use ve_shader_reflect::BlockMemberType;
+
+    use super::{MaterialPipeline, MaterialProperty};
+
+    #[test]
+    fn test_uniform_properties() {
+        let mut properties = HashMap::new();
+        let kind = BlockMemberType::Array {
+            element: Box::new(BlockMemberType::IntVector(3)),
+            length: 2,
+            stride: 16,
+        };
+        MaterialPipeline::insert_uniform_property(&mut properties, "offsets".into(), &kind, 1, 32);
+        MaterialPipeline::insert_uniform_property(
+            &mut properties,
+            "color".into(),
+            &BlockMemberType::UIntVector(4),
+            1,
+            0,
+        );
+
+        assert_eq!(properties.len(), 3);
+        assert!(
+            properties["offsets[1]"]
+                == MaterialProperty::IntVector {
+                    binding: 1,
+                    offset: 48,
+                    count: 3
                 }
-            }
-            for a in &self.allocations {
-                self.pipeline.allocator.free(a.clone());
-            }
-        }
+        );
+        assert_eq!(properties["offsets[1]"].uniform_range(), Some((1, 48, 12)));
+        assert_eq!(properties["color"].uniform_range(), Some((1, 0, 16)));
+
+        // vectors only have the same type if they have the same number of components
+        assert!(properties["offsets[0]"].same_type(&properties["offsets[1]"]));
+        assert!(
+            !properties["offsets[0]"].same_type(&MaterialProperty::IntVector {
+                binding: 1,
+                offset: 0,
+                count: 2
+            })
+        );
+        assert!(!properties["offsets[0]"].same_type(&properties["color"]));
     }
 }
@@ -1,3 +1,4 @@
+pub mod bounds;
 pub mod component;
 pub mod entity;
 pub mod light;
@@ -20,6 +21,7 @@ use self::transform::{Transform, TransformData};
 pub struct Scene {
     pub(crate) root_entity: RefCell<Rc<Entity>>,
     pub(crate) main_camera: RefCell<Weak<CameraComponent>>,
+    cameras: RefCell<Vec<Weak<CameraComponent>>>,
     id_counter: Cell<u64>,
 }
 
@@ -29,6 +31,7 @@ impl Scene {
         let res = Rc::new(Self {
             root_entity: RefCell::new(Rc::new(Entity::new_root())),
             main_camera: RefCell::new(Weak::new()),
+            cameras: RefCell::new(Vec::new()),
             id_counter: Cell::new(0),
         });
         root.scene = Rc::downgrade(&res);
@@ -77,8 +80,44 @@ impl Scene {
         self.root_entity.borrow().update(input, delta);
     }
 
-    pub(crate) fn set_main_camera(&self, cam: Weak<CameraComponent>) {
-        *self.main_camera.borrow_mut() = cam;
+    /// Registers a new camera, which becomes the main camera unless another one has a higher priority.
+    pub(crate) fn add_camera(&self, cam: Weak<CameraComponent>) {
+        self.cameras.borrow_mut().push(cam);
+        self.update_main_camera();
+    }
+
+    /// Makes the screen camera with the highest priority the main camera.
+    pub(crate) fn update_main_camera(&self) {
+        *self.main_camera.borrow_mut() = self
+            .collect_screen_cameras()
+            .last()
+            .map(Rc::downgrade)
+            .unwrap_or_default();
+    }
+
+    /// Returns all cameras that render into the window, in the order they are drawn.
+    pub(crate) fn collect_screen_cameras(&self) -> Vec<Rc<CameraComponent>> {
+        let mut cameras = self.cameras.borrow_mut();
+        cameras.retain(|cam| cam.strong_count() > 0);
+        let mut cameras: Vec<Rc<CameraComponent>> = cameras
+            .iter()
+            .filter_map(Weak::upgrade)
+            .filter(|cam| cam.get_render_target().is_none())
+            .collect();
+        // the sort is stable, so later cameras are drawn on top of earlier ones with the same priority
+        cameras.sort_by_key(|cam| cam.get_priority());
+        cameras
+    }
+
+    /// Returns all cameras that render into a texture.
+    pub(crate) fn collect_texture_cameras(&self) -> Vec<Rc<CameraComponent>> {
+        let mut cameras = self.cameras.borrow_mut();
+        cameras.retain(|cam| cam.strong_count() > 0);
+        cameras
+            .iter()
+            .filter_map(Weak::upgrade)
+            .filter(|cam| cam.get_render_target().is_some())
+            .collect()
     }
 }
 
@@ -1,73 +1,97 @@
 use std::{mem::size_of, rc::Rc};
 
-use ash::vk;
-use gpu_allocator::{vulkan::Allocation, MemoryLocation};
-use ve_format::mesh::{Face, MeshData, Vertex};
+use ve_format::mesh::{MeshData, Vertex};
+
+use crate::{
+    scene::bounds::{Aabb, BoundingSphere},
+    vulkan::{
+        error::GraphicsResult,
+        mesh_pool::{MeshAllocation, MeshPool},
+        pipeline::VertexLayout,
+        render_order::SortIdCounter,
+        uploader::Uploader,
+    },
+};
+
+static MESH_SORT_IDS: SortIdCounter = SortIdCounter::new();
 
-use crate::vulkan::{allocator::Allocator, error::GraphicsResult, uploader::Uploader};
 pub struct Mesh {
-    allocator: Rc<Allocator>,
-    pub(crate) vertex_buffer: vk::Buffer,
-    pub(crate) vertex_buffer_alloc: Allocation,
-    pub(crate) index_buffer: vk::Buffer,
-    pub(crate) index_buffer_alloc: Allocation,
+    pool: Rc<MeshPool>,
+    /// The vertex and index range of this mesh inside the [`MeshPool`].
+    pub(crate) allocation: MeshAllocation,
+    /// (first index, index count) of every submesh, relative to the start of the pool block's index buffer.
     pub(crate) submeshes: Vec<(u32, u32)>,
+    /// The local space bounding box of all vertices.
+    pub(crate) aabb: Aabb,
+    /// The local space bounding sphere of all vertices, used for culling.
+    pub(crate) bounding_sphere: BoundingSphere,
+    /// Id used for building render order sort keys.
+    pub(crate) sort_id: u32,
+    /// The layout of the vertices, which the vertex shader of a material has to match.
+    pub(crate) vertex_layout: &'static VertexLayout,
 }
 
 impl Drop for Mesh {
     fn drop(&mut self) {
-        self.allocator
-            .destroy_buffer(self.vertex_buffer, self.vertex_buffer_alloc.clone());
-        self.allocator
-            .destroy_buffer(self.index_buffer, self.index_buffer_alloc.clone());
+        self.pool.free(self.allocation);
     }
 }
 
 impl Mesh {
     pub fn bake(
         mesh_data: MeshData,
-        allocator: Rc<Allocator>,
+        pool: Rc<MeshPool>,
         uploader: &mut Uploader,
     ) -> GraphicsResult<Rc<Mesh>> {
-        let vertex_buffer_size = mesh_data.vertices.len() * size_of::<Vertex>();
-        let (vertex_buffer, vertex_buffer_alloc) = allocator.create_buffer(
-            vertex_buffer_size as u64,
-            vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
-            MemoryLocation::GpuOnly,
-        )?;
-
-        let mut index_buffer_size = 0u64;
-        for sm in &mesh_data.submeshes {
-            index_buffer_size += (sm.faces.len() * size_of::<Face>()) as u64;
-        }
-        let (index_buffer, index_buffer_alloc) = allocator.create_buffer(
-            index_buffer_size,
-            vk::BufferUsageFlags::INDEX_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
-            MemoryLocation::GpuOnly,
-        )?;
-
-        uploader.enqueue_buffer_upload(vertex_buffer, 0, &mesh_data.vertices);
+        let index_count: usize = mesh_data
+            .submeshes
+            .iter()
+            .map(|sm| sm.faces.len() * 3)
+            .sum();
+        let allocation = pool.allocate(mesh_data.vertices.len() as u32, index_count as u32)?;
+        let (vertex_buffer, index_buffer) = pool.get_buffers(allocation.block);
 
-        let mut offset = 0u64;
-        for sm in &mesh_data.submeshes {
-            uploader.enqueue_buffer_upload(index_buffer, offset, &sm.faces);
-            offset += (sm.faces.len() * size_of::<Face>()) as u64;
-        }
+        uploader.enqueue_buffer_upload(
+            vertex_buffer,
+            allocation.first_vertex as u64 * size_of::<Vertex>() as u64,
+            &mesh_data.vertices,
+        );
 
         let mut submeshes = Vec::with_capacity(mesh_data.submeshes.len());
-        let mut start_index = 0u32;
+        let mut start_index = allocation.first_index;
         for sm in &mesh_data.submeshes {
+            uploader.enqueue_buffer_upload(
+                index_buffer,
+                start_index as u64 * size_of::<u32>() as u64,
+                &sm.faces,
+            );
+
             submeshes.push((start_index, sm.faces.len() as u32 * 3));
             start_index += sm.faces.len() as u32 * 3;
         }
 
+        let aabb = Aabb::from_points(mesh_data.vertices.iter().map(|v| &v.position));
+        let bounding_sphere =
+            BoundingSphere::from_points(&aabb, mesh_data.vertices.iter().map(|v| &v.position));
+
         Ok(Rc::new(Mesh {
-            allocator,
-            vertex_buffer,
-            vertex_buffer_alloc,
-            index_buffer,
-            index_buffer_alloc,
+            pool,
+            allocation,
             submeshes,
+            aabb,
+            bounding_sphere,
+            sort_id: MESH_SORT_IDS.next(),
+            vertex_layout: &VertexLayout::MESH,
         }))
     }
+
+    /// Returns the local space bounding box of this mesh.
+    pub fn aabb(&self) -> Aabb {
+        self.aabb
+    }
+
+    /// Returns the local space bounding sphere of this mesh.
+    pub fn bounding_sphere(&self) -> BoundingSphere {
+        self.bounding_sphere
+    }
 }
@@ -3,6 +3,7 @@ use std::rc::Rc;
 use self::mesh::Mesh;
 
 use super::material::Material;
+use crate::vulkan::error::{GraphicsError, GraphicsResult};
 
 pub mod mesh;
 
@@ -11,3 +12,26 @@ pub struct Model {
     pub material: Rc<Material>,
     pub mesh: Rc<Mesh>,
 }
+
+impl Model {
+    /// Creates a Model that draws `mesh` with `material`.
+    ///
+    /// # Errors
+    /// - [`GraphicsError::IncompatibleVertexLayout`] when the vertex shader of `material` was created for a different vertex layout than the one of `mesh`
+    pub fn new(material: Rc<Material>, mesh: Rc<Mesh>) -> GraphicsResult<Model> {
+        let model = Model { material, mesh };
+        if !model.is_compatible() {
+            return Err(GraphicsError::IncompatibleVertexLayout {
+                shader: model.material.get_shader_name().to_owned(),
+            });
+        }
+        Ok(model)
+    }
+
+    /// Returns true if the mesh provides the vertex layout the material was created for.
+    ///
+    /// The fields are public, so this is checked again before a model is drawn.
+    pub(crate) fn is_compatible(&self) -> bool {
+        self.mesh.vertex_layout == self.material.get_vertex_layout()
+    }
+}
@@ -1,5 +1,7 @@
 use gfx_maths::*;
 
+pub use crate::vulkan::shader_structs::TransformData;
+
 #[derive(Debug)]
 pub struct Transform {
     pub position: Vec3,
@@ -24,10 +26,3 @@ impl Transform {
         Mat4::translate(self.position) * Mat4::rotate(self.rotation)
     }
 }
-
-#[repr(C)]
-#[derive(Clone, Copy)]
-pub struct TransformData {
-    pub model_matrix: Mat4,
-    pub inv_model_matrix: Mat4,
-}
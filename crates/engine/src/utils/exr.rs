@@ -0,0 +1,132 @@
+//! A minimal OpenEXR writer for uncompressed scanline images with half float RGBA channels.
+
+use std::io::Write;
+
+const MAGIC: u32 = 20000630;
+const VERSION: u32 = 2;
+const PIXEL_TYPE_HALF: i32 = 1;
+
+fn write_attribute(header: &mut Vec<u8>, name: &str, ty: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(ty.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+/// Writes an RGBA image to `writer` in the OpenEXR format.
+///
+/// `pixels` contains the bits of `width * height` interleaved RGBA half floats, starting with the top left pixel.
+pub fn write_rgba_f16(
+    writer: &mut impl Write,
+    width: u32,
+    height: u32,
+    pixels: &[u16],
+) -> std::io::Result<()> {
+    assert_eq!(pixels.len(), width as usize * height as usize * 4);
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&MAGIC.to_le_bytes());
+    header.extend_from_slice(&VERSION.to_le_bytes());
+
+    // channels have to be sorted by name
+    let mut channels = Vec::new();
+    for name in ["A", "B", "G", "R"] {
+        channels.extend_from_slice(name.as_bytes());
+        channels.push(0);
+        channels.extend_from_slice(&PIXEL_TYPE_HALF.to_le_bytes());
+        // pLinear and reserved bytes
+        channels.extend_from_slice(&[0; 4]);
+        // x and y sampling
+        channels.extend_from_slice(&1i32.to_le_bytes());
+        channels.extend_from_slice(&1i32.to_le_bytes());
+    }
+    channels.push(0);
+    write_attribute(&mut header, "channels", "chlist", &channels);
+
+    write_attribute(&mut header, "compression", "compression", &[0]);
+
+    let mut window = Vec::new();
+    for value in [0, 0, width as i32 - 1, height as i32 - 1] {
+        window.extend_from_slice(&value.to_le_bytes());
+    }
+    write_attribute(&mut header, "dataWindow", "box2i", &window);
+    write_attribute(&mut header, "displayWindow", "box2i", &window);
+
+    // increasing y
+    write_attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    write_attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1.0f32.to_le_bytes(),
+    );
+    write_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    write_attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1.0f32.to_le_bytes(),
+    );
+    header.push(0);
+
+    // every scanline is stored in its own block, preceded by a table of block offsets
+    let line_size = width as usize * 4 * 2;
+    let block_size = 4 + 4 + line_size;
+    let first_block = header.len() + height as usize * 8;
+    for y in 0..height as usize {
+        header.extend_from_slice(&((first_block + y * block_size) as u64).to_le_bytes());
+    }
+    writer.write_all(&header)?;
+
+    let mut block = Vec::with_capacity(block_size);
+    for (y, line) in pixels.chunks_exact(width as usize * 4).enumerate() {
+        block.clear();
+        block.extend_from_slice(&(y as i32).to_le_bytes());
+        block.extend_from_slice(&(line_size as i32).to_le_bytes());
+        // channel data is stored one channel after another, in the same order as the header
+        for channel in [3, 2, 1, 0] {
+            for pixel in line.chunks_exact(4) {
+                block.extend_from_slice(&pixel[channel].to_le_bytes());
+            }
+        }
+        writer.write_all(&block)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::write_rgba_f16;
+
+    #[test]
+    fn test_write_rgba_f16() {
+        // 2x1 image, half float bits of 1.0, 0.5, 0.25 and 0.0
+        let pixels = [0x3C00, 0x3800, 0x3400, 0x0000, 0, 0, 0, 0x3C00];
+        let mut file = Vec::new();
+        write_rgba_f16(&mut file, 2, 1, &pixels).unwrap();
+
+        assert_eq!(&file[0..4], &[0x76, 0x2f, 0x31, 0x01]);
+
+        // the offset table has a single entry that points to the only scanline block
+        let block_size = 4 + 4 + 2 * 4 * 2;
+        let offset = u64::from_le_bytes(
+            file[file.len() - block_size - 8..file.len() - block_size]
+                .try_into()
+                .unwrap(),
+        );
+        assert_eq!(offset as usize, file.len() - block_size);
+
+        let block = &file[offset as usize..];
+        assert_eq!(&block[0..4], &0i32.to_le_bytes());
+        assert_eq!(&block[4..8], &16i32.to_le_bytes());
+        let channels: Vec<u16> = block[8..]
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+        // A, B, G and R of both pixels
+        assert_eq!(channels, [0x0000, 0x3C00, 0x3400, 0, 0x3800, 0, 0x3C00, 0]);
+    }
+}
@@ -137,10 +137,21 @@ impl BufferWrapper {
         Ok(())
     }
 
+    /// Reads the first `count` elements of a host visible buffer.
+    pub fn read<T: Copy>(&self, count: usize) -> Vec<T> {
+        let count = count.min(self.capacity as usize / std::mem::size_of::<T>());
+        let data_ptr = self.allocation.mapped_ptr().unwrap().as_ptr() as *const T;
+        unsafe { std::slice::from_raw_parts(data_ptr, count).to_vec() }
+    }
+
     pub fn get_size(&self) -> u64 {
         self.size
     }
 
+    pub fn get_capacity(&self) -> u64 {
+        self.capacity
+    }
+
     fn resize(&mut self, allocator: &Allocator, new_capacity: u64) -> GraphicsResult<()> {
         allocator.destroy_buffer(self.buffer, self.allocation.clone());
         let new_buffer = BufferWrapper::new(
@@ -0,0 +1,175 @@
+use std::{fs::File, io::BufWriter, path::PathBuf};
+
+use ash::vk;
+use gpu_allocator::{vulkan::Allocation, MemoryLocation};
+
+use crate::utils::exr;
+
+use super::{allocator::Allocator, error::GraphicsError, GraphicsResult};
+
+/// The image that is captured from a frame.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CaptureKind {
+    /// The finished frame including post processing and UI, written as PNG.
+    Final,
+    /// The HDR scene before post processing, written as OpenEXR.
+    Hdr,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct CaptureRequest {
+    pub(crate) kind: CaptureKind,
+    pub(crate) path: PathBuf,
+}
+
+fn bytes_per_pixel(format: vk::Format) -> GraphicsResult<u64> {
+    match format {
+        vk::Format::R8G8B8A8_SRGB
+        | vk::Format::R8G8B8A8_UNORM
+        | vk::Format::B8G8R8A8_SRGB
+        | vk::Format::B8G8R8A8_UNORM => Ok(4),
+        vk::Format::R16G16B16A16_SFLOAT => Ok(8),
+        _ => Err(GraphicsError::UnsupportedCaptureFormat(format)),
+    }
+}
+
+/// A capture whose copy to host memory has been recorded, but not necessarily executed.
+pub(crate) struct Readback {
+    request: CaptureRequest,
+    buffer: vk::Buffer,
+    alloc: Allocation,
+    format: vk::Format,
+    extent: vk::Extent2D,
+}
+
+impl Readback {
+    /// Records a copy of `image`, which has to be in `TRANSFER_SRC_OPTIMAL` layout.
+    pub(crate) fn record(
+        device: &ash::Device,
+        allocator: &Allocator,
+        commandbuffer: vk::CommandBuffer,
+        request: CaptureRequest,
+        image: vk::Image,
+        format: vk::Format,
+        extent: vk::Extent2D,
+    ) -> GraphicsResult<Self> {
+        let size = extent.width as u64 * extent.height as u64 * bytes_per_pixel(format)?;
+        let (buffer, alloc) = allocator.create_buffer(
+            size,
+            vk::BufferUsageFlags::TRANSFER_DST,
+            MemoryLocation::GpuToCpu,
+        )?;
+
+        let region = vk::BufferImageCopy::builder()
+            .image_subresource(vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            })
+            .image_extent(vk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            })
+            .build();
+        // make the copied data visible to the host after the frame's fence was signaled
+        let barrier = vk::BufferMemoryBarrier::builder()
+            .buffer(buffer)
+            .size(vk::WHOLE_SIZE)
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::HOST_READ)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .build();
+        unsafe {
+            device.cmd_copy_image_to_buffer(
+                commandbuffer,
+                image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                buffer,
+                &[region],
+            );
+            device.cmd_pipeline_barrier(
+                commandbuffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::HOST,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[barrier],
+                &[],
+            );
+        }
+
+        Ok(Self {
+            request,
+            buffer,
+            alloc,
+            format,
+            extent,
+        })
+    }
+
+    /// Writes the captured image to its file and frees the readback buffer.
+    ///
+    /// The commandbuffer containing the copy has to be finished.
+    pub(crate) fn write(self, allocator: &Allocator) -> GraphicsResult<()> {
+        let result = self.write_file();
+        allocator.destroy_buffer(self.buffer, self.alloc);
+        result
+    }
+
+    /// Frees the readback buffer without writing the file.
+    pub(crate) fn destroy(self, allocator: &Allocator) {
+        allocator.destroy_buffer(self.buffer, self.alloc);
+    }
+
+    fn write_file(&self) -> GraphicsResult<()> {
+        let size = self.extent.width as usize
+            * self.extent.height as usize
+            * bytes_per_pixel(self.format)? as usize;
+        let data = unsafe {
+            std::slice::from_raw_parts(self.alloc.mapped_ptr().unwrap().as_ptr() as *const u8, size)
+        };
+
+        match self.format {
+            vk::Format::R16G16B16A16_SFLOAT => {
+                let pixels: Vec<u16> = data
+                    .chunks_exact(2)
+                    .map(|c| u16::from_ne_bytes([c[0], c[1]]))
+                    .collect();
+                let mut writer =
+                    BufWriter::new(File::create(&self.request.path).map_err(anyhow::Error::from)?);
+                exr::write_rgba_f16(&mut writer, self.extent.width, self.extent.height, &pixels)
+                    .map_err(anyhow::Error::from)?;
+            }
+            format => {
+                let swizzle =
+                    format == vk::Format::B8G8R8A8_SRGB || format == vk::Format::B8G8R8A8_UNORM;
+                let mut pixels = data.to_vec();
+                for pixel in pixels.chunks_exact_mut(4) {
+                    if swizzle {
+                        pixel.swap(0, 2);
+                    }
+                    // the alpha channel of the final image is meaningless
+                    pixel[3] = 0xFF;
+                }
+                image::save_buffer(
+                    &self.request.path,
+                    &pixels,
+                    self.extent.width,
+                    self.extent.height,
+                    image::ColorType::Rgba8,
+                )
+                .map_err(anyhow::Error::from)?;
+            }
+        }
+
+        log::info!(
+            "Captured {:?} frame to {}",
+            self.request.kind,
+            self.request.path.display()
+        );
+        Ok(())
+    }
+}
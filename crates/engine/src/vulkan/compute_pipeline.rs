@@ -0,0 +1,418 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    mem::size_of,
+    rc::Rc,
+    slice,
+};
+
+use ash::vk;
+use ve_shader_reflect::SetBindingData;
+
+use super::{
+    descriptor_manager::{DescriptorData, DescriptorManager},
+    error::{GraphicsError, GraphicsResult},
+    pipeline,
+    texture::Texture2D,
+};
+
+/// Defines the point in a frame at which a registered [`ComputePipeline`] is dispatched.
+///
+/// All writes of the dispatches of a stage are visible to everything that is recorded after it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ComputeStage {
+    /// Before the gpass is rendered, e.g. to generate vertex data or simulate particles.
+    BeforeGPass,
+    /// After lighting has been resolved, before the post processing effects are applied.
+    BeforePostProcessing,
+    /// After post processing, before the ui is rendered.
+    BeforeUi,
+}
+
+/// The location of a named binding inside the pipeline layout.
+struct BindingSlot {
+    set: u32,
+    binding: u32,
+}
+
+/// This struct holds a compute shader together with the values of all of its bindings.
+///
+/// The bindings are discovered through reflection, so every resource the shader uses
+/// can be set by the name of its variable, e.g. `u_Particles` for
+/// `RWStructuredBuffer<Particle> u_Particles;`.
+/// Push constants are passed as a single struct matching the push constant block of the shader.
+///
+/// A ComputePipeline is dispatched every frame after registering it with
+/// [`VulkanManager::register_compute_pipeline`](super::VulkanManager::register_compute_pipeline).
+pub struct ComputePipeline {
+    device: Rc<ash::Device>,
+    pipeline: vk::Pipeline,
+    pipeline_layout: vk::PipelineLayout,
+    descriptor_set_layouts: Vec<vk::DescriptorSetLayout>,
+    bindings: HashMap<String, BindingSlot>,
+    workgroup_size: [u32; 3],
+    resources: RefCell<Vec<Vec<DescriptorData>>>,
+    textures: RefCell<HashMap<String, Rc<Texture2D>>>,
+    group_count: Cell<[u32; 3]>,
+    /// the range of the push constant block declared in the shader, if any
+    push_constant_range: Option<vk::PushConstantRange>,
+    push_constants: RefCell<Vec<u8>>,
+}
+
+impl ComputePipeline {
+    /// Creates a new [`ComputePipeline`] from the shader `shader`, which has to contain the entry point `comp`.
+    ///
+    /// The group count is initialized to zero, so the pipeline is not dispatched until [`set_group_count`](Self::set_group_count) is called.
+    ///
+    /// # Errors
+    /// - [`GraphicsError::ShaderReflect`] when the shader could not be reflected
+    /// - [`GraphicsError::Vk`]
+    pub fn new(device: Rc<ash::Device>, shader: &str) -> GraphicsResult<Rc<Self>> {
+        let mut computeshader_code = Vec::new();
+        let compute_shader =
+            pipeline::create_compute_shader_module(shader, &device, &mut computeshader_code)?;
+
+        let refl = ve_shader_reflect::reflect_shader(&computeshader_code)?;
+
+        let set_count = refl
+            .set_bindings
+            .iter()
+            .map(|b| b.set + 1)
+            .max()
+            .unwrap_or(0);
+        let mut resources = vec![Vec::new(); set_count as usize];
+        let mut bindings = HashMap::new();
+        for binding in &refl.set_bindings {
+            let res_info = match &binding.data {
+                // arrays of resources are not supported by the DescriptorManager
+                _ if binding.count != 1 => {
+                    log::warn!(
+                        "Compute shader {} uses array binding {}, which is not supported",
+                        shader,
+                        binding.var_name
+                    );
+                    continue;
+                }
+                SetBindingData::UniformBuffer { layout } => DescriptorData::UniformBuffer {
+                    buffer: vk::Buffer::null(),
+                    offset: 0,
+                    size: layout.total_size as u64,
+                },
+                SetBindingData::StorageBuffer { .. } => DescriptorData::StorageBuffer {
+                    buffer: vk::Buffer::null(),
+                    offset: 0,
+                    size: vk::WHOLE_SIZE,
+                },
+                SetBindingData::StorageImage { .. } => DescriptorData::StorageImage {
+                    image: vk::ImageView::null(),
+                    layout: vk::ImageLayout::GENERAL,
+                },
+                SetBindingData::SampledImage { .. } => DescriptorData::ImageSampler {
+                    image: vk::ImageView::null(),
+                    layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    sampler: vk::Sampler::null(),
+                },
+                _ => {
+                    log::warn!(
+                        "Compute shader {} uses binding {} of unsupported type",
+                        shader,
+                        binding.var_name
+                    );
+                    continue;
+                }
+            };
+
+            let set_resources = &mut resources[binding.set as usize];
+            if binding.binding >= set_resources.len() as u32 {
+                set_resources.resize(binding.binding as usize + 1, DescriptorData::None);
+            }
+            set_resources[binding.binding as usize] = res_info;
+
+            bindings.insert(
+                binding.var_name.clone(),
+                BindingSlot {
+                    set: binding.set,
+                    binding: binding.binding,
+                },
+            );
+        }
+
+        let mut descriptor_set_layouts = Vec::with_capacity(resources.len());
+        for set_resources in &resources {
+            let layout_bindings: Vec<_> = set_resources
+                .iter()
+                .enumerate()
+                .filter_map(|(i, r)| {
+                    Some(
+                        vk::DescriptorSetLayoutBinding::builder()
+                            .binding(i as u32)
+                            .descriptor_count(1)
+                            .descriptor_type(r.descriptor_type()?)
+                            .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                            .build(),
+                    )
+                })
+                .collect();
+            let layout_info = vk::DescriptorSetLayoutCreateInfo::builder()
+                .bindings(&layout_bindings)
+                .build();
+            descriptor_set_layouts
+                .push(unsafe { device.create_descriptor_set_layout(&layout_info, None)? });
+        }
+
+        let push_constant_ranges = pipeline::push_constant_ranges(&refl.push_constants);
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(&descriptor_set_layouts)
+            .push_constant_ranges(&push_constant_ranges)
+            .build();
+        let pipeline_layout =
+            unsafe { device.create_pipeline_layout(&pipeline_layout_info, None)? };
+
+        let pipeline = pipeline::create_compute_pipeline(pipeline_layout, &device, compute_shader)?;
+
+        unsafe {
+            device.destroy_shader_module(compute_shader, None);
+        }
+
+        Ok(Rc::new(Self {
+            device,
+            pipeline,
+            pipeline_layout,
+            descriptor_set_layouts,
+            bindings,
+            workgroup_size: refl.workgroup_size.unwrap_or([1, 1, 1]),
+            resources: RefCell::new(resources),
+            textures: RefCell::new(HashMap::new()),
+            group_count: Cell::new([0, 0, 0]),
+            push_constant_range: push_constant_ranges.first().copied(),
+            push_constants: RefCell::new(Vec::new()),
+        }))
+    }
+
+    /// Returns the number of invocations per workgroup, as declared by `numthreads` in the shader.
+    pub fn get_workgroup_size(&self) -> [u32; 3] {
+        self.workgroup_size
+    }
+
+    /// Sets the number of workgroups that are dispatched.
+    ///
+    /// A group count of zero in any dimension disables the dispatch.
+    pub fn set_group_count(&self, group_count: [u32; 3]) {
+        self.group_count.set(group_count);
+    }
+
+    /// Sets the number of workgroups so that at least `invocations` shader invocations are dispatched.
+    pub fn set_invocation_count(&self, invocations: [u32; 3]) {
+        let size = self.workgroup_size;
+        self.set_group_count([
+            invocations[0].div_ceil(size[0]),
+            invocations[1].div_ceil(size[1]),
+            invocations[2].div_ceil(size[2]),
+        ]);
+    }
+
+    /// Returns the size of the push constant block declared in the shader, or zero if it has none.
+    pub fn get_push_constant_size(&self) -> usize {
+        self.push_constant_range
+            .map_or(0, |range| (range.offset + range.size) as usize)
+    }
+
+    /// Sets the push constants that are passed to every dispatch.
+    ///
+    /// # Panics
+    /// When `T` is larger than the push constant block of the shader, see [`get_push_constant_size`](Self::get_push_constant_size).
+    pub fn set_push_constants<T: Copy>(&self, data: &T) {
+        let max_size = self.get_push_constant_size();
+        assert!(
+            size_of::<T>() <= max_size,
+            "Push constants must not be larger than the {max_size} bytes declared in the shader"
+        );
+
+        let bytes = unsafe { slice::from_raw_parts(data as *const T as *const u8, size_of::<T>()) };
+        let mut push_constants = self.push_constants.borrow_mut();
+        push_constants.clear();
+        push_constants.extend_from_slice(bytes);
+        // push constant updates have to be a multiple of 4 bytes
+        push_constants.resize((bytes.len() + 3) & !3, 0);
+    }
+
+    /// Sets a binding of type `StructuredBuffer` or `RWStructuredBuffer`.
+    ///
+    /// # Errors
+    /// - [`GraphicsError::InvalidComputeBinding`] when no storage buffer binding with name `name` exists
+    pub fn set_storage_buffer(
+        &self,
+        name: &str,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        size: vk::DeviceSize,
+    ) -> GraphicsResult<()> {
+        self.set_resource(
+            name,
+            DescriptorData::StorageBuffer {
+                buffer,
+                offset,
+                size,
+            },
+        )
+    }
+
+    /// Sets a binding of type `ConstantBuffer` or `cbuffer`.
+    ///
+    /// # Errors
+    /// - [`GraphicsError::InvalidComputeBinding`] when no uniform buffer binding with name `name` exists
+    pub fn set_uniform_buffer(
+        &self,
+        name: &str,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        size: vk::DeviceSize,
+    ) -> GraphicsResult<()> {
+        self.set_resource(
+            name,
+            DescriptorData::UniformBuffer {
+                buffer,
+                offset,
+                size,
+            },
+        )
+    }
+
+    /// Sets a binding of type `RWTexture2D` or similar.
+    ///
+    /// The image has to be in [`vk::ImageLayout::GENERAL`] when the pipeline is dispatched.
+    ///
+    /// # Errors
+    /// - [`GraphicsError::InvalidComputeBinding`] when no storage image binding with name `name` exists
+    pub fn set_storage_image(&self, name: &str, image: vk::ImageView) -> GraphicsResult<()> {
+        self.set_resource(
+            name,
+            DescriptorData::StorageImage {
+                image,
+                layout: vk::ImageLayout::GENERAL,
+            },
+        )
+    }
+
+    /// Sets a binding of type `Texture2D`.
+    ///
+    /// # Errors
+    /// - [`GraphicsError::InvalidComputeBinding`] when no texture binding with name `name` exists
+    pub fn set_texture(&self, name: &str, val: Rc<Texture2D>) -> GraphicsResult<()> {
+        self.set_resource(
+            name,
+            DescriptorData::ImageSampler {
+                image: val.view,
+                layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                sampler: val.sampler,
+            },
+        )?;
+        self.textures.borrow_mut().insert(name.to_owned(), val);
+
+        Ok(())
+    }
+
+    fn set_resource(&self, name: &str, data: DescriptorData) -> GraphicsResult<()> {
+        let slot = self
+            .bindings
+            .get(name)
+            .ok_or_else(|| GraphicsError::InvalidComputeBinding(name.to_owned()))?;
+
+        let res = &mut self.resources.borrow_mut()[slot.set as usize][slot.binding as usize];
+        if res.descriptor_type() != data.descriptor_type() {
+            return Err(GraphicsError::InvalidComputeBinding(name.to_owned()));
+        }
+        *res = data;
+
+        Ok(())
+    }
+
+    /// Records a dispatch of this pipeline with the current group count, push constants and bindings.
+    ///
+    /// Nothing is recorded if the group count is zero.
+    ///
+    /// # Errors
+    /// - [`GraphicsError::UnboundComputeBinding`] when a binding has not been set yet
+    /// - [`GraphicsError::Vk`]
+    pub(crate) fn record(
+        &self,
+        commandbuffer: vk::CommandBuffer,
+        descriptor_manager: &mut DescriptorManager<8>,
+    ) -> GraphicsResult<()> {
+        let group_count = self.group_count.get();
+        if group_count.contains(&0) {
+            return Ok(());
+        }
+
+        let resources = self.resources.borrow();
+        for (name, slot) in &self.bindings {
+            let bound = match resources[slot.set as usize][slot.binding as usize] {
+                DescriptorData::UniformBuffer { buffer, .. }
+                | DescriptorData::StorageBuffer { buffer, .. } => buffer != vk::Buffer::null(),
+                DescriptorData::StorageImage { image, .. }
+                | DescriptorData::ImageSampler { image, .. } => image != vk::ImageView::null(),
+                _ => true,
+            };
+            if !bound {
+                return Err(GraphicsError::UnboundComputeBinding(name.clone()));
+            }
+        }
+
+        let mut desc_sets = Vec::with_capacity(resources.len());
+        for (layout, set_resources) in self.descriptor_set_layouts.iter().zip(resources.iter()) {
+            desc_sets.push(descriptor_manager.get_descriptor_set(*layout, set_resources)?);
+        }
+
+        unsafe {
+            self.device.cmd_bind_pipeline(
+                commandbuffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.pipeline,
+            );
+            if !desc_sets.is_empty() {
+                self.device.cmd_bind_descriptor_sets(
+                    commandbuffer,
+                    vk::PipelineBindPoint::COMPUTE,
+                    self.pipeline_layout,
+                    0,
+                    &desc_sets,
+                    &[],
+                );
+            }
+            let push_constants = self.push_constants.borrow();
+            if let Some(range) = self.push_constant_range {
+                // the bytes in front of the first member of the block are not part of the range
+                if push_constants.len() > range.offset as usize {
+                    self.device.cmd_push_constants(
+                        commandbuffer,
+                        self.pipeline_layout,
+                        vk::ShaderStageFlags::COMPUTE,
+                        range.offset,
+                        &push_constants[range.offset as usize..],
+                    );
+                }
+            }
+            self.device.cmd_dispatch(
+                commandbuffer,
+                group_count[0],
+                group_count[1],
+                group_count[2],
+            );
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for ComputePipeline {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_pipeline(self.pipeline, None);
+            self.device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+            for layout in &self.descriptor_set_layouts {
+                self.device.destroy_descriptor_set_layout(*layout, None);
+            }
+        }
+    }
+}
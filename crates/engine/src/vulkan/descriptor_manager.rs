@@ -36,6 +36,27 @@ pub enum DescriptorData {
         image: vk::ImageView,
         layout: vk::ImageLayout,
     },
+    StorageImage {
+        image: vk::ImageView,
+        layout: vk::ImageLayout,
+    },
+}
+
+impl DescriptorData {
+    /// Returns the [`vk::DescriptorType`] a binding needs to hold this data, or `None` for [`DescriptorData::None`].
+    pub fn descriptor_type(&self) -> Option<vk::DescriptorType> {
+        match self {
+            DescriptorData::None => None,
+            DescriptorData::UniformBuffer { .. } => Some(vk::DescriptorType::UNIFORM_BUFFER),
+            DescriptorData::DynamicUniformBuffer { .. } => {
+                Some(vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
+            }
+            DescriptorData::ImageSampler { .. } => Some(vk::DescriptorType::COMBINED_IMAGE_SAMPLER),
+            DescriptorData::StorageBuffer { .. } => Some(vk::DescriptorType::STORAGE_BUFFER),
+            DescriptorData::InputAttachment { .. } => Some(vk::DescriptorType::INPUT_ATTACHMENT),
+            DescriptorData::StorageImage { .. } => Some(vk::DescriptorType::STORAGE_IMAGE),
+        }
+    }
 }
 
 /// This struct holds the necessary data for a complete DescriptorSet.
@@ -82,6 +103,10 @@ impl<const HISTORY_SIZE: usize> DescriptorManager<HISTORY_SIZE> {
                 .descriptor_count(1024)
                 .ty(vk::DescriptorType::STORAGE_BUFFER)
                 .build(),
+            vk::DescriptorPoolSize::builder()
+                .descriptor_count(256)
+                .ty(vk::DescriptorType::STORAGE_IMAGE)
+                .build(),
         ];
         let pool_info = vk::DescriptorPoolCreateInfo::builder()
             .max_sets(4096)
@@ -150,6 +175,8 @@ impl<const HISTORY_SIZE: usize> DescriptorManager<HISTORY_SIZE> {
         bindings: &[DescriptorData],
     ) -> Result<vk::DescriptorSet, vk::Result> {
         let mut hasher = DefaultHasher::new();
+        // sets with the same contents but different layouts (e.g. different shader stages) must not be shared
+        layout.hash(&mut hasher);
         for b in bindings {
             b.hash(&mut hasher);
         }
@@ -275,6 +302,22 @@ impl<const HISTORY_SIZE: usize> DescriptorManager<HISTORY_SIZE> {
                             .build(),
                     );
                 }
+                DescriptorData::StorageImage { image, layout } => {
+                    image_infos.push(
+                        vk::DescriptorImageInfo::builder()
+                            .image_view(*image)
+                            .image_layout(*layout)
+                            .build(),
+                    );
+                    set_writes.push(
+                        vk::WriteDescriptorSet::builder()
+                            .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                            .dst_binding(index as u32)
+                            .dst_set(new_set)
+                            .image_info(slice::from_ref(image_infos.last().unwrap()))
+                            .build(),
+                    );
+                }
             }
         }
         unsafe { self.device.update_descriptor_sets(&set_writes, &[]) };
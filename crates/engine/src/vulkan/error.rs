@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use ash::vk;
 
 /// Error type for every function in the graphics system
@@ -19,6 +21,39 @@ pub enum GraphicsError {
     /// A Material::set_X() function was called with a property of a different type.
     #[error("Incompatible material property type: {0}")]
     InvalidMaterialPropertyType(String),
+    /// An invalid binding name was given to a ComputePipeline::set_X() function,
+    /// or the binding has a different type.
+    #[error("Invalid compute pipeline binding: {0}")]
+    InvalidComputeBinding(String),
+    /// A ComputePipeline was dispatched before all of its bindings were set.
+    #[error("Compute pipeline binding was not set: {0}")]
+    UnboundComputeBinding(String),
+    /// A compiled shader could not be read or does not contain SPIR-V code.
+    #[error("Invalid shader {}: {reason}", .path.display())]
+    InvalidShader { path: PathBuf, reason: String },
+    /// A reloaded shader declares different push constants than the MaterialPipeline it was reloaded for.
+    #[error("Push constants of shader {0} changed, a restart is required")]
+    PushConstantsChanged(String),
+    /// A MaterialPipeline was given a value for a specialization constant the shader does not declare,
+    /// or the value has a different type.
+    #[error("Invalid specialization constant: {0}")]
+    InvalidSpecializationConstant(String),
+    /// A vertex shader has an input that is not an attribute of meshes.
+    #[error("Shader {shader} requires vertex attribute {attribute}, which meshes do not provide")]
+    MissingVertexAttribute { shader: String, attribute: String },
+    /// A vertex shader input has a different component type than the mesh attribute it reads.
+    #[error("Vertex attribute {attribute} of shader {shader} has the wrong type, expected components of {expected:?}")]
+    InvalidVertexAttributeType {
+        shader: String,
+        attribute: String,
+        expected: ve_shader_reflect::BlockMemberType,
+    },
+    /// A model combines a mesh and a material whose vertex layouts differ.
+    #[error("Mesh and material of a model have different vertex layouts, the material uses shader {shader}")]
+    IncompatibleVertexLayout { shader: String },
+    /// A frame capture was requested for an image whose format can't be written to a file.
+    #[error("Unsupported capture format: {0:?}")]
+    UnsupportedCaptureFormat(vk::Format),
     /// No GPU was found that matches the engines requirements
     #[error("No suitable gpu found")]
     NoSuitableGpu,
@@ -0,0 +1,265 @@
+//! GPU-driven drawing of the gpass.
+//!
+//! Every frame, all draws are written into a single indirect buffer with one
+//! [`vk::DrawIndexedIndirectCommand`] per submesh. The index of the drawn object is passed as
+//! first instance, which the gpass vertex shaders use to fetch their transform from a storage buffer.
+//! Before the gpass starts, a compute shader can cull these commands against the camera frustum
+//! by setting their instance count to zero, so the CPU cost does not grow with the amount of culled objects.
+//! When several cameras are rendered, every camera draws its own range of commands.
+//! The culling of the main camera also counts the culled objects, which is read back once the frame has finished.
+
+use std::{
+    mem::{size_of, ManuallyDrop},
+    ops::Range,
+    rc::Rc,
+};
+
+use ash::vk;
+use gfx_maths::Vec4;
+use gpu_allocator::MemoryLocation;
+
+use crate::scene::{bounds::Frustum, transform::TransformData};
+
+use super::{
+    allocator::Allocator,
+    buffer::BufferWrapper,
+    compute_pipeline::ComputePipeline,
+    descriptor_manager::{DescriptorData, DescriptorManager},
+    error::GraphicsResult,
+};
+
+/// Number of objects the per frame buffers can hold before they have to be resized.
+const INITIAL_OBJECT_CAPACITY: u64 = 1024;
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+struct CullPushConstants {
+    frustum_planes: [Vec4; 6],
+    command_count: u32,
+    first_command: u32,
+    count_culled: u32,
+}
+
+pub(crate) struct IndirectDrawData {
+    device: Rc<ash::Device>,
+    pipeline_cull: ManuallyDrop<Rc<ComputePipeline>>,
+    transform_buffers: Vec<BufferWrapper>,
+    bounding_sphere_buffers: Vec<BufferWrapper>,
+    draw_command_buffers: Vec<BufferWrapper>,
+    culled_count_buffers: Vec<BufferWrapper>,
+    /// Whether the culled count of a frame was written since it was last read
+    culled_count_pending: Vec<bool>,
+}
+
+impl IndirectDrawData {
+    pub(crate) fn new(
+        device: Rc<ash::Device>,
+        allocator: &Allocator,
+        max_frames_in_flight: u8,
+    ) -> GraphicsResult<Self> {
+        let pipeline_cull = ManuallyDrop::new(ComputePipeline::new(device.clone(), "gpu_cull")?);
+
+        let mut transform_buffers = Vec::with_capacity(max_frames_in_flight as usize);
+        let mut bounding_sphere_buffers = Vec::with_capacity(max_frames_in_flight as usize);
+        let mut draw_command_buffers = Vec::with_capacity(max_frames_in_flight as usize);
+        let mut culled_count_buffers = Vec::with_capacity(max_frames_in_flight as usize);
+        for _ in 0..max_frames_in_flight {
+            transform_buffers.push(BufferWrapper::new(
+                allocator,
+                INITIAL_OBJECT_CAPACITY * size_of::<TransformData>() as u64,
+                vk::BufferUsageFlags::STORAGE_BUFFER,
+                MemoryLocation::CpuToGpu,
+            )?);
+            bounding_sphere_buffers.push(BufferWrapper::new(
+                allocator,
+                INITIAL_OBJECT_CAPACITY * size_of::<Vec4>() as u64,
+                vk::BufferUsageFlags::STORAGE_BUFFER,
+                MemoryLocation::CpuToGpu,
+            )?);
+            draw_command_buffers.push(BufferWrapper::new(
+                allocator,
+                INITIAL_OBJECT_CAPACITY * size_of::<vk::DrawIndexedIndirectCommand>() as u64,
+                vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::INDIRECT_BUFFER,
+                MemoryLocation::CpuToGpu,
+            )?);
+            culled_count_buffers.push(BufferWrapper::new(
+                allocator,
+                size_of::<u32>() as u64,
+                vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
+                MemoryLocation::GpuToCpu,
+            )?);
+        }
+
+        Ok(Self {
+            device,
+            pipeline_cull,
+            transform_buffers,
+            bounding_sphere_buffers,
+            draw_command_buffers,
+            culled_count_buffers,
+            culled_count_pending: vec![false; max_frames_in_flight as usize],
+        })
+    }
+
+    /// Uploads the object data and draw commands of the current frame.
+    ///
+    /// `transforms` and `bounding_spheres` are indexed by the `first_instance` field of the draw commands.
+    /// Bounding spheres are given in object space, `xyz` being the center and `w` the radius.
+    pub(crate) fn upload(
+        &mut self,
+        allocator: &Allocator,
+        frame_index: u8,
+        transforms: &[TransformData],
+        bounding_spheres: &[Vec4],
+        draw_commands: &[vk::DrawIndexedIndirectCommand],
+    ) -> GraphicsResult<()> {
+        profile_function!();
+
+        self.transform_buffers[frame_index as usize].fill(allocator, transforms)?;
+        self.bounding_sphere_buffers[frame_index as usize].fill(allocator, bounding_spheres)?;
+        self.draw_command_buffers[frame_index as usize].fill(allocator, draw_commands)?;
+
+        Ok(())
+    }
+
+    /// Returns the binding of the transform storage buffer read by the gpass vertex shaders.
+    pub(crate) fn get_transform_descriptor(&self, frame_index: u8) -> DescriptorData {
+        let buffer = &self.transform_buffers[frame_index as usize];
+        DescriptorData::StorageBuffer {
+            buffer: buffer.buffer,
+            offset: 0,
+            size: buffer.get_capacity(),
+        }
+    }
+
+    /// Returns the buffer containing the draw commands of the given frame.
+    pub(crate) fn get_draw_command_buffer(&self, frame_index: u8) -> vk::Buffer {
+        self.draw_command_buffers[frame_index as usize].buffer
+    }
+
+    /// Returns the number of objects culled by the last frame that used `frame_index`, i.e. `frames_in_flight` frames ago,
+    /// or `None` if that frame did not count them.
+    ///
+    /// Has to be called after waiting for the fence of the frame.
+    pub(crate) fn take_culled_count(&mut self, frame_index: u8) -> Option<u32> {
+        if !std::mem::take(&mut self.culled_count_pending[frame_index as usize]) {
+            return None;
+        }
+        self.culled_count_buffers[frame_index as usize]
+            .read::<u32>(1)
+            .first()
+            .copied()
+    }
+
+    /// Records the culling dispatch of the draw commands in `commands`, which has to happen outside of a renderpass.
+    ///
+    /// `frustum` has to be given in world space.
+    /// If `count_culled` is set, the culled objects are counted, see [`Self::take_culled_count`].
+    pub(crate) fn record_culling(
+        &mut self,
+        commandbuffer: vk::CommandBuffer,
+        descriptor_manager: &mut DescriptorManager<8>,
+        frame_index: u8,
+        frustum: &Frustum,
+        commands: Range<u32>,
+        count_culled: bool,
+    ) -> GraphicsResult<()> {
+        profile_function!();
+
+        if commands.is_empty() {
+            return Ok(());
+        }
+
+        let set_buffer = |name: &str, buffer: &BufferWrapper| {
+            self.pipeline_cull
+                .set_storage_buffer(name, buffer.buffer, 0, buffer.get_capacity())
+        };
+        set_buffer(
+            "u_Transforms",
+            &self.transform_buffers[frame_index as usize],
+        )?;
+        set_buffer(
+            "u_BoundingSpheres",
+            &self.bounding_sphere_buffers[frame_index as usize],
+        )?;
+        set_buffer(
+            "u_Commands",
+            &self.draw_command_buffers[frame_index as usize],
+        )?;
+        set_buffer(
+            "u_CulledCount",
+            &self.culled_count_buffers[frame_index as usize],
+        )?;
+
+        if count_culled {
+            let counter = self.culled_count_buffers[frame_index as usize].buffer;
+            unsafe {
+                self.device
+                    .cmd_fill_buffer(commandbuffer, counter, 0, vk::WHOLE_SIZE, 0);
+
+                let barrier = vk::MemoryBarrier::builder()
+                    .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                    .dst_access_mask(vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE)
+                    .build();
+                self.device.cmd_pipeline_barrier(
+                    commandbuffer,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::COMPUTE_SHADER,
+                    vk::DependencyFlags::empty(),
+                    &[barrier],
+                    &[],
+                    &[],
+                );
+            }
+            self.culled_count_pending[frame_index as usize] = true;
+        }
+
+        self.pipeline_cull.set_push_constants(&CullPushConstants {
+            frustum_planes: frustum.planes,
+            command_count: commands.len() as u32,
+            first_command: commands.start,
+            count_culled: count_culled as u32,
+        });
+        self.pipeline_cull
+            .set_invocation_count([commands.len() as u32, 1, 1]);
+        self.pipeline_cull
+            .record(commandbuffer, descriptor_manager)?;
+
+        unsafe {
+            // the culled count is read on the host once the frame has finished
+            let barrier = vk::MemoryBarrier::builder()
+                .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+                .dst_access_mask(
+                    vk::AccessFlags::INDIRECT_COMMAND_READ | vk::AccessFlags::HOST_READ,
+                )
+                .build();
+            self.device.cmd_pipeline_barrier(
+                commandbuffer,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::PipelineStageFlags::DRAW_INDIRECT | vk::PipelineStageFlags::HOST,
+                vk::DependencyFlags::empty(),
+                &[barrier],
+                &[],
+                &[],
+            );
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn destroy(&mut self, allocator: &Allocator) {
+        for buffer in self
+            .transform_buffers
+            .iter_mut()
+            .chain(self.bounding_sphere_buffers.iter_mut())
+            .chain(self.draw_command_buffers.iter_mut())
+            .chain(self.culled_count_buffers.iter_mut())
+        {
+            buffer.cleanup(allocator);
+        }
+
+        unsafe {
+            ManuallyDrop::drop(&mut self.pipeline_cull);
+        }
+    }
+}
@@ -1,22 +1,30 @@
-use std::rc::Rc;
+use std::{cell::Cell, rc::Rc};
 
 use ash::vk;
 
-use super::pipeline;
+use super::{
+    error::GraphicsResult,
+    pipeline::{self, VertexLayout},
+};
 
 /// This struct describes a Deferred Resolve shader and its associated state.
 ///
 /// A LightingPipeline can be thought of as a specific lighting equation to be applied to a specific set of [`Materials`](crate::scene::material::Material).
 pub struct LightingPipeline {
     /// The [`vk::Pipeline`] to be used for rendering point lights
-    pub(crate) point_pipeline: Option<vk::Pipeline>,
+    pub(crate) point_pipeline: Cell<Option<vk::Pipeline>>,
     /// The [`vk::Pipeline`] to be used for rendering directional lights
-    pub(crate) directional_pipeline: Option<vk::Pipeline>,
+    pub(crate) directional_pipeline: Cell<Option<vk::Pipeline>>,
     /// The [`vk::Pipeline`] to be used for rendering ambient lighting (or unlit materials).
     /// Will be rendered exactly once each frame.
-    pub(crate) ambient_pipeline: Option<vk::Pipeline>,
+    pub(crate) ambient_pipeline: Cell<Option<vk::Pipeline>>,
     /// The stencil value that identifies this LightingPipeline in the GPass attachments.
     pub(crate) stencil_id: u8,
+    point_shader: Option<String>,
+    directional_shader: Option<String>,
+    ambient_shader: Option<String>,
+    pipe_layout_resolve: vk::PipelineLayout,
+    renderpass: vk::RenderPass,
     device: Rc<ash::Device>,
 }
 
@@ -37,7 +45,43 @@ impl LightingPipeline {
         renderpass: vk::RenderPass,
         device: Rc<ash::Device>,
         stencil_id: u8,
-    ) -> Result<Rc<LightingPipeline>, vk::Result> {
+    ) -> GraphicsResult<Rc<LightingPipeline>> {
+        let lighting_pipeline = LightingPipeline {
+            point_pipeline: Cell::new(None),
+            directional_pipeline: Cell::new(None),
+            ambient_pipeline: Cell::new(None),
+            stencil_id,
+            point_shader: point_shader.map(str::to_owned),
+            directional_shader: directional_shader.map(str::to_owned),
+            ambient_shader: ambient_shader.map(str::to_owned),
+            pipe_layout_resolve,
+            renderpass,
+            device,
+        };
+
+        // pipelines that were already created are destroyed on drop if a later one fails
+        for (shader, pipeline) in lighting_pipeline.stages() {
+            if let Some(shader) = shader {
+                pipeline.set(Some(lighting_pipeline.create_pipeline(shader)?));
+            }
+        }
+
+        Ok(Rc::new(lighting_pipeline))
+    }
+
+    /// The shader name and pipeline of the point, directional and ambient stage.
+    fn stages(&self) -> [(Option<&str>, &Cell<Option<vk::Pipeline>>); 3] {
+        [
+            (self.point_shader.as_deref(), &self.point_pipeline),
+            (
+                self.directional_shader.as_deref(),
+                &self.directional_pipeline,
+            ),
+            (self.ambient_shader.as_deref(), &self.ambient_pipeline),
+        ]
+    }
+
+    fn create_pipeline(&self, shader: &str) -> GraphicsResult<vk::Pipeline> {
         let stencil_func = vk::StencilOpState::builder()
             .fail_op(vk::StencilOp::KEEP)
             .pass_op(vk::StencilOp::KEEP)
@@ -45,7 +89,7 @@ impl LightingPipeline {
             .compare_op(vk::CompareOp::EQUAL)
             .write_mask(0x00)
             .compare_mask(0xFF)
-            .reference(stencil_id as u32)
+            .reference(self.stencil_id as u32)
             .build();
         let blend_func = vk::PipelineColorBlendAttachmentState::builder()
             .blend_enable(true)
@@ -63,132 +107,70 @@ impl LightingPipeline {
             )
             .build();
 
-        let point_pipeline = if let Some(point_shader) = point_shader {
-            let mut vertexshader_code = Vec::new();
-            let mut fragmentshader_code = Vec::new();
-            let (vertex_shader, fragment_shader) = pipeline::create_shader_modules(
-                point_shader,
-                &device,
-                &mut vertexshader_code,
-                &mut fragmentshader_code,
-            )?;
-
-            let pipeline = pipeline::create_pipeline(
-                pipe_layout_resolve,
-                renderpass,
-                1,
-                false,
-                1,
-                blend_func,
-                false,
-                Some(stencil_func),
-                &device,
-                vertex_shader,
-                fragment_shader,
-                false,
-            )?;
-
-            unsafe {
-                device.destroy_shader_module(vertex_shader, None);
-                device.destroy_shader_module(fragment_shader, None);
-            }
-
-            Some(pipeline)
-        } else {
-            None
-        };
-
-        let directional_pipeline = if let Some(directional_shader) = directional_shader {
-            let mut vertexshader_code = Vec::new();
-            let mut fragmentshader_code = Vec::new();
-            let (vertex_shader, fragment_shader) = pipeline::create_shader_modules(
-                directional_shader,
-                &device,
-                &mut vertexshader_code,
-                &mut fragmentshader_code,
-            )?;
+        let mut vertexshader_code = Vec::new();
+        let mut fragmentshader_code = Vec::new();
+        let (vertex_shader, fragment_shader) = pipeline::create_shader_modules(
+            shader,
+            &self.device,
+            &mut vertexshader_code,
+            &mut fragmentshader_code,
+        )?;
+
+        let pipeline = pipeline::create_pipeline(
+            self.pipe_layout_resolve,
+            self.renderpass,
+            1,
+            &[],
+            &VertexLayout::MESH,
+            1,
+            blend_func,
+            None,
+            Some(stencil_func),
+            &self.device,
+            vertex_shader,
+            fragment_shader,
+            None,
+            false,
+        );
 
-            let pipeline = pipeline::create_pipeline(
-                pipe_layout_resolve,
-                renderpass,
-                1,
-                false,
-                1,
-                blend_func,
-                false,
-                Some(stencil_func),
-                &device,
-                vertex_shader,
-                fragment_shader,
-                false,
-            )?;
-
-            unsafe {
-                device.destroy_shader_module(vertex_shader, None);
-                device.destroy_shader_module(fragment_shader, None);
-            }
-
-            Some(pipeline)
-        } else {
-            None
-        };
+        unsafe {
+            self.device.destroy_shader_module(vertex_shader, None);
+            self.device.destroy_shader_module(fragment_shader, None);
+        }
 
-        let ambient_pipeline = if let Some(ambient_shader) = ambient_shader {
-            let mut vertexshader_code = Vec::new();
-            let mut fragmentshader_code = Vec::new();
-            let (vertex_shader, fragment_shader) = pipeline::create_shader_modules(
-                ambient_shader,
-                &device,
-                &mut vertexshader_code,
-                &mut fragmentshader_code,
-            )?;
+        Ok(pipeline?)
+    }
 
-            let pipeline = pipeline::create_pipeline(
-                pipe_layout_resolve,
-                renderpass,
-                1,
-                false,
-                1,
-                blend_func,
-                false,
-                Some(stencil_func),
-                &device,
-                vertex_shader,
-                fragment_shader,
-                false,
-            )?;
+    /// Returns true if any stage of this LightingPipeline was created with the shader `name`.
+    pub(crate) fn uses_shader(&self, name: &str) -> bool {
+        self.stages()
+            .iter()
+            .any(|(shader, _)| *shader == Some(name))
+    }
 
-            unsafe {
-                device.destroy_shader_module(vertex_shader, None);
-                device.destroy_shader_module(fragment_shader, None);
+    /// Recreates the pipelines of all stages using the shader `name` from the current shader code.
+    ///
+    /// If creating a pipeline fails, it keeps its old one. The pipelines must not be in use by the GPU.
+    pub(crate) fn reload(&self, name: &str) -> GraphicsResult<()> {
+        for (shader, pipeline) in self.stages() {
+            if shader == Some(name) {
+                let new_pipeline = self.create_pipeline(name)?;
+                if let Some(old_pipeline) = pipeline.replace(Some(new_pipeline)) {
+                    unsafe { self.device.destroy_pipeline(old_pipeline, None) };
+                }
             }
-
-            Some(pipeline)
-        } else {
-            None
-        };
-
-        Ok(Rc::new(LightingPipeline {
-            point_pipeline,
-            directional_pipeline,
-            ambient_pipeline,
-            stencil_id,
-            device,
-        }))
+        }
+        Ok(())
     }
 }
 
 impl Drop for LightingPipeline {
     fn drop(&mut self) {
         unsafe {
-            if let Some(pp) = self.point_pipeline {
-                self.device.destroy_pipeline(pp, None);
-            }
-            if let Some(dp) = self.directional_pipeline {
-                self.device.destroy_pipeline(dp, None);
-            }
-            if let Some(ap) = self.ambient_pipeline {
-                self.device.destroy_pipeline(ap, None);
+            for (_, pipeline) in self.stages() {
+                if let Some(pipeline) = pipeline.get() {
+                    self.device.destroy_pipeline(pipeline, None);
+                }
             }
         }
     }
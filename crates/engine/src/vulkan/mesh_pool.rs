@@ -0,0 +1,284 @@
+use std::{cell::RefCell, mem::size_of, rc::Rc};
+
+use ash::vk;
+use gpu_allocator::{vulkan::Allocation, MemoryLocation};
+use ve_format::mesh::Vertex;
+
+use super::{allocator::Allocator, error::GraphicsResult};
+
+/// Number of vertices a single pool block can hold, unless a larger mesh requires a bigger block.
+const DEFAULT_BLOCK_VERTICES: u32 = 256 * 1024;
+/// Number of indices a single pool block can hold, unless a larger mesh requires a bigger block.
+const DEFAULT_BLOCK_INDICES: u32 = 1024 * 1024;
+
+/// A first-fit allocator for ranges of elements inside a buffer.
+struct FreeList {
+    /// Free ranges as (start, length), sorted by start and never adjacent to each other.
+    ranges: Vec<(u32, u32)>,
+}
+
+impl FreeList {
+    fn new(size: u32) -> Self {
+        Self {
+            ranges: vec![(0, size)],
+        }
+    }
+
+    fn allocate(&mut self, len: u32) -> Option<u32> {
+        if len == 0 {
+            return Some(0);
+        }
+
+        let index = self.ranges.iter().position(|r| r.1 >= len)?;
+        let (start, free_len) = self.ranges[index];
+        if free_len == len {
+            self.ranges.remove(index);
+        } else {
+            self.ranges[index] = (start + len, free_len - len);
+        }
+
+        Some(start)
+    }
+
+    fn free(&mut self, start: u32, len: u32) {
+        if len == 0 {
+            return;
+        }
+
+        let index = self.ranges.partition_point(|r| r.0 < start);
+        self.ranges.insert(index, (start, len));
+
+        // merge with the following range
+        if index + 1 < self.ranges.len() && start + len == self.ranges[index + 1].0 {
+            self.ranges[index].1 += self.ranges[index + 1].1;
+            self.ranges.remove(index + 1);
+        }
+        // merge with the preceding range
+        if index > 0 && self.ranges[index - 1].0 + self.ranges[index - 1].1 == start {
+            self.ranges[index - 1].1 += self.ranges[index].1;
+            self.ranges.remove(index);
+        }
+    }
+}
+
+struct MeshPoolBlock {
+    vertex_buffer: vk::Buffer,
+    vertex_buffer_alloc: Allocation,
+    index_buffer: vk::Buffer,
+    index_buffer_alloc: Allocation,
+    free_vertices: FreeList,
+    free_indices: FreeList,
+}
+
+/// A range of vertices and indices inside a [`MeshPool`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct MeshAllocation {
+    pub(crate) block: usize,
+    pub(crate) first_vertex: u32,
+    pub(crate) vertex_count: u32,
+    pub(crate) first_index: u32,
+    pub(crate) index_count: u32,
+}
+
+struct MeshPoolInner {
+    blocks: Vec<MeshPoolBlock>,
+    frame_counter: u64,
+    /// Allocations of dropped meshes together with the frame they were freed in.
+    pending_frees: Vec<(u64, MeshAllocation)>,
+}
+
+/// Suballocates vertex and index ranges for all [`Meshes`](crate::scene::model::mesh::Mesh) from a few large buffers.
+///
+/// Meshes in the same block share their vertex and index buffer,
+/// so they can be drawn without rebinding buffers by using `vertex_offset` and `first_index`.
+///
+/// Ranges of dropped meshes are reclaimed once no frame in flight can use them anymore.
+pub struct MeshPool {
+    allocator: Rc<Allocator>,
+    max_frames_in_flight: u64,
+    inner: RefCell<MeshPoolInner>,
+}
+
+impl MeshPool {
+    pub(crate) fn new(allocator: Rc<Allocator>, max_frames_in_flight: u64) -> Self {
+        Self {
+            allocator,
+            max_frames_in_flight,
+            inner: RefCell::new(MeshPoolInner {
+                blocks: Vec::new(),
+                frame_counter: 0,
+                pending_frees: Vec::new(),
+            }),
+        }
+    }
+
+    /// Reserves space for the given amount of vertices and indices.
+    ///
+    /// A new block is created if no existing block has enough free space.
+    pub(crate) fn allocate(
+        &self,
+        vertex_count: u32,
+        index_count: u32,
+    ) -> GraphicsResult<MeshAllocation> {
+        let mut inner = self.inner.borrow_mut();
+
+        for (block_index, block) in inner.blocks.iter_mut().enumerate() {
+            if let Some(allocation) =
+                Self::allocate_in_block(block, block_index, vertex_count, index_count)
+            {
+                return Ok(allocation);
+            }
+        }
+
+        let mut block = self.create_block(
+            vertex_count.max(DEFAULT_BLOCK_VERTICES),
+            index_count.max(DEFAULT_BLOCK_INDICES),
+        )?;
+        let block_index = inner.blocks.len();
+        let allocation =
+            Self::allocate_in_block(&mut block, block_index, vertex_count, index_count)
+                .expect("a new block always has enough space");
+        inner.blocks.push(block);
+
+        Ok(allocation)
+    }
+
+    /// Returns the space of the given allocation to the pool.
+    ///
+    /// The space will only be reused after all frames that might still use it have finished.
+    pub(crate) fn free(&self, allocation: MeshAllocation) {
+        let mut inner = self.inner.borrow_mut();
+        let frame = inner.frame_counter;
+        inner.pending_frees.push((frame, allocation));
+    }
+
+    /// Advances the pool to the next frame, reclaiming the space of meshes that are no longer in use.
+    ///
+    /// This should be called every frame.
+    pub(crate) fn next_frame(&self) {
+        let mut inner = self.inner.borrow_mut();
+        inner.frame_counter += 1;
+
+        let MeshPoolInner {
+            blocks,
+            frame_counter,
+            pending_frees,
+        } = &mut *inner;
+        pending_frees.retain(|(frame, allocation)| {
+            if *frame + self.max_frames_in_flight > *frame_counter {
+                return true;
+            }
+
+            let block = &mut blocks[allocation.block];
+            block
+                .free_vertices
+                .free(allocation.first_vertex, allocation.vertex_count);
+            block
+                .free_indices
+                .free(allocation.first_index, allocation.index_count);
+            false
+        });
+    }
+
+    /// Returns the vertex and index buffer of the given block.
+    pub(crate) fn get_buffers(&self, block: usize) -> (vk::Buffer, vk::Buffer) {
+        let inner = self.inner.borrow();
+        let block = &inner.blocks[block];
+        (block.vertex_buffer, block.index_buffer)
+    }
+
+    fn allocate_in_block(
+        block: &mut MeshPoolBlock,
+        block_index: usize,
+        vertex_count: u32,
+        index_count: u32,
+    ) -> Option<MeshAllocation> {
+        let first_vertex = block.free_vertices.allocate(vertex_count)?;
+        let first_index = match block.free_indices.allocate(index_count) {
+            Some(first_index) => first_index,
+            None => {
+                block.free_vertices.free(first_vertex, vertex_count);
+                return None;
+            }
+        };
+
+        Some(MeshAllocation {
+            block: block_index,
+            first_vertex,
+            vertex_count,
+            first_index,
+            index_count,
+        })
+    }
+
+    fn create_block(
+        &self,
+        vertex_capacity: u32,
+        index_capacity: u32,
+    ) -> GraphicsResult<MeshPoolBlock> {
+        log::debug!(
+            "Creating mesh pool block with {} vertices and {} indices",
+            vertex_capacity,
+            index_capacity
+        );
+
+        let (vertex_buffer, vertex_buffer_alloc) = self.allocator.create_buffer(
+            vertex_capacity as u64 * size_of::<Vertex>() as u64,
+            vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
+            MemoryLocation::GpuOnly,
+        )?;
+        let (index_buffer, index_buffer_alloc) = self.allocator.create_buffer(
+            index_capacity as u64 * size_of::<u32>() as u64,
+            vk::BufferUsageFlags::INDEX_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
+            MemoryLocation::GpuOnly,
+        )?;
+
+        Ok(MeshPoolBlock {
+            vertex_buffer,
+            vertex_buffer_alloc,
+            index_buffer,
+            index_buffer_alloc,
+            free_vertices: FreeList::new(vertex_capacity),
+            free_indices: FreeList::new(index_capacity),
+        })
+    }
+}
+
+impl Drop for MeshPool {
+    fn drop(&mut self) {
+        for block in &self.inner.borrow().blocks {
+            self.allocator
+                .destroy_buffer(block.vertex_buffer, block.vertex_buffer_alloc.clone());
+            self.allocator
+                .destroy_buffer(block.index_buffer, block.index_buffer_alloc.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::FreeList;
+
+    #[test]
+    fn test_free_list() {
+        let mut list = FreeList::new(100);
+
+        let a = list.allocate(30).unwrap();
+        let b = list.allocate(30).unwrap();
+        let c = list.allocate(30).unwrap();
+        assert_eq!((a, b, c), (0, 30, 60));
+        assert_eq!(list.allocate(20), None);
+
+        // freeing the middle range leaves a hole that is reused
+        list.free(b, 30);
+        assert_eq!(list.allocate(20), Some(30));
+        assert_eq!(list.allocate(20), None);
+
+        // freed neighbours are merged into a single range
+        list.free(30, 20);
+        list.free(a, 30);
+        list.free(c, 30);
+        assert_eq!(list.ranges, vec![(0, 100)]);
+        assert_eq!(list.allocate(100), Some(0));
+    }
+}
@@ -1,33 +1,45 @@
 pub(crate) mod allocator;
 pub(crate) mod buffer;
+pub mod capture;
+pub mod compute_pipeline;
 pub(crate) mod descriptor_manager;
 mod device;
 pub mod error;
+pub(crate) mod indirect;
 pub mod lighting_pipeline;
+pub mod mesh_pool;
+mod offscreen;
 pub(crate) mod pipeline;
+mod pipeline_cache;
 pub mod pp_effect;
+pub mod present;
 mod queue;
-mod renderpass;
+pub(crate) mod render_graph;
+pub mod render_order;
+pub mod resolution;
+pub(crate) mod shader_structs;
 mod surface;
 mod swapchain;
 pub mod texture;
 pub(crate) mod uploader;
 
-use std::{ffi::CString, mem::size_of, ptr::null, rc::Rc, slice};
+use std::{ffi::CString, mem::size_of, ops::Range, path::PathBuf, ptr::null, rc::Rc, slice};
 
-use ash::vk::{self, Handle};
+use ash::vk;
 use egui::ClippedMesh;
-use gfx_maths::Mat4;
+use gfx_maths::{Mat4, Vec4};
 use gpu_allocator::MemoryLocation;
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    assets::{shader, shader_watcher::ShaderWatcher},
     core::engine::EngineInfo,
     scene::{
-        component::camera_component::CameraUniformData,
+        bounds::Frustum,
+        component::camera_component::{CameraComponent, Viewport},
         light::Light,
-        material::Material,
-        model::{mesh::Mesh, Model},
+        material::{Material, MaterialPipeline},
+        model::Model,
         transform::TransformData,
         Scene,
     },
@@ -36,21 +48,212 @@ use crate::{
 use self::{
     allocator::Allocator,
     buffer::{MutableBuffer, PerFrameUniformBuffer, VulkanBuffer},
+    capture::{CaptureKind, CaptureRequest, Readback},
+    compute_pipeline::{ComputePipeline, ComputeStage},
     descriptor_manager::{DescriptorData, DescriptorManager},
     error::GraphicsResult,
+    indirect::IndirectDrawData,
     lighting_pipeline::LightingPipeline,
+    mesh_pool::MeshPool,
+    offscreen::{OffscreenTarget, OFFSCREEN_FORMAT},
     pp_effect::PPEffect,
+    present::{PresentConfig, PresentMode, MAX_FRAMES_IN_FLIGHT},
     queue::{PoolsWrapper, QueueFamilies, Queues},
+    render_graph::{ImageAccess, ImageId, ImageSize, PassDesc, RenderGraph, SubpassDesc},
+    render_order::SortKey,
+    resolution::ResolutionScale,
+    shader_structs::{CameraUniformData, DirectionalLightData, PointLightData},
     surface::SurfaceWrapper,
     swapchain::SwapchainWrapper,
-    texture::{Texture2D, TextureFilterMode},
+    texture::{Texture2D, TextureFilterMode, TEXTURE_FORMAT},
     uploader::Uploader,
 };
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub(crate) struct RendererConfig {
     pub(crate) gpu_vendor_id: Option<u32>,
     pub(crate) gpu_device_id: Option<u32>,
+    #[serde(default)]
+    pub(crate) depth_mode: DepthMode,
+    #[serde(default)]
+    pub(crate) resolution: ResolutionScale,
+    #[serde(default)]
+    pub(crate) present: PresentConfig,
+}
+
+/// How the depth buffer of the deferred pass stores the distance to the camera.
+///
+/// Materials have to be created with the mode of [`VulkanManager::depth_mode`], as it decides the depth test.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DepthMode {
+    /// 24 bit fixed point depth from 0 at the near plane to 1 at the far plane of the camera
+    #[default]
+    Standard,
+    /// 32 bit floating point depth from 1 at the near plane to 0 at infinity, the far plane of perspective cameras
+    /// is ignored. Floating point precision matches the perspective division, which avoids z-fighting at large distances.
+    ReversedInfinite,
+}
+
+impl DepthMode {
+    pub(crate) fn format(self) -> vk::Format {
+        match self {
+            DepthMode::Standard => vk::Format::D24_UNORM_S8_UINT,
+            DepthMode::ReversedInfinite => vk::Format::D32_SFLOAT_S8_UINT,
+        }
+    }
+
+    /// Depth of the empty depth buffer, which is behind everything else.
+    pub(crate) fn clear_depth(self) -> f32 {
+        match self {
+            DepthMode::Standard => 1.0,
+            DepthMode::ReversedInfinite => 0.0,
+        }
+    }
+
+    /// Passes fragments that are at least as close to the camera as the current depth.
+    pub(crate) fn compare_op(self) -> vk::CompareOp {
+        match self {
+            DepthMode::Standard => vk::CompareOp::LESS_OR_EQUAL,
+            DepthMode::ReversedInfinite => vk::CompareOp::GREATER_OR_EQUAL,
+        }
+    }
+}
+
+/// Maximum number of cameras that are rendered in a single frame.
+const MAX_VIEWS: usize = 16;
+
+/// The light shaders of the engine, whose push constants determine the layout of the deferred resolve pass.
+const RESOLVE_LIGHT_SHADERS: [&str; 2] = ["deferred_point_brdf", "deferred_directional_brdf"];
+
+/// Identifies the passes of the frame graph, see [`VulkanManager::declare_frame`].
+///
+/// `view` is the index of the camera that is rendered by the pass.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum FramePass {
+    Culling {
+        view: usize,
+    },
+    Compute(ComputeStage),
+    /// gpass and resolve pass, the images are bound as input attachments
+    Deferred {
+        view: usize,
+        gbuffer0: ImageId,
+        gbuffer1: ImageId,
+        depth: ImageId,
+    },
+    PostProcess {
+        view: usize,
+        effect: usize,
+        src: ImageId,
+    },
+    Ui,
+    /// Clears `dst` to black before the cameras are blitted into their regions
+    Clear {
+        dst: ImageId,
+    },
+    /// Blits all of `src` to the rectangle `dst_rect` (x, y, width and height) of `dst`, e.g. the finished image
+    /// of a camera into its render target, or upscaled into its viewport
+    Blit {
+        src: ImageId,
+        dst: ImageId,
+        dst_rect: [i32; 4],
+    },
+    /// Copies `src` to host memory for the capture request with index `request`
+    Capture {
+        request: usize,
+        src: ImageId,
+    },
+}
+
+/// The destination of finished frames.
+enum FrameOutput {
+    Swapchain(SwapchainWrapper),
+    /// Used when rendering without a window
+    Offscreen(OffscreenTarget),
+}
+
+impl FrameOutput {
+    fn extent(&self) -> vk::Extent2D {
+        match self {
+            FrameOutput::Swapchain(swapchain) => swapchain.extent,
+            FrameOutput::Offscreen(target) => target.extent,
+        }
+    }
+
+    fn format(&self) -> vk::Format {
+        match self {
+            FrameOutput::Swapchain(swapchain) => swapchain.surface_format.format,
+            FrameOutput::Offscreen(_) => OFFSCREEN_FORMAT,
+        }
+    }
+
+    /// Returns whether captures can copy from the output image directly.
+    fn supports_capture(&self) -> bool {
+        match self {
+            FrameOutput::Swapchain(swapchain) => swapchain
+                .image_usage
+                .contains(vk::ImageUsageFlags::TRANSFER_SRC),
+            FrameOutput::Offscreen(_) => true,
+        }
+    }
+
+    /// Imports the output image with index `image_index` into `graph`.
+    fn import(&self, graph: &mut RenderGraph<FramePass>, image_index: usize) -> ImageId {
+        match self {
+            FrameOutput::Swapchain(swapchain) => graph.import_image(
+                "swapchain",
+                swapchain.images[image_index],
+                swapchain.imageviews[image_index],
+                swapchain.surface_format.format,
+                swapchain.extent,
+                None,
+                ImageAccess::Present,
+            ),
+            FrameOutput::Offscreen(target) => graph.import_image(
+                "offscreen",
+                target.image,
+                target.imageview,
+                OFFSCREEN_FORMAT,
+                target.extent,
+                None,
+                ImageAccess::TransferSrc,
+            ),
+        }
+    }
+}
+
+/// The passes of a single camera, see [`VulkanManager::declare_frame`].
+struct ViewDecl<'a> {
+    /// `None` for cameras that render into the window
+    target: Option<&'a Texture2D>,
+    /// the pixels that are covered, in the render target or the frame output
+    region: vk::Rect2D,
+    /// the resolution the camera is rendered at, which is scaled to `region` afterwards
+    extent: vk::Extent2D,
+    pp_effect_count: usize,
+    /// the main camera is used for HDR captures
+    main: bool,
+}
+
+/// A camera that is rendered in the current frame.
+struct FrameView {
+    target: Option<Rc<Texture2D>>,
+    region: vk::Rect2D,
+    extent: vk::Extent2D,
+    main: bool,
+    pp_effects: Vec<Rc<PPEffect>>,
+    /// world space frustum for culling on the GPU
+    frustum: Frustum,
+    /// the draw commands of this camera in the indirect buffer
+    commands: Range<u32>,
+}
+
+/// A range of draw commands that share the same [`Material`] and [`MeshPool`] block.
+struct DrawBatch<'a> {
+    model: &'a Model,
+    first_command: u32,
+    command_count: u32,
 }
 
 pub struct VulkanManager {
@@ -60,13 +263,19 @@ pub struct VulkanManager {
     pub allocator: std::mem::ManuallyDrop<Rc<Allocator>>,
     pub device: Rc<ash::Device>,
 
-    surface: std::mem::ManuallyDrop<SurfaceWrapper>,
+    /// `None` when rendering without a window
+    surface: std::mem::ManuallyDrop<Option<SurfaceWrapper>>,
     physical_device: vk::PhysicalDevice,
     pub(crate) physical_device_properties: vk::PhysicalDeviceProperties,
+    /// The depth mode of the deferred pass, which materials have to be created with.
+    pub depth_mode: DepthMode,
     #[allow(dead_code)]
     queue_families: QueueFamilies,
     pub(crate) queues: Queues,
-    pub(crate) swapchain: SwapchainWrapper,
+    /// shared by all pipelines and saved to disk when the renderer is dropped
+    pipeline_cache: vk::PipelineCache,
+    output: FrameOutput,
+    render_graph: RenderGraph<FramePass>,
     pub renderpass: vk::RenderPass,
     pub(crate) pools: PoolsWrapper,
     pub(crate) commandbuffers: Vec<vk::CommandBuffer>,
@@ -75,7 +284,7 @@ pub struct VulkanManager {
     pipeline_layout_gpass: vk::PipelineLayout,
     pub pipeline_layout_resolve_pass: vk::PipelineLayout,
     pub(crate) descriptor_manager: DescriptorManager<8>,
-    max_frames_in_flight: u8,
+    present_config: PresentConfig,
     pub(crate) current_frame_index: u8,
     image_acquire_semaphores: Vec<vk::Semaphore>,
     render_finished_semaphores: Vec<vk::Semaphore>,
@@ -86,13 +295,29 @@ pub struct VulkanManager {
     pub pipe_layout_pp: vk::PipelineLayout,
     pub renderpass_pp: vk::RenderPass,
     pp_effects: Vec<Rc<PPEffect>>,
+    compute_pipelines: Vec<(ComputeStage, Rc<ComputePipeline>)>,
     pub uploader: std::mem::ManuallyDrop<Uploader>,
+    pub mesh_pool: std::mem::ManuallyDrop<Rc<MeshPool>>,
     pub(crate) enable_wireframe: bool,
     pub(crate) enable_ui_wireframe: bool,
+    pub(crate) enable_frustum_culling: bool,
+    pub(crate) enable_gpu_culling: bool,
+    /// How the resolution of cameras rendering into the window is scaled, applied by [`Self::update_render_scale`].
+    pub resolution: ResolutionScale,
+    render_scale: f32,
+    pub(crate) drawn_object_count: usize,
+    pub(crate) culled_object_count: usize,
+    /// How many frames ago the object counts were recorded, as GPU culling is only counted once a frame has finished
+    pub(crate) object_count_age: u8,
+    /// The objects of the main camera before GPU culling, for every frame index
+    gpu_cull_object_counts: [usize; MAX_FRAMES_IN_FLIGHT as usize],
+    indirect_draw_data: IndirectDrawData,
+    /// true if draw commands can be read from a buffer, which is required for GPU culling
+    pub(crate) indirect_draw_supported: bool,
+    multi_draw_indirect_supported: bool,
 
     desc_layout_ui: vk::DescriptorSetLayout,
     pipe_layout_ui: vk::PipelineLayout,
-    renderpass_ui: vk::RenderPass,
     pipeline_ui: vk::Pipeline,
     pipeline_ui_wireframe: vk::Pipeline,
 
@@ -103,90 +328,120 @@ pub struct VulkanManager {
     ui_index_buffers: Vec<(vk::Buffer, gpu_allocator::vulkan::Allocation, u64)>,
     ui_meshes: Vec<(egui::Rect, u64, u64)>,
 
+    shader_watcher: ShaderWatcher,
+    /// true if changed shaders are recompiled and reloaded, see [`Self::reload_changed_shaders`]
+    pub(crate) enable_shader_hot_reload: bool,
+    /// shader names and errors of the last reload, until the shader reloads successfully
+    pub(crate) shader_errors: Vec<(String, String)>,
+
+    /// captures that will be recorded in the next frame
+    capture_requests: Vec<CaptureRequest>,
+    /// captures of the current frame that are written once it is finished
+    readbacks: Vec<Readback>,
+
     ext_memory_budget_supported: bool,
     pub(crate) supported_devices: Vec<(vk::PhysicalDevice, vk::PhysicalDeviceProperties, bool)>,
 }
 
 impl VulkanManager {
+    /// Creates a renderer that presents to `window`.
+    ///
+    /// Without a window, frames are rendered into an offscreen image with the initial window dimensions of `engine_info`.
     pub(crate) fn new(
         engine_info: EngineInfo,
-        window: &winit::window::Window,
-        max_frames_in_flight: u8,
+        window: Option<&winit::window::Window>,
         config: Option<&RendererConfig>,
     ) -> GraphicsResult<Self> {
         let entry = unsafe { ash::Entry::load() }.map_err(anyhow::Error::from)?;
         let instance = VulkanManager::init_instance(engine_info, &entry, window)?;
 
-        let surface = SurfaceWrapper::init(window, &entry, &instance);
+        let surface = window.map(|window| SurfaceWrapper::init(window, &entry, &instance));
 
         let supported_devices = device::get_candidates(&instance)?;
 
         let (physical_device, physical_device_properties, ext_memory_budget_supported) =
             device::select_physical_device(&instance, config)?;
 
-        let queue_families = QueueFamilies::init(&instance, physical_device, &surface)?;
+        let queue_families = QueueFamilies::init(&instance, physical_device, surface.as_ref())?;
 
-        let (logical_device, queues) = queue::init_device_and_queues(
+        let (logical_device, queues, enabled_features) = queue::init_device_and_queues(
             &instance,
             physical_device,
             &queue_families,
+            surface.is_some(),
             ext_memory_budget_supported,
         )?;
 
         let logical_device = Rc::new(logical_device);
 
+        // created before any pipeline, all of them use it
+        let pipeline_cache = pipeline_cache::create(&logical_device, &physical_device_properties)?;
+
         let allocator = Rc::new(Allocator::new(
             instance.clone(),
             physical_device,
             (*logical_device).clone(),
         ));
 
-        let mut swapchain = SwapchainWrapper::init(
+        let resolution = config.map(|c| c.resolution).unwrap_or_default().clamped();
+        let present_config = config.map(|c| c.present).unwrap_or_default().clamped();
+
+        let depth_mode = Self::select_depth_mode(
             &instance,
             physical_device,
-            &logical_device,
-            &surface,
-            &queue_families,
-            &allocator,
-        )?;
+            config.map(|c| c.depth_mode).unwrap_or_default(),
+        );
 
-        let renderpass_pp_attachments = [vk::AttachmentDescription::builder()
-            .format(vk::Format::R16G16B16A16_SFLOAT)
-            .samples(vk::SampleCountFlags::TYPE_1)
-            .load_op(vk::AttachmentLoadOp::DONT_CARE)
-            .store_op(vk::AttachmentStoreOp::STORE)
-            .initial_layout(vk::ImageLayout::UNDEFINED)
-            .final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
-            .build()];
-        let renderpass_pp_sub0_refs = [vk::AttachmentReference::builder()
-            .attachment(0)
-            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
-            .build()];
-        let renderpass_pp_sub_info = [vk::SubpassDescription::builder()
-            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
-            .color_attachments(&renderpass_pp_sub0_refs)
-            .build()];
-        let renderpass_pp_deps = [vk::SubpassDependency::builder()
-            .src_subpass(vk::SUBPASS_EXTERNAL)
-            .dst_subpass(0)
-            .src_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
-            .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
-            .src_access_mask(vk::AccessFlags::SHADER_READ)
-            .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
-            .build()];
-        let renderpass_pp_info = vk::RenderPassCreateInfo::builder()
-            .attachments(&renderpass_pp_attachments)
-            .subpasses(&renderpass_pp_sub_info)
-            .dependencies(&renderpass_pp_deps)
-            .build();
-        let renderpass_pp =
-            unsafe { logical_device.create_render_pass(&renderpass_pp_info, None)? };
+        let output = match &surface {
+            Some(surface) => FrameOutput::Swapchain(SwapchainWrapper::init(
+                &instance,
+                physical_device,
+                &logical_device,
+                surface,
+                &queue_families,
+                &present_config,
+            )?),
+            None => FrameOutput::Offscreen(OffscreenTarget::new(
+                &logical_device,
+                &allocator,
+                vk::Extent2D {
+                    width: engine_info.window_info.initial_dimensions.width,
+                    height: engine_info.window_info.initial_dimensions.height,
+                },
+            )?),
+        };
 
-        let renderpass = renderpass::create_deferred_pass(
-            vk::Format::R16G16B16A16_SFLOAT,
-            vk::Format::D24_UNORM_S8_UINT,
-            &logical_device,
-        )?;
+        // compile the graph once to create the renderpasses that pipelines are created with
+        let mut render_graph = RenderGraph::new(logical_device.clone());
+        let main_view = ViewDecl {
+            target: None,
+            region: vk::Rect2D {
+                offset: vk::Offset2D::default(),
+                extent: output.extent(),
+            },
+            extent: output.extent(),
+            pp_effect_count: 1,
+            main: true,
+        };
+        Self::declare_frame(
+            &mut render_graph,
+            &output,
+            0,
+            &[main_view],
+            depth_mode,
+            false,
+            &[],
+        );
+        render_graph.compile(&allocator)?;
+        let renderpass = render_graph
+            .get_renderpass(|pass| matches!(pass, FramePass::Deferred { .. }))
+            .unwrap();
+        let renderpass_pp = render_graph
+            .get_renderpass(|pass| matches!(pass, FramePass::PostProcess { .. }))
+            .unwrap();
+        let renderpass_ui = render_graph
+            .get_renderpass(|pass| *pass == FramePass::Ui)
+            .unwrap();
 
         let sampler_linear_info = vk::SamplerCreateInfo::builder()
             .mag_filter(vk::Filter::LINEAR)
@@ -200,19 +455,20 @@ impl VulkanManager {
             .build();
         let sampler_linear = unsafe { logical_device.create_sampler(&sampler_linear_info, None)? };
 
-        let (desc_layout_ui, pipe_layout_ui, renderpass_ui, pipeline_ui, pipeline_ui_wireframe) =
-            pipeline::create_ui_pipeline(&logical_device, sampler_linear);
+        let (desc_layout_ui, pipe_layout_ui, pipeline_ui, pipeline_ui_wireframe) =
+            pipeline::create_ui_pipeline(&logical_device, sampler_linear, renderpass_ui)?;
 
-        swapchain.create_framebuffers(&logical_device, renderpass, renderpass_pp)?;
         let pools = PoolsWrapper::init(&logical_device, &queue_families)?;
 
+        // per-frame resources are created for the maximum, so that the frames in flight can change at runtime
         let commandbuffers =
-            queue::create_commandbuffers(&logical_device, &pools, max_frames_in_flight as usize)?;
+            queue::create_commandbuffers(&logical_device, &pools, MAX_FRAMES_IN_FLIGHT as usize)?;
 
+        // one slot for every camera in every frame, see `uniform_slot`
         let uniform_buffer = PerFrameUniformBuffer::<CameraUniformData>::new(
             &physical_device_properties,
             &allocator,
-            max_frames_in_flight as u64,
+            MAX_FRAMES_IN_FLIGHT as u64 * MAX_VIEWS as u64,
             vk::BufferUsageFlags::UNIFORM_BUFFER,
         )?;
 
@@ -245,6 +501,13 @@ impl VulkanManager {
                 .descriptor_count(1)
                 .stage_flags(vk::ShaderStageFlags::FRAGMENT)
                 .build(),
+            // Transforms
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(4)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::VERTEX)
+                .build(),
         ];
         let desc_layout_frame_data_info = vk::DescriptorSetLayoutCreateInfo::builder()
             .bindings(&desc_layout_frame_data_bindings)
@@ -253,24 +516,21 @@ impl VulkanManager {
             logical_device.create_descriptor_set_layout(&desc_layout_frame_data_info, None)?
         };
 
-        let pipeline_layout_gpass_push_constants = [vk::PushConstantRange::builder()
-            .stage_flags(vk::ShaderStageFlags::VERTEX)
-            .offset(0)
-            .size(128)
-            .build()];
         let pipeline_layout_gpass_bindings = [desc_layout_frame_data];
         let pipeline_layout_gpass_info = vk::PipelineLayoutCreateInfo::builder()
             .set_layouts(&pipeline_layout_gpass_bindings)
-            .push_constant_ranges(&pipeline_layout_gpass_push_constants)
             .build();
         let pipeline_layout_gpass =
             unsafe { logical_device.create_pipeline_layout(&pipeline_layout_gpass_info, None)? };
 
-        let pipeline_layout_resolve_pass_push_constants = [vk::PushConstantRange::builder()
-            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
-            .offset(0)
-            .size(32)
-            .build()];
+        // all LightingPipelines share this layout, so it has to fit the lights of every resolve shader
+        let mut resolve_push_constants = Vec::new();
+        for name in RESOLVE_LIGHT_SHADERS {
+            let code = shader::load(name, shader::ShaderKind::Fragment)?;
+            resolve_push_constants.extend(ve_shader_reflect::reflect_shader(&code)?.push_constants);
+        }
+        let pipeline_layout_resolve_pass_push_constants =
+            pipeline::push_constant_ranges(&resolve_push_constants);
         let pipeline_layout_resolve_pass_bindings = [desc_layout_frame_data];
         let pipeline_layout_resolve_pass_info = vk::PipelineLayoutCreateInfo::builder()
             .set_layouts(&pipeline_layout_resolve_pass_bindings)
@@ -282,16 +542,23 @@ impl VulkanManager {
 
         let descriptor_manager = DescriptorManager::new((*logical_device).clone())?;
 
+        let indirect_draw_data =
+            IndirectDrawData::new(logical_device.clone(), &allocator, MAX_FRAMES_IN_FLIGHT)?;
+        let indirect_draw_supported = enabled_features.draw_indirect_first_instance == vk::TRUE;
+        if !indirect_draw_supported {
+            log::warn!("drawIndirectFirstInstance is not supported, falling back to direct drawing without GPU culling");
+        }
+
         let sem_info = vk::SemaphoreCreateInfo::builder().build();
         let fence_info = vk::FenceCreateInfo::builder()
             .flags(vk::FenceCreateFlags::SIGNALED)
             .build();
 
-        let mut image_acquire_semaphores = Vec::with_capacity(max_frames_in_flight as usize);
-        let mut render_finished_semaphores = Vec::with_capacity(max_frames_in_flight as usize);
-        let mut frame_resource_fences = Vec::with_capacity(max_frames_in_flight as usize);
+        let mut image_acquire_semaphores = Vec::with_capacity(MAX_FRAMES_IN_FLIGHT as usize);
+        let mut render_finished_semaphores = Vec::with_capacity(MAX_FRAMES_IN_FLIGHT as usize);
+        let mut frame_resource_fences = Vec::with_capacity(MAX_FRAMES_IN_FLIGHT as usize);
 
-        for _ in 0..max_frames_in_flight {
+        for _ in 0..MAX_FRAMES_IN_FLIGHT {
             image_acquire_semaphores
                 .push(unsafe { logical_device.create_semaphore(&sem_info, None)? });
             render_finished_semaphores
@@ -323,12 +590,17 @@ impl VulkanManager {
         let uploader = Uploader::new(
             logical_device.clone(),
             allocator.clone(),
-            max_frames_in_flight as u64,
+            MAX_FRAMES_IN_FLIGHT as u64,
             queue_families.graphics_q_index,
         );
 
-        let mut ui_vertex_buffers = Vec::with_capacity(max_frames_in_flight as usize);
-        for _ in 0..max_frames_in_flight {
+        let mesh_pool = Rc::new(MeshPool::new(
+            allocator.clone(),
+            MAX_FRAMES_IN_FLIGHT as u64,
+        ));
+
+        let mut ui_vertex_buffers = Vec::with_capacity(MAX_FRAMES_IN_FLIGHT as usize);
+        for _ in 0..MAX_FRAMES_IN_FLIGHT {
             let (buffer, alloc) = allocator
                 .create_buffer(
                     20 * 1000,
@@ -339,8 +611,8 @@ impl VulkanManager {
             ui_vertex_buffers.push((buffer, alloc, 1000));
         }
 
-        let mut ui_index_buffers = Vec::with_capacity(max_frames_in_flight as usize);
-        for _ in 0..max_frames_in_flight {
+        let mut ui_index_buffers = Vec::with_capacity(MAX_FRAMES_IN_FLIGHT as usize);
+        for _ in 0..MAX_FRAMES_IN_FLIGHT {
             let (buffer, alloc) = allocator
                 .create_buffer(
                     4 * 1000,
@@ -357,10 +629,13 @@ impl VulkanManager {
             surface: std::mem::ManuallyDrop::new(surface),
             physical_device,
             physical_device_properties,
+            depth_mode,
             queue_families,
             queues,
+            pipeline_cache,
             device: logical_device,
-            swapchain,
+            output,
+            render_graph,
             renderpass,
             pools,
             commandbuffers,
@@ -370,7 +645,7 @@ impl VulkanManager {
             pipeline_layout_gpass,
             pipeline_layout_resolve_pass,
             descriptor_manager,
-            max_frames_in_flight,
+            present_config,
             current_frame_index: 0,
             image_acquire_semaphores,
             render_finished_semaphores,
@@ -381,13 +656,25 @@ impl VulkanManager {
             pipe_layout_pp,
             renderpass_pp,
             pp_effects: Vec::new(),
+            compute_pipelines: Vec::new(),
             uploader: std::mem::ManuallyDrop::new(uploader),
+            mesh_pool: std::mem::ManuallyDrop::new(mesh_pool),
             enable_wireframe: false,
             enable_ui_wireframe: false,
+            enable_frustum_culling: true,
+            enable_gpu_culling: true,
+            resolution,
+            render_scale: resolution.next_scale(1.0, &[]),
+            drawn_object_count: 0,
+            culled_object_count: 0,
+            object_count_age: 0,
+            gpu_cull_object_counts: [0; MAX_FRAMES_IN_FLIGHT as usize],
+            indirect_draw_data,
+            indirect_draw_supported,
+            multi_draw_indirect_supported: enabled_features.multi_draw_indirect == vk::TRUE,
 
             desc_layout_ui,
             pipe_layout_ui,
-            renderpass_ui,
             pipeline_ui,
             pipeline_ui_wireframe,
 
@@ -398,6 +685,13 @@ impl VulkanManager {
             ui_index_buffers,
             ui_meshes: Vec::new(),
 
+            shader_watcher: ShaderWatcher::new(),
+            enable_shader_hot_reload: true,
+            shader_errors: Vec::new(),
+
+            capture_requests: Vec::new(),
+            readbacks: Vec::new(),
+
             ext_memory_budget_supported,
             supported_devices,
         })
@@ -431,10 +725,126 @@ impl VulkanManager {
         self.pp_effects.push(pp_effect);
     }
 
+    /// Registers a [`ComputePipeline`] that is dispatched every frame at the given [`ComputeStage`].
+    ///
+    /// Pipelines of the same stage are dispatched in the order they were registered.
+    pub fn register_compute_pipeline(
+        &mut self,
+        stage: ComputeStage,
+        pipeline: Rc<ComputePipeline>,
+    ) {
+        self.compute_pipelines.push((stage, pipeline));
+    }
+
+    /// Recompiles and reloads all shaders whose source changed, if [`Self::enable_shader_hot_reload`] is set.
+    pub(crate) fn reload_changed_shaders(&mut self) {
+        if !self.enable_shader_hot_reload {
+            return;
+        }
+        let changed = self.shader_watcher.poll();
+        if !changed.is_empty() {
+            self.reload_shaders(&changed);
+        }
+    }
+
+    /// Recompiles and reloads every shader, e.g. after headers were changed outside of the watched directory.
+    pub(crate) fn reload_all_shaders(&mut self) {
+        let names = self.shader_watcher.all_shaders();
+        self.reload_shaders(&names);
+    }
+
+    /// Recompiles the given shaders and rebuilds the material, lighting and post processing pipelines that use them.
+    ///
+    /// Pipelines keep their old shader code if compiling or rebuilding fails,
+    /// the errors are kept in [`Self::shader_errors`] until the shader reloads successfully.
+    fn reload_shaders(&mut self, names: &[String]) {
+        profile_function!();
+
+        let mut compiled = Vec::new();
+        for name in names {
+            self.shader_errors.retain(|(shader, _)| shader != name);
+            match shader::compile(name) {
+                Ok(()) => compiled.push(name),
+                Err(e) => {
+                    log::error!("Failed to compile shader {}:\n{}", name, e);
+                    self.shader_errors.push((name.clone(), e));
+                }
+            }
+        }
+        if compiled.is_empty() {
+            return;
+        }
+
+        // the old pipelines may still be used by frames in flight
+        unsafe {
+            self.device
+                .device_wait_idle()
+                .expect("something went wrong while waiting");
+        }
+
+        let material_pipelines = MaterialPipeline::live_pipelines();
+        // includes the effects of cameras with their own post processing
+        let pp_effects = PPEffect::live_effects();
+        for name in compiled {
+            let mut errors = Vec::new();
+            for pipeline in material_pipelines.iter().filter(|p| p.uses_shader(name)) {
+                if let Err(e) = pipeline.reload() {
+                    errors.push(e.to_string());
+                }
+            }
+            for pipeline in self
+                .lighting_pipelines
+                .iter()
+                .filter(|p| p.uses_shader(name))
+            {
+                if let Err(e) = pipeline.reload(name) {
+                    errors.push(e.to_string());
+                }
+            }
+            for effect in pp_effects.iter().filter(|e| e.uses_shader(name)) {
+                if let Err(e) = effect.reload() {
+                    errors.push(e.to_string());
+                }
+            }
+
+            if errors.is_empty() {
+                log::info!("Reloaded shader {}", name);
+            } else {
+                let error = errors.join("\n");
+                log::error!("Failed to reload shader {}:\n{}", name, error);
+                self.shader_errors.push((name.clone(), error));
+            }
+        }
+    }
+
+    /// Returns `requested`, or [`DepthMode::Standard`] if the device does not support its depth format.
+    fn select_depth_mode(
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+        requested: DepthMode,
+    ) -> DepthMode {
+        let properties = unsafe {
+            instance.get_physical_device_format_properties(physical_device, requested.format())
+        };
+        if properties
+            .optimal_tiling_features
+            .contains(vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT)
+        {
+            requested
+        } else {
+            log::warn!(
+                "Depth format {:?} is not supported, falling back to {:?}",
+                requested.format(),
+                DepthMode::Standard
+            );
+            DepthMode::Standard
+        }
+    }
+
     fn init_instance(
         engine_info: EngineInfo,
         entry: &ash::Entry,
-        window: &winit::window::Window,
+        window: Option<&winit::window::Window>,
     ) -> anyhow::Result<ash::Instance> {
         let app_name = CString::new(engine_info.app_name).unwrap();
 
@@ -445,11 +855,14 @@ impl VulkanManager {
             .engine_version(vk::make_api_version(0, 0, 1, 0))
             .api_version(vk::API_VERSION_1_2);
 
-        let surface_extensions = ash_window::enumerate_required_extensions(window).unwrap();
-        let extension_names_raw = surface_extensions
-            .iter()
-            .map(|ext| ext.as_ptr())
-            .collect::<Vec<_>>();
+        let extension_names_raw = match window {
+            Some(window) => ash_window::enumerate_required_extensions(window)
+                .unwrap()
+                .iter()
+                .map(|ext| ext.as_ptr())
+                .collect::<Vec<_>>(),
+            None => Vec::new(),
+        };
 
         let instance_create_info = vk::InstanceCreateInfo::builder()
             .application_info(&app_info)
@@ -461,10 +874,15 @@ impl VulkanManager {
     pub(crate) fn next_frame(&mut self) -> u32 {
         profile_function!();
 
-        self.current_frame_index = (self.current_frame_index + 1) % self.max_frames_in_flight;
+        self.current_frame_index =
+            (self.current_frame_index + 1) % self.present_config.frames_in_flight;
 
-        self.swapchain
-            .aquire_next_image(self.image_acquire_semaphores[self.current_frame_index as usize])
+        match &self.output {
+            FrameOutput::Swapchain(swapchain) => swapchain.aquire_next_image(
+                self.image_acquire_semaphores[self.current_frame_index as usize],
+            ),
+            FrameOutput::Offscreen(_) => 0,
+        }
     }
 
     fn set_viewport(&self, commandbuffer: vk::CommandBuffer, width: f32, height: f32) {
@@ -499,44 +917,47 @@ impl VulkanManager {
         }
     }
 
+    /// Blits all of `src` to `dst_rect` (x, y, width and height) of `dst`, with linear filtering if the sizes differ.
     fn blit_image(
         &self,
         commandbuffer: vk::CommandBuffer,
         src: vk::Image,
+        src_extent: vk::Extent2D,
         dst: vk::Image,
-        width: i32,
-        height: i32,
+        dst_rect: [i32; 4],
     ) {
+        let [x, y, width, height] = dst_rect;
+        let subresource = vk::ImageSubresourceLayers {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            mip_level: 0,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
         let blit = vk::ImageBlit {
-            src_subresource: vk::ImageSubresourceLayers {
-                aspect_mask: vk::ImageAspectFlags::COLOR,
-                mip_level: 0,
-                base_array_layer: 0,
-                layer_count: 1,
-            },
+            src_subresource: subresource,
             src_offsets: [
                 vk::Offset3D { x: 0, y: 0, z: 0 },
                 vk::Offset3D {
-                    x: width,
-                    y: height,
+                    x: src_extent.width as i32,
+                    y: src_extent.height as i32,
                     z: 1,
                 },
             ],
-            dst_subresource: vk::ImageSubresourceLayers {
-                aspect_mask: vk::ImageAspectFlags::COLOR,
-                mip_level: 0,
-                base_array_layer: 0,
-                layer_count: 1,
-            },
+            dst_subresource: subresource,
             dst_offsets: [
-                vk::Offset3D { x: 0, y: 0, z: 0 },
+                vk::Offset3D { x, y, z: 0 },
                 vk::Offset3D {
-                    x: width,
-                    y: height,
+                    x: x + width,
+                    y: y + height,
                     z: 1,
                 },
             ],
         };
+        let filter = if src_extent.width as i32 == width && src_extent.height as i32 == height {
+            vk::Filter::NEAREST
+        } else {
+            vk::Filter::LINEAR
+        };
         unsafe {
             self.device.cmd_blit_image(
                 commandbuffer,
@@ -545,115 +966,176 @@ impl VulkanManager {
                 dst,
                 vk::ImageLayout::TRANSFER_DST_OPTIMAL,
                 &[blit],
-                vk::Filter::NEAREST,
+                filter,
             );
         }
     }
 
-    fn transition_images<const N: usize>(
-        &self,
-        commandbuffer: vk::CommandBuffer,
-        src_stages: vk::PipelineStageFlags,
-        dst_stages: vk::PipelineStageFlags,
-        transitions: &[ImageTransition; N],
+    /// Removes every model whose bounding sphere lies completely outside of the given view space frustum.
+    fn cull_renderables(
+        models: &mut Vec<(TransformData, Rc<Model>)>,
+        view_matrix: Mat4,
+        frustum: &Frustum,
     ) {
-        let mut vk_transitions = [vk::ImageMemoryBarrier::builder()
-            .subresource_range(vk::ImageSubresourceRange {
-                aspect_mask: vk::ImageAspectFlags::COLOR,
-                base_mip_level: 0,
-                level_count: 1,
-                base_array_layer: 0,
-                layer_count: 1,
+        profile_function!();
+
+        models.retain(|(transform, model)| {
+            let sphere = model
+                .mesh
+                .bounding_sphere
+                .transform(view_matrix * transform.model_matrix);
+            frustum.intersects_sphere(&sphere)
+        });
+    }
+
+    /// Sorts the models by pipeline, material, mesh and view depth (front to back) to minimize state changes.
+    fn build_render_order(
+        models: &[(TransformData, Rc<Model>)],
+        view_matrix: Mat4,
+        near: f32,
+    ) -> Vec<(TransformData, &Model)> {
+        profile_function!();
+
+        let mut keyed: Vec<(SortKey, (TransformData, &Model))> = models
+            .iter()
+            .map(|(transform, model)| {
+                let center = model.mesh.bounding_sphere.center;
+                let view_pos = view_matrix
+                    * transform.model_matrix
+                    * Vec4::new(center.x, center.y, center.z, 1.0);
+
+                let key = SortKey::new(
+                    model.material.get_pipeline_sort_id(),
+                    model.material.get_sort_id(),
+                    model.mesh.sort_id,
+                    render_order::normalized_depth(view_pos.z, near),
+                );
+                (key, (*transform, model.as_ref()))
             })
-            .build(); N];
+            .collect();
+
+        render_order::sort(&mut keyed);
+
+        keyed.into_iter().map(|(_, obj)| obj).collect()
+    }
 
-        for (t, vk_t) in transitions.iter().zip(vk_transitions.iter_mut()) {
-            vk_t.image = t.image;
-            vk_t.old_layout = t.from;
-            vk_t.new_layout = t.to;
-            vk_t.src_access_mask = t.wait_access;
-            vk_t.dst_access_mask = t.dst_access;
+    /// Creates one draw command per submesh and groups consecutive commands sharing a Material and MeshPool block into batches.
+    ///
+    /// The object index is passed as first instance, so the vertex shader can fetch the matching transform.
+    /// Object and command indices start at `first_object` and `first_command`, so that the results of several
+    /// cameras can be stored in the same buffers.
+    fn build_draw_batches<'a>(
+        models: &[(TransformData, &'a Model)],
+        first_object: u32,
+        first_command: u32,
+    ) -> (Vec<DrawBatch<'a>>, Vec<vk::DrawIndexedIndirectCommand>) {
+        profile_function!();
+
+        let mut batches: Vec<DrawBatch> = Vec::new();
+        let mut draw_commands = Vec::with_capacity(models.len());
+
+        for (object_index, (_, model)) in models.iter().enumerate() {
+            let model_commands = draw_commands.len() as u32;
+            for sm in &model.mesh.submeshes {
+                draw_commands.push(vk::DrawIndexedIndirectCommand {
+                    index_count: sm.1,
+                    instance_count: 1,
+                    first_index: sm.0,
+                    vertex_offset: model.mesh.allocation.first_vertex as i32,
+                    first_instance: first_object + object_index as u32,
+                });
+            }
+            let command_count = draw_commands.len() as u32 - model_commands;
+
+            match batches.last_mut() {
+                Some(batch)
+                    if Rc::ptr_eq(&batch.model.material, &model.material)
+                        && batch.model.mesh.allocation.block == model.mesh.allocation.block =>
+                {
+                    batch.command_count += command_count;
+                }
+                _ => batches.push(DrawBatch {
+                    model,
+                    first_command: first_command + model_commands,
+                    command_count,
+                }),
+            }
+        }
+
+        (batches, draw_commands)
+    }
+
+    /// Records all compute pipelines registered for `stage`, which has to happen outside of a renderpass.
+    fn record_compute_stage(
+        &mut self,
+        commandbuffer: vk::CommandBuffer,
+        stage: ComputeStage,
+    ) -> GraphicsResult<()> {
+        profile_function!();
+
+        if !self.compute_pipelines.iter().any(|(s, _)| *s == stage) {
+            return Ok(());
         }
 
+        // make previous writes visible to the compute shaders and their results to everything afterwards
+        let barrier = vk::MemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::MEMORY_WRITE)
+            .dst_access_mask(vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE)
+            .build();
         unsafe {
             self.device.cmd_pipeline_barrier(
                 commandbuffer,
-                src_stages,
-                dst_stages,
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
                 vk::DependencyFlags::empty(),
+                &[barrier],
                 &[],
                 &[],
-                &vk_transitions,
             );
         }
-    }
 
-    fn begin_renderpass(
-        &self,
-        commandbuffer: vk::CommandBuffer,
-        renderpass: vk::RenderPass,
-        framebuffer: vk::Framebuffer,
-        clear_values: &[vk::ClearValue],
-    ) {
-        let info = vk::RenderPassBeginInfo::builder()
-            .render_pass(renderpass)
-            .framebuffer(framebuffer)
-            .render_area(vk::Rect2D {
-                offset: vk::Offset2D { x: 0, y: 0 },
-                extent: self.swapchain.extent,
-            })
-            .clear_values(clear_values);
-        unsafe {
-            self.device
-                .cmd_begin_render_pass(commandbuffer, &info, vk::SubpassContents::INLINE);
+        for (_, pipeline) in self.compute_pipelines.iter().filter(|(s, _)| *s == stage) {
+            pipeline.record(commandbuffer, &mut self.descriptor_manager)?;
         }
-    }
-
-    fn build_render_order(models: &[(TransformData, Rc<Model>)]) -> Vec<(TransformData, &Model)> {
-        profile_function!();
-
-        let mut res: Vec<(TransformData, &Model)> = Vec::with_capacity(models.len());
-
-        for obj in models {
-            let mut index = 0;
-            for cmp in &res {
-                // order: pipeline -> material -> mesh
-                if cmp.1.material.get_pipeline().as_raw() > obj.1.material.get_pipeline().as_raw() {
-                    break;
-                }
-                if cmp.1.material.as_ref() as *const Material as *const u8
-                    > obj.1.material.as_ref() as *const Material as *const u8
-                {
-                    break;
-                }
-                if cmp.1.mesh.as_ref() as *const Mesh > obj.1.mesh.as_ref() as *const Mesh {
-                    break;
-                }
 
-                index += 1;
-            }
-            res.insert(index, (obj.0, obj.1.as_ref()));
+        unsafe {
+            self.device.cmd_pipeline_barrier(
+                commandbuffer,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                vk::DependencyFlags::empty(),
+                &[barrier],
+                &[],
+                &[],
+            );
         }
 
-        res
+        Ok(())
     }
 
     fn render_gpass(
         &mut self,
         commandbuffer: vk::CommandBuffer,
-        models: &[(TransformData, &Model)],
+        batches: &[DrawBatch],
+        draw_commands: &[vk::DrawIndexedIndirectCommand],
+        extent: vk::Extent2D,
     ) -> Result<(), vk::Result> {
         profile_function!();
 
+        let draw_command_buffer = self
+            .indirect_draw_data
+            .get_draw_command_buffer(self.current_frame_index);
+        let stride = size_of::<vk::DrawIndexedIndirectCommand>() as u32;
+
         let mut last_pipeline = vk::Pipeline::null();
         let mut last_mat: *const u8 = null();
-        let mut last_mesh: *const Mesh = null();
-        for obj in models {
+        let mut last_block = None;
+        for batch in batches {
             unsafe {
                 let pipeline = if self.enable_wireframe {
-                    obj.1.material.get_wireframe_pipeline()
+                    batch.model.material.get_wireframe_pipeline()
                 } else {
-                    obj.1.material.get_pipeline()
+                    batch.model.material.get_pipeline()
                 };
 
                 if last_pipeline != pipeline {
@@ -662,26 +1144,22 @@ impl VulkanManager {
                         vk::PipelineBindPoint::GRAPHICS,
                         pipeline,
                     );
-                    self.set_viewport(
-                        commandbuffer,
-                        self.swapchain.extent.width as f32,
-                        self.swapchain.extent.height as f32,
-                    );
+                    self.set_viewport(commandbuffer, extent.width as f32, extent.height as f32);
 
-                    last_pipeline = obj.1.material.get_pipeline();
+                    last_pipeline = batch.model.material.get_pipeline();
                     last_mat = null();
                 }
 
-                let mat = obj.1.material.as_ref() as *const Material as *const u8; // see https://doc.rust-lang.org/std/ptr/fn.eq.html
+                let mat = batch.model.material.as_ref() as *const Material as *const u8; // see https://doc.rust-lang.org/std/ptr/fn.eq.html
                 if mat != last_mat {
                     let mat_desc_set = self.descriptor_manager.get_descriptor_set(
-                        obj.1.material.get_descriptor_set_layout(),
-                        &obj.1.material.get_descriptor_data(),
+                        batch.model.material.get_descriptor_set_layout(),
+                        &batch.model.material.get_descriptor_data(),
                     )?;
                     self.device.cmd_bind_descriptor_sets(
                         commandbuffer,
                         vk::PipelineBindPoint::GRAPHICS,
-                        obj.1.material.get_pipeline_layout(),
+                        batch.model.material.get_pipeline_layout(),
                         1,
                         &[mat_desc_set],
                         &[],
@@ -690,34 +1168,51 @@ impl VulkanManager {
                     last_mat = mat;
                 }
 
-                let mesh = obj.1.mesh.as_ref() as *const Mesh;
-                if mesh != last_mesh {
-                    self.device.cmd_bind_vertex_buffers(
-                        commandbuffer,
-                        0,
-                        &[obj.1.mesh.vertex_buffer],
-                        &[0],
-                    );
+                let block = batch.model.mesh.allocation.block;
+                if last_block != Some(block) {
+                    let (vertex_buffer, index_buffer) = self.mesh_pool.get_buffers(block);
+                    self.device
+                        .cmd_bind_vertex_buffers(commandbuffer, 0, &[vertex_buffer], &[0]);
                     self.device.cmd_bind_index_buffer(
                         commandbuffer,
-                        obj.1.mesh.index_buffer,
+                        index_buffer,
                         0,
                         vk::IndexType::UINT32,
                     );
 
-                    last_mesh = mesh;
+                    last_block = Some(block);
                 }
 
-                self.push_constants(
-                    commandbuffer,
-                    obj.1.material.get_pipeline_layout(),
-                    vk::ShaderStageFlags::VERTEX,
-                    &obj.0,
-                );
-
-                for sm in &obj.1.mesh.submeshes {
-                    self.device
-                        .cmd_draw_indexed(commandbuffer, sm.1, 1, sm.0, 0, 0);
+                let commands = batch.first_command..batch.first_command + batch.command_count;
+                if !self.indirect_draw_supported {
+                    for cmd in &draw_commands[commands.start as usize..commands.end as usize] {
+                        self.device.cmd_draw_indexed(
+                            commandbuffer,
+                            cmd.index_count,
+                            cmd.instance_count,
+                            cmd.first_index,
+                            cmd.vertex_offset,
+                            cmd.first_instance,
+                        );
+                    }
+                } else if self.multi_draw_indirect_supported {
+                    self.device.cmd_draw_indexed_indirect(
+                        commandbuffer,
+                        draw_command_buffer,
+                        (commands.start * stride) as u64,
+                        batch.command_count,
+                        stride,
+                    );
+                } else {
+                    for cmd in commands {
+                        self.device.cmd_draw_indexed_indirect(
+                            commandbuffer,
+                            draw_command_buffer,
+                            (cmd * stride) as u64,
+                            1,
+                            stride,
+                        );
+                    }
                 }
             }
         }
@@ -725,23 +1220,24 @@ impl VulkanManager {
         Ok(())
     }
 
-    fn render_resolve_pass(&self, commandbuffer: vk::CommandBuffer, lights: &[Light]) {
+    fn render_resolve_pass(
+        &self,
+        commandbuffer: vk::CommandBuffer,
+        lights: &[Light],
+        extent: vk::Extent2D,
+    ) {
         profile_function!();
 
         for lp in &self.lighting_pipelines {
             unsafe {
                 // point lights
-                if let Some(point_pipe) = lp.point_pipeline {
+                if let Some(point_pipe) = lp.point_pipeline.get() {
                     self.device.cmd_bind_pipeline(
                         commandbuffer,
                         vk::PipelineBindPoint::GRAPHICS,
                         point_pipe,
                     );
-                    self.set_viewport(
-                        commandbuffer,
-                        self.swapchain.extent.width as f32,
-                        self.swapchain.extent.height as f32,
-                    );
+                    self.set_viewport(commandbuffer, extent.width as f32, extent.height as f32);
 
                     for pl in lights.iter().filter_map(|l| {
                         if let Light::Point(pl) = l {
@@ -754,24 +1250,20 @@ impl VulkanManager {
                             commandbuffer,
                             self.pipeline_layout_resolve_pass,
                             vk::ShaderStageFlags::FRAGMENT,
-                            pl,
+                            &PointLightData::from(pl),
                         );
                         self.device.cmd_draw(commandbuffer, 6, 1, 0, 0);
                     }
                 }
 
                 // directional lights
-                if let Some(directional_pipe) = lp.directional_pipeline {
+                if let Some(directional_pipe) = lp.directional_pipeline.get() {
                     self.device.cmd_bind_pipeline(
                         commandbuffer,
                         vk::PipelineBindPoint::GRAPHICS,
                         directional_pipe,
                     );
-                    self.set_viewport(
-                        commandbuffer,
-                        self.swapchain.extent.width as f32,
-                        self.swapchain.extent.height as f32,
-                    );
+                    self.set_viewport(commandbuffer, extent.width as f32, extent.height as f32);
 
                     for dl in lights.iter().filter_map(|l| {
                         if let Light::Directional(dl) = l {
@@ -784,142 +1276,71 @@ impl VulkanManager {
                             commandbuffer,
                             self.pipeline_layout_resolve_pass,
                             vk::ShaderStageFlags::FRAGMENT,
-                            dl,
+                            &DirectionalLightData::from(dl),
                         );
                         self.device.cmd_draw(commandbuffer, 6, 1, 0, 0);
                     }
                 }
 
                 // ambient
-                if let Some(ambient_pipe) = lp.ambient_pipeline {
+                if let Some(ambient_pipe) = lp.ambient_pipeline.get() {
                     self.device.cmd_bind_pipeline(
                         commandbuffer,
                         vk::PipelineBindPoint::GRAPHICS,
                         ambient_pipe,
                     );
-                    self.set_viewport(
-                        commandbuffer,
-                        self.swapchain.extent.width as f32,
-                        self.swapchain.extent.height as f32,
-                    );
+                    self.set_viewport(commandbuffer, extent.width as f32, extent.height as f32);
                     self.device.cmd_draw(commandbuffer, 6, 1, 0, 0);
                 }
             }
         }
     }
 
-    fn render_pp(&mut self, commandbuffer: vk::CommandBuffer) -> Result<bool, vk::Result> {
+    /// Draws a single post processing effect that samples `src`.
+    fn render_pp(
+        &mut self,
+        commandbuffer: vk::CommandBuffer,
+        effect: &PPEffect,
+        src: ImageId,
+        extent: vk::Extent2D,
+    ) -> Result<(), vk::Result> {
         profile_function!();
-        // resolve image contains finished scene rendering in hdr format
-        // for each pp effect:
-        //      - transition src image (either resolve_image or g0_image) to SHADER_READONLY_OPTIMAL layout (done by previous pp renderpass and resolve pass)
-        //      - transition dst image (either resolve_image or g0_image) to COLOR_ATTACHMENT_OPTIMAL layout (done by renderpass)
-        //      - begin pp renderpass with correct framebuffer
-        //      - bind pipeline and correct descriptor set
-        //      - draw fullscreen quad
-        // transition final dst image (either resolve_image or g0_image) to COLOR_ATTACHMENT_OPTIMAL
-
-        let mut direction = false;
-
-        for effect in &self.pp_effects {
-            // begin renderpass
-            self.begin_renderpass(
+
+        // bind pp pipeline and descriptor set
+        unsafe {
+            self.device.cmd_bind_pipeline(
                 commandbuffer,
-                self.renderpass_pp,
-                if !direction {
-                    self.swapchain.framebuffer_pp_a
-                } else {
-                    self.swapchain.framebuffer_pp_b
-                },
-                &[],
+                vk::PipelineBindPoint::GRAPHICS,
+                effect.pipeline.get(),
             );
+        }
 
-            // bind pp pipeline and descriptor set
-            unsafe {
-                self.device.cmd_bind_pipeline(
-                    commandbuffer,
-                    vk::PipelineBindPoint::GRAPHICS,
-                    effect.pipeline,
-                );
-            }
+        self.set_viewport(commandbuffer, extent.width as f32, extent.height as f32);
 
-            self.set_viewport(
+        let desc_data = [DescriptorData::ImageSampler {
+            image: self.render_graph.get_view(src),
+            layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            sampler: vk::Sampler::null(),
+        }];
+        let desc_set = self
+            .descriptor_manager
+            .get_descriptor_set(self.desc_layout_pp, &desc_data)?;
+        unsafe {
+            self.device.cmd_bind_descriptor_sets(
                 commandbuffer,
-                self.swapchain.extent.width as f32,
-                self.swapchain.extent.height as f32,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipe_layout_pp,
+                0,
+                &[desc_set],
+                &[],
             );
-
-            let desc_data = [DescriptorData::ImageSampler {
-                image: if !direction {
-                    self.swapchain.resolve_imageview
-                } else {
-                    self.swapchain.g0_imageview
-                },
-                layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-                sampler: vk::Sampler::null(),
-            }];
-            let desc_set = self
-                .descriptor_manager
-                .get_descriptor_set(self.desc_layout_pp, &desc_data)?;
-            unsafe {
-                self.device.cmd_bind_descriptor_sets(
-                    commandbuffer,
-                    vk::PipelineBindPoint::GRAPHICS,
-                    self.pipe_layout_pp,
-                    0,
-                    &[desc_set],
-                    &[],
-                );
-            }
-
-            unsafe {
-                self.device.cmd_draw(commandbuffer, 6, 1, 0, 0);
-                self.device.cmd_end_render_pass(commandbuffer);
-            }
-
-            direction = !direction;
+            self.device.cmd_draw(commandbuffer, 6, 1, 0, 0);
         }
 
-        // transition final destination image to COLOR_ATTACHMENT_OPTIMAL
-        self.transition_images(
-            commandbuffer,
-            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
-            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
-            &[ImageTransition {
-                image: if direction {
-                    self.swapchain.g0_image
-                } else {
-                    self.swapchain.resolve_image
-                },
-                from: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-                to: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-                wait_access: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
-                dst_access: vk::AccessFlags::COLOR_ATTACHMENT_READ
-                    | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
-            }],
-        );
-
-        Ok(direction)
+        Ok(())
     }
 
-    fn render_ui(
-        &mut self,
-        commandbuffer: vk::CommandBuffer,
-        direction: bool,
-        swapchain_image_index: usize,
-    ) -> Result<(), vk::Result> {
-        // begin the ui renderpass
-        self.begin_renderpass(
-            commandbuffer,
-            self.renderpass_ui,
-            if direction {
-                self.swapchain.framebuffer_pp_a
-            } else {
-                self.swapchain.framebuffer_pp_b
-            },
-            &[],
-        );
-
+    fn render_ui(&mut self, commandbuffer: vk::CommandBuffer) -> Result<(), vk::Result> {
         // bind the ui pipeline
         unsafe {
             self.device.cmd_bind_pipeline(
@@ -936,9 +1357,9 @@ impl VulkanManager {
                 0,
                 &[vk::Viewport {
                     x: 0.0,
-                    y: self.swapchain.extent.height as f32,
-                    width: self.swapchain.extent.width as f32,
-                    height: -(self.swapchain.extent.height as f32),
+                    y: self.output.extent().height as f32,
+                    width: self.output.extent().width as f32,
+                    height: -(self.output.extent().height as f32),
                     min_depth: 0.0,
                     max_depth: 1.0,
                 }],
@@ -967,8 +1388,8 @@ impl VulkanManager {
 
             let proj_matrix = Mat4::orthographic_vulkan(
                 0.0,
-                self.swapchain.extent.width as f32,
-                self.swapchain.extent.height as f32,
+                self.output.extent().width as f32,
+                self.output.extent().height as f32,
                 0.0,
                 -1.0,
                 1.0,
@@ -1022,90 +1443,460 @@ impl VulkanManager {
             }
         }
 
-        // end the ui renderpass
-        unsafe {
-            self.device.cmd_end_render_pass(commandbuffer);
-        }
-
-        // transition swapchain image to TRANSFER_DST_OPTIMAL and final pp image to TRANSFER_SRC_OPTIMAL
-        self.transition_images(
-            commandbuffer,
-            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
-            vk::PipelineStageFlags::TRANSFER,
-            &[
-                ImageTransition {
-                    image: self.swapchain.images[swapchain_image_index],
-                    from: vk::ImageLayout::UNDEFINED,
-                    to: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                    wait_access: vk::AccessFlags::empty(),
-                    dst_access: vk::AccessFlags::TRANSFER_WRITE,
+        Ok(())
+    }
+
+    /// Declares all passes of a frame that renders to the output image `image_index`.
+    ///
+    /// Cameras are rendered in the order of `views`, those rendering into the window are then composed in
+    /// this order as well. Every material may sample the render targets of all cameras, so each camera sees
+    /// the targets rendered before it in the current frame and all others from the previous frame.
+    fn declare_frame(
+        graph: &mut RenderGraph<FramePass>,
+        output: &FrameOutput,
+        image_index: usize,
+        views: &[ViewDecl],
+        depth_mode: DepthMode,
+        gpu_culling: bool,
+        captures: &[CaptureKind],
+    ) {
+        graph.reset();
+        graph.set_output_extent(output.extent());
+
+        let output_image = output.import(graph, image_index);
+
+        // several cameras might render into the same texture, which must only be imported once
+        let mut targets: Vec<(*const Texture2D, ImageId)> = Vec::new();
+        for target in views.iter().filter_map(|view| view.target) {
+            if targets.iter().any(|(t, _)| std::ptr::eq(*t, target)) {
+                continue;
+            }
+            let image = graph.import_image(
+                "render target",
+                target.image,
+                target.view,
+                TEXTURE_FORMAT,
+                vk::Extent2D {
+                    width: target.width,
+                    height: target.height,
                 },
-                ImageTransition {
-                    image: if direction {
-                        self.swapchain.g0_image
-                    } else {
-                        self.swapchain.resolve_image
+                Some(ImageAccess::Sampled),
+                ImageAccess::Sampled,
+            );
+            targets.push((target as *const Texture2D, image));
+        }
+        let sampled_targets: Vec<(ImageId, ImageAccess)> = targets
+            .iter()
+            .map(|(_, image)| (*image, ImageAccess::Sampled))
+            .collect();
+
+        // the cameras rendering into the window are composed in here, UI is drawn on top afterwards
+        let frame = graph.create_image("frame", vk::Format::R16G16B16A16_SFLOAT, ImageSize::Output);
+
+        graph.add_pass(PassDesc::new(FramePass::Compute(ComputeStage::BeforeGPass)));
+
+        let mut screen_views = Vec::new();
+        for (view, decl) in views.iter().enumerate() {
+            let size = ImageSize::Absolute(decl.extent.width, decl.extent.height);
+
+            // resolve will contain the finished deferred scene rendering
+            let resolve = graph.create_image(
+                format!("resolve.{view}"),
+                vk::Format::R16G16B16A16_SFLOAT,
+                size,
+            );
+            let depth = graph.create_image(format!("depth.{view}"), depth_mode.format(), size);
+            let gbuffer0 =
+                graph.create_image(format!("g0.{view}"), vk::Format::R16G16B16A16_SFLOAT, size);
+            let gbuffer1 =
+                graph.create_image(format!("g1.{view}"), vk::Format::R16G16B16A16_SFLOAT, size);
+
+            if gpu_culling {
+                graph.add_pass(PassDesc::new(FramePass::Culling { view }));
+            }
+
+            graph.add_pass(PassDesc {
+                subpasses: vec![
+                    SubpassDesc {
+                        colors: vec![gbuffer0, gbuffer1],
+                        depth_stencil: Some((depth, ImageAccess::DepthStencilAttachment)),
+                        inputs: vec![],
                     },
-                    from: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-                    to: vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
-                    wait_access: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
-                    dst_access: vk::AccessFlags::TRANSFER_READ,
-                },
+                    SubpassDesc {
+                        colors: vec![resolve],
+                        depth_stencil: Some((depth, ImageAccess::DepthStencilReadOnly)),
+                        inputs: vec![gbuffer0, gbuffer1, depth],
+                    },
+                ],
+                accesses: sampled_targets.clone(),
+                clear_values: vec![
+                    (
+                        resolve,
+                        vk::ClearValue {
+                            color: vk::ClearColorValue {
+                                float32: [0.2, 0.2, 0.2, 0.0],
+                            },
+                        },
+                    ),
+                    (
+                        depth,
+                        vk::ClearValue {
+                            depth_stencil: vk::ClearDepthStencilValue {
+                                depth: depth_mode.clear_depth(),
+                                stencil: 0,
+                            },
+                        },
+                    ),
+                ],
+                ..PassDesc::new(FramePass::Deferred {
+                    view,
+                    gbuffer0,
+                    gbuffer1,
+                    depth,
+                })
+            });
+
+            if decl.main {
+                Self::declare_captures(graph, captures, CaptureKind::Hdr, resolve);
+                graph.add_pass(PassDesc::new(FramePass::Compute(
+                    ComputeStage::BeforePostProcessing,
+                )));
+            }
+
+            // every effect samples the result of the previous one, alternating between resolve and g0
+            let (mut src, mut dst) = (resolve, gbuffer0);
+            for effect in 0..decl.pp_effect_count {
+                graph.add_pass(PassDesc {
+                    subpasses: vec![SubpassDesc {
+                        colors: vec![dst],
+                        ..Default::default()
+                    }],
+                    accesses: vec![(src, ImageAccess::Sampled)],
+                    discard: vec![dst],
+                    ..PassDesc::new(FramePass::PostProcess { view, effect, src })
+                });
+                std::mem::swap(&mut src, &mut dst);
+            }
+
+            match decl.target {
+                Some(target) => {
+                    let dst = targets
+                        .iter()
+                        .find(|(t, _)| std::ptr::eq(*t, target))
+                        .unwrap()
+                        .1;
+                    graph.add_pass(PassDesc {
+                        accesses: vec![
+                            (src, ImageAccess::TransferSrc),
+                            (dst, ImageAccess::TransferDst),
+                        ],
+                        discard: vec![dst],
+                        ..PassDesc::new(FramePass::Blit {
+                            src,
+                            dst,
+                            dst_rect: Self::blit_rect(decl.region),
+                        })
+                    });
+                }
+                None => screen_views.push((src, decl.region)),
+            }
+        }
+
+        // regions that are not covered by any camera stay black
+        graph.add_pass(PassDesc {
+            accesses: vec![(frame, ImageAccess::TransferDst)],
+            discard: vec![frame],
+            ..PassDesc::new(FramePass::Clear { dst: frame })
+        });
+        // upscales every camera to its viewport in the native resolution
+        for (src, region) in screen_views {
+            graph.add_pass(PassDesc {
+                accesses: vec![
+                    (src, ImageAccess::TransferSrc),
+                    (frame, ImageAccess::TransferDst),
+                ],
+                ..PassDesc::new(FramePass::Blit {
+                    src,
+                    dst: frame,
+                    dst_rect: Self::blit_rect(region),
+                })
+            });
+        }
+
+        graph.add_pass(PassDesc::new(FramePass::Compute(ComputeStage::BeforeUi)));
+        graph.add_pass(PassDesc {
+            subpasses: vec![SubpassDesc {
+                colors: vec![frame],
+                ..Default::default()
+            }],
+            ..PassDesc::new(FramePass::Ui)
+        });
+
+        // blit the composed frame to the output (automatically converts to sRGB)
+        graph.add_pass(PassDesc {
+            accesses: vec![
+                (frame, ImageAccess::TransferSrc),
+                (output_image, ImageAccess::TransferDst),
             ],
+            discard: vec![output_image],
+            ..PassDesc::new(FramePass::Blit {
+                src: frame,
+                dst: output_image,
+                dst_rect: [
+                    0,
+                    0,
+                    output.extent().width as i32,
+                    output.extent().height as i32,
+                ],
+            })
+        });
+        if output.supports_capture() {
+            Self::declare_captures(graph, captures, CaptureKind::Final, output_image);
+        } else if captures.contains(&CaptureKind::Final) {
+            // the same blit into an image of the output format, which is captured instead
+            let capture = graph.create_image("capture", output.format(), ImageSize::Output);
+            graph.add_pass(PassDesc {
+                accesses: vec![
+                    (frame, ImageAccess::TransferSrc),
+                    (capture, ImageAccess::TransferDst),
+                ],
+                discard: vec![capture],
+                ..PassDesc::new(FramePass::Blit {
+                    src: frame,
+                    dst: capture,
+                    dst_rect: [
+                        0,
+                        0,
+                        output.extent().width as i32,
+                        output.extent().height as i32,
+                    ],
+                })
+            });
+            Self::declare_captures(graph, captures, CaptureKind::Final, capture);
+        }
+    }
+
+    /// Adds a capture pass of `src` for every entry of `captures` that matches `kind`.
+    fn declare_captures(
+        graph: &mut RenderGraph<FramePass>,
+        captures: &[CaptureKind],
+        kind: CaptureKind,
+        src: ImageId,
+    ) {
+        for (request, _) in captures.iter().enumerate().filter(|(_, k)| **k == kind) {
+            graph.add_pass(PassDesc {
+                accesses: vec![(src, ImageAccess::TransferSrc)],
+                ..PassDesc::new(FramePass::Capture { request, src })
+            });
+        }
+    }
+
+    fn blit_rect(region: vk::Rect2D) -> [i32; 4] {
+        [
+            region.offset.x,
+            region.offset.y,
+            region.extent.width as i32,
+            region.extent.height as i32,
+        ]
+    }
+
+    /// Converts a normalized viewport to pixels of the output, or `None` if it does not cover any pixel.
+    ///
+    /// Edges are rounded to the nearest pixel, so that adjacent viewports neither overlap nor leave gaps.
+    fn viewport_region(viewport: Viewport, extent: vk::Extent2D) -> Option<vk::Rect2D> {
+        let edge =
+            |value: f32, size: u32| (value * size as f32).round().clamp(0.0, size as f32) as i32;
+        let (x0, x1) = (
+            edge(viewport.x, extent.width),
+            edge(viewport.x + viewport.width, extent.width),
+        );
+        let (y0, y1) = (
+            edge(viewport.y, extent.height),
+            edge(viewport.y + viewport.height, extent.height),
         );
+        if x1 <= x0 || y1 <= y0 {
+            return None;
+        }
 
-        // blit pp image to swapchain (automatically converts to sRGB)
-        self.blit_image(
-            commandbuffer,
-            if direction {
-                self.swapchain.g0_image
-            } else {
-                self.swapchain.resolve_image
+        Some(vk::Rect2D {
+            offset: vk::Offset2D { x: x0, y: y0 },
+            extent: vk::Extent2D {
+                width: (x1 - x0) as u32,
+                height: (y1 - y0) as u32,
             },
-            self.swapchain.images[swapchain_image_index],
-            self.swapchain.extent.width as i32,
-            self.swapchain.extent.height as i32,
-        );
+        })
+    }
 
-        // transition swapchain image to PRESENT_SRC_KHR
-        self.transition_images(
-            commandbuffer,
-            vk::PipelineStageFlags::TRANSFER,
-            vk::PipelineStageFlags::BOTTOM_OF_PIPE, // can be BOTTOM_OF_PIPE because vkQueuePresentKHR waits for a semaphore
-            &[ImageTransition {
-                image: self.swapchain.images[swapchain_image_index],
-                from: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                to: vk::ImageLayout::PRESENT_SRC_KHR,
-                wait_access: vk::AccessFlags::TRANSFER_WRITE,
-                dst_access: vk::AccessFlags::empty(),
-            }],
-        );
+    /// The current scale of the internal resolution, see [`Self::resolution`].
+    pub fn render_scale(&self) -> f32 {
+        self.render_scale
+    }
 
-        Ok(())
+    /// Updates the render scale according to [`Self::resolution`], given the most recent frame times in milliseconds.
+    pub(crate) fn update_render_scale(&mut self, frame_times: &[f32]) {
+        let scale = self.resolution.next_scale(self.render_scale, frame_times);
+        if scale != self.render_scale {
+            log::debug!("Render scale changed to {:.2}", scale);
+            self.render_scale = scale;
+        }
+    }
+
+    /// Index of the camera uniform data of `view` in the current frame.
+    fn uniform_slot(&self, view: usize) -> u8 {
+        (self.current_frame_index as usize * MAX_VIEWS + view) as u8
     }
 
     pub(crate) fn update_commandbuffer(
         &mut self,
-        swapchain_image_index: usize,
+        image_index: usize,
         scene: Rc<Scene>,
-    ) -> Result<(), vk::Result> {
+    ) -> GraphicsResult<()> {
         profile_function!();
 
-        {
-            profile_scope!("Camera uniform upload");
-            let cam_comp = scene
-                .main_camera
-                .borrow()
-                .upgrade()
-                .expect("Scene has to have a CameraComponent");
-            let cam_data = cam_comp.get_cam_data(
-                self.swapchain.extent.width as f32 / self.swapchain.extent.height as f32,
+        // cameras rendering into textures come first, so that their results can be used by all others
+        let screen_cameras = scene.collect_screen_cameras();
+        let main_camera = scene.main_camera.borrow().upgrade();
+        let mut cameras: Vec<(Rc<CameraComponent>, vk::Rect2D)> = scene
+            .collect_texture_cameras()
+            .into_iter()
+            .map(|cam| {
+                let target = cam.get_render_target().unwrap();
+                let region = vk::Rect2D {
+                    offset: vk::Offset2D::default(),
+                    extent: vk::Extent2D {
+                        width: target.width,
+                        height: target.height,
+                    },
+                };
+                (cam, region)
+            })
+            .chain(screen_cameras.into_iter().filter_map(|cam| {
+                let region = Self::viewport_region(cam.get_viewport(), self.output.extent())?;
+                Some((cam, region))
+            }))
+            .collect();
+        if cameras.len() > MAX_VIEWS {
+            log::warn!(
+                "Only {} of {} cameras are rendered",
+                MAX_VIEWS,
+                cameras.len()
             );
+            cameras.drain(..cameras.len() - MAX_VIEWS);
+        }
+
+        let (mut all_models, lights) = scene.collect_renderables();
+        // the fields of a Model are public, so its mesh or material may have been replaced after Model::new checked them
+        all_models.retain(|(_, model)| model.is_compatible());
+
+        let gpu_culling =
+            self.enable_frustum_culling && self.enable_gpu_culling && self.indirect_draw_supported;
+
+        let mut views = Vec::with_capacity(cameras.len());
+        let mut view_models = Vec::with_capacity(cameras.len());
+        for (view, (cam_comp, region)) in cameras.iter().enumerate() {
+            profile_scope!("Camera uniform upload");
+
+            let aspect = region.extent.width as f32 / region.extent.height as f32;
+            let extent = match cam_comp.get_render_target() {
+                Some(_) => region.extent,
+                None => {
+                    let scaled =
+                        |size: u32| ((size as f32 * self.render_scale).round() as u32).max(1);
+                    vk::Extent2D {
+                        width: scaled(region.extent.width),
+                        height: scaled(region.extent.height),
+                    }
+                }
+            };
+            let cam_data = cam_comp.get_cam_data(aspect, self.depth_mode);
             self.uniform_buffer
-                .set_data(&self.allocator, &cam_data, self.current_frame_index)
+                .set_data(&self.allocator, &cam_data, self.uniform_slot(view))
                 .unwrap();
+
+            let frustum = cam_comp.get_frustum(aspect, self.depth_mode);
+            let mut models = all_models.clone();
+            if self.enable_frustum_culling && !gpu_culling {
+                Self::cull_renderables(&mut models, cam_data.view_matrix, &frustum);
+            }
+            view_models.push((models, cam_data.view_matrix, cam_comp.get_near()));
+
+            views.push(FrameView {
+                target: cam_comp.get_render_target(),
+                region: *region,
+                extent,
+                main: main_camera
+                    .as_ref()
+                    .is_some_and(|main| Rc::ptr_eq(main, cam_comp)),
+                pp_effects: cam_comp
+                    .get_pp_effects()
+                    .unwrap_or_else(|| self.pp_effects.clone()),
+                frustum: frustum.to_world_space(cam_data.view_matrix),
+                commands: 0..0,
+            });
+        }
+
+        // the statistics only cover the main camera
+        let drawn_object_count = views
+            .iter()
+            .zip(view_models.iter())
+            .find(|(view, _)| view.main)
+            .map_or(0, |(_, (models, _, _))| models.len());
+        if gpu_culling {
+            // the count of this frame is only known once it has finished,
+            // so the counts of the last frame that used this frame index are shown instead
+            let frame = self.current_frame_index as usize;
+            if let Some(culled) = self.indirect_draw_data.take_culled_count(frame as u8) {
+                self.culled_object_count = culled as usize;
+                self.drawn_object_count =
+                    self.gpu_cull_object_counts[frame].saturating_sub(culled as usize);
+                self.object_count_age = self.present_config.frames_in_flight;
+            }
+            self.gpu_cull_object_counts[frame] = drawn_object_count;
+        } else {
+            self.drawn_object_count = drawn_object_count;
+            self.culled_object_count = all_models.len() - drawn_object_count;
+            self.object_count_age = 0;
         }
 
+        let render_maps: Vec<Vec<(TransformData, &Model)>> = view_models
+            .iter()
+            .map(|(models, view_matrix, near)| {
+                Self::build_render_order(models.as_slice(), *view_matrix, *near)
+            })
+            .collect();
+
+        // all cameras share the buffers of the indirect draw data, each one uses its own range
+        let mut view_batches = Vec::with_capacity(render_maps.len());
+        let mut draw_commands = Vec::new();
+        let mut transforms: Vec<TransformData> = Vec::new();
+        let mut bounding_spheres: Vec<Vec4> = Vec::new();
+        for (render_map, view) in render_maps.iter().zip(views.iter_mut()) {
+            let first_command = draw_commands.len() as u32;
+            let (batches, commands) =
+                Self::build_draw_batches(render_map, transforms.len() as u32, first_command);
+            draw_commands.extend(commands);
+            view.commands = first_command..draw_commands.len() as u32;
+            view_batches.push(batches);
+
+            transforms.extend(render_map.iter().map(|obj| obj.0));
+            bounding_spheres.extend(render_map.iter().map(|obj| {
+                let sphere = obj.1.mesh.bounding_sphere;
+                Vec4::new(
+                    sphere.center.x,
+                    sphere.center.y,
+                    sphere.center.z,
+                    sphere.radius,
+                )
+            }));
+        }
+        self.indirect_draw_data.upload(
+            &self.allocator,
+            self.current_frame_index,
+            &transforms,
+            &bounding_spheres,
+            &draw_commands,
+        )?;
+
         let commandbuffer = self.commandbuffers[self.current_frame_index as usize];
         let commandbuffer_begininfo = vk::CommandBufferBeginInfo::builder();
         unsafe {
@@ -1113,86 +1904,168 @@ impl VulkanManager {
                 .begin_command_buffer(commandbuffer, &commandbuffer_begininfo)?;
         }
 
-        self.begin_renderpass(
-            commandbuffer,
-            self.renderpass,
-            self.swapchain.framebuffer_deferred,
-            &[
-                vk::ClearValue {
-                    color: vk::ClearColorValue {
-                        float32: [0.2, 0.2, 0.2, 0.0],
-                    },
-                },
-                vk::ClearValue {
-                    depth_stencil: vk::ClearDepthStencilValue {
-                        depth: 1.0,
-                        stencil: 0,
-                    },
-                },
-            ],
+        let capture_requests = std::mem::take(&mut self.capture_requests);
+        let capture_kinds: Vec<CaptureKind> = capture_requests.iter().map(|r| r.kind).collect();
+        let view_decls: Vec<ViewDecl> = views
+            .iter()
+            .map(|view| ViewDecl {
+                target: view.target.as_deref(),
+                region: view.region,
+                extent: view.extent,
+                pp_effect_count: view.pp_effects.len(),
+                main: view.main,
+            })
+            .collect();
+        Self::declare_frame(
+            &mut self.render_graph,
+            &self.output,
+            image_index,
+            &view_decls,
+            self.depth_mode,
+            gpu_culling,
+            &capture_kinds,
         );
+        self.render_graph.compile(&self.allocator)?;
 
-        let desc_values_frame_data = [
-            DescriptorData::DynamicUniformBuffer {
-                buffer: self.uniform_buffer.get_buffer(),
-                offset: 0,
-                size: self.uniform_buffer.get_size(),
-            },
-            DescriptorData::InputAttachment {
-                image: self.swapchain.g0_imageview,
-                layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-            },
-            DescriptorData::InputAttachment {
-                image: self.swapchain.g1_imageview,
-                layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-            },
-            DescriptorData::InputAttachment {
-                image: self.swapchain.depth_imageview_depth_only,
-                layout: vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
-            },
-        ];
-        let desc_set_camera = self
-            .descriptor_manager
-            .get_descriptor_set(self.desc_layout_frame_data, &desc_values_frame_data)?;
-
-        unsafe {
-            self.device.cmd_bind_descriptor_sets(
-                commandbuffer,
-                vk::PipelineBindPoint::GRAPHICS,
-                self.pipeline_layout_gpass,
-                0,
-                &[desc_set_camera],
-                &[self.uniform_buffer.get_offset(self.current_frame_index) as u32],
-            );
-        }
+        for pass_index in 0..self.render_graph.pass_count() {
+            match self.render_graph.begin_pass(commandbuffer, pass_index) {
+                FramePass::Culling { view } => {
+                    self.indirect_draw_data.record_culling(
+                        commandbuffer,
+                        &mut self.descriptor_manager,
+                        self.current_frame_index,
+                        &views[view].frustum,
+                        views[view].commands.clone(),
+                        views[view].main,
+                    )?;
+                }
+                FramePass::Compute(stage) => {
+                    self.record_compute_stage(commandbuffer, stage)?;
+                }
+                FramePass::Deferred {
+                    view,
+                    gbuffer0,
+                    gbuffer1,
+                    depth,
+                } => {
+                    let uniform_offset =
+                        self.uniform_buffer.get_offset(self.uniform_slot(view)) as u32;
+                    let desc_values_frame_data = [
+                        DescriptorData::DynamicUniformBuffer {
+                            buffer: self.uniform_buffer.get_buffer(),
+                            offset: 0,
+                            size: self.uniform_buffer.get_size(),
+                        },
+                        DescriptorData::InputAttachment {
+                            image: self.render_graph.get_view(gbuffer0),
+                            layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                        },
+                        DescriptorData::InputAttachment {
+                            image: self.render_graph.get_view(gbuffer1),
+                            layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                        },
+                        DescriptorData::InputAttachment {
+                            image: self.render_graph.get_depth_view(depth),
+                            layout: vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+                        },
+                        self.indirect_draw_data
+                            .get_transform_descriptor(self.current_frame_index),
+                    ];
+                    let desc_set_camera = self
+                        .descriptor_manager
+                        .get_descriptor_set(self.desc_layout_frame_data, &desc_values_frame_data)?;
+
+                    unsafe {
+                        self.device.cmd_bind_descriptor_sets(
+                            commandbuffer,
+                            vk::PipelineBindPoint::GRAPHICS,
+                            self.pipeline_layout_gpass,
+                            0,
+                            &[desc_set_camera],
+                            &[uniform_offset],
+                        );
+                    }
 
-        let (models, lights) = scene.collect_renderables();
+                    self.render_gpass(
+                        commandbuffer,
+                        &view_batches[view],
+                        &draw_commands,
+                        views[view].extent,
+                    )?;
 
-        let render_map = Self::build_render_order(models.as_slice());
-        self.render_gpass(commandbuffer, &render_map)?;
+                    unsafe {
+                        self.device
+                            .cmd_next_subpass(commandbuffer, vk::SubpassContents::INLINE);
 
-        unsafe {
-            self.device
-                .cmd_next_subpass(commandbuffer, vk::SubpassContents::INLINE);
+                        self.device.cmd_bind_descriptor_sets(
+                            commandbuffer,
+                            vk::PipelineBindPoint::GRAPHICS,
+                            self.pipeline_layout_resolve_pass,
+                            0,
+                            &[desc_set_camera],
+                            &[uniform_offset],
+                        );
+                    }
 
-            self.device.cmd_bind_descriptor_sets(
-                commandbuffer,
-                vk::PipelineBindPoint::GRAPHICS,
-                self.pipeline_layout_resolve_pass,
-                0,
-                &[desc_set_camera],
-                &[self.uniform_buffer.get_offset(self.current_frame_index) as u32],
-            );
+                    self.render_resolve_pass(commandbuffer, &lights, views[view].extent);
+                }
+                FramePass::PostProcess { view, effect, src } => {
+                    let effect = views[view].pp_effects[effect].clone();
+                    self.render_pp(commandbuffer, &effect, src, views[view].extent)?;
+                }
+                FramePass::Ui => {
+                    self.render_ui(commandbuffer)?;
+                }
+                FramePass::Clear { dst } => unsafe {
+                    self.device.cmd_clear_color_image(
+                        commandbuffer,
+                        self.render_graph.get_image(dst),
+                        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                        &vk::ClearColorValue {
+                            float32: [0.0, 0.0, 0.0, 1.0],
+                        },
+                        &[vk::ImageSubresourceRange {
+                            aspect_mask: vk::ImageAspectFlags::COLOR,
+                            base_mip_level: 0,
+                            level_count: 1,
+                            base_array_layer: 0,
+                            layer_count: 1,
+                        }],
+                    );
+                },
+                FramePass::Blit { src, dst, dst_rect } => {
+                    // blit pp image to its destination (automatically converts to sRGB)
+                    self.blit_image(
+                        commandbuffer,
+                        self.render_graph.get_image(src),
+                        self.render_graph.get_extent(src),
+                        self.render_graph.get_image(dst),
+                        dst_rect,
+                    );
+                }
+                FramePass::Capture { request, src } => {
+                    let request = capture_requests[request].clone();
+                    let format = match request.kind {
+                        CaptureKind::Final => self.output.format(),
+                        CaptureKind::Hdr => vk::Format::R16G16B16A16_SFLOAT,
+                    };
+                    let readback = Readback::record(
+                        &self.device,
+                        &self.allocator,
+                        commandbuffer,
+                        request,
+                        self.render_graph.get_image(src),
+                        format,
+                        self.render_graph.get_extent(src),
+                    )?;
+                    self.readbacks.push(readback);
+                }
+            }
+            self.render_graph.end_pass(commandbuffer, pass_index);
         }
-
-        self.render_resolve_pass(commandbuffer, &lights);
+        self.render_graph.end(commandbuffer);
 
         unsafe {
-            self.device.cmd_end_render_pass(commandbuffer);
-
-            let direction = self.render_pp(commandbuffer)?;
-            self.render_ui(commandbuffer, direction, swapchain_image_index)?;
-
             self.device.end_command_buffer(commandbuffer)?;
         }
 
@@ -1205,17 +2078,104 @@ impl VulkanManager {
                 .device_wait_idle()
                 .expect("something went wrong while waiting");
         }
-        self.swapchain.recreate(
-            &self.device,
-            self.physical_device,
-            &self.allocator,
-            &self.surface,
-            self.renderpass,
-            self.renderpass_pp,
-        )?;
+        // the render graph recreates its images when the output extent changes
+        if let (FrameOutput::Swapchain(swapchain), Some(surface)) =
+            (&mut self.output, self.surface.as_ref())
+        {
+            swapchain.recreate(
+                &self.device,
+                self.physical_device,
+                surface,
+                &self.present_config,
+            )?;
+        }
         Ok(())
     }
 
+    /// The frame pacing settings that are currently applied.
+    pub fn present_config(&self) -> PresentConfig {
+        self.present_config
+    }
+
+    /// The present mode that is actually used, which may differ from [`PresentConfig::present_mode`] if it is unsupported.
+    ///
+    /// Returns `None` without a window.
+    pub fn active_present_mode(&self) -> Option<PresentMode> {
+        match &self.output {
+            FrameOutput::Swapchain(swapchain) => PresentMode::from_vk(swapchain.present_mode),
+            FrameOutput::Offscreen(_) => None,
+        }
+    }
+
+    /// The number of images the swapchain was created with, or `None` without a window.
+    pub fn swapchain_image_count(&self) -> Option<u32> {
+        match &self.output {
+            FrameOutput::Swapchain(swapchain) => Some(swapchain.amount_of_images),
+            FrameOutput::Offscreen(_) => None,
+        }
+    }
+
+    /// Applies new frame pacing settings, recreating the swapchain if the present mode or image count changed.
+    ///
+    /// Values out of range are clamped, e.g. the frames in flight to [`MAX_FRAMES_IN_FLIGHT`].
+    pub fn set_present_config(&mut self, config: PresentConfig) -> GraphicsResult<()> {
+        let config = config.clamped();
+        let old = std::mem::replace(&mut self.present_config, config);
+
+        if old.frames_in_flight != config.frames_in_flight {
+            // all frames have to finish, as the frame indices in use change
+            unsafe { self.device.device_wait_idle()? };
+            self.current_frame_index %= config.frames_in_flight;
+        }
+        if old.present_mode != config.present_mode
+            || old.swapchain_image_count != config.swapchain_image_count
+        {
+            unsafe { self.device.device_wait_idle()? };
+            if let (FrameOutput::Swapchain(swapchain), Some(surface)) =
+                (&mut self.output, self.surface.as_ref())
+            {
+                swapchain.recreate(&self.device, self.physical_device, surface, &config)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Captures the next rendered frame to the file at `path`.
+    ///
+    /// The file is written after the frame was submitted, see [`Self::finish_captures`].
+    pub fn request_capture(&mut self, kind: CaptureKind, path: impl Into<PathBuf>) {
+        self.capture_requests.push(CaptureRequest {
+            kind,
+            path: path.into(),
+        });
+    }
+
+    /// Waits for the submitted frame and writes all of its captures to their files.
+    pub(crate) fn finish_captures(&mut self) -> GraphicsResult<()> {
+        profile_function!();
+
+        if self.readbacks.is_empty() {
+            return Ok(());
+        }
+        // the fence is reset when this frame index is used again
+        unsafe {
+            self.device.wait_for_fences(
+                &[self.frame_resource_fences[self.current_frame_index as usize]],
+                true,
+                u64::MAX,
+            )?;
+        }
+
+        let mut result = Ok(());
+        for readback in self.readbacks.drain(..) {
+            let written = readback.write(&self.allocator);
+            if result.is_ok() {
+                result = written;
+            }
+        }
+        result
+    }
+
     pub(crate) fn wait_for_fence(&mut self) {
         profile_function!();
 
@@ -1233,6 +2193,7 @@ impl VulkanManager {
         }
 
         self.descriptor_manager.next_frame();
+        self.mesh_pool.next_frame();
     }
 
     pub(crate) fn wait_for_uploads(&mut self) {
@@ -1364,12 +2325,15 @@ impl VulkanManager {
         let semaphores_finished =
             [self.render_finished_semaphores[self.current_frame_index as usize]];
         let commandbuffers = [self.commandbuffers[self.current_frame_index as usize]];
-        let submit_info = [vk::SubmitInfo::builder()
-            .wait_semaphores(&semaphores_available)
-            .wait_dst_stage_mask(&waiting_stages)
-            .command_buffers(&commandbuffers)
-            .signal_semaphores(&semaphores_finished)
-            .build()];
+        let mut submit_info = vk::SubmitInfo::builder().command_buffers(&commandbuffers);
+        // offscreen images are neither acquired nor presented
+        if let FrameOutput::Swapchain(_) = self.output {
+            submit_info = submit_info
+                .wait_semaphores(&semaphores_available)
+                .wait_dst_stage_mask(&waiting_stages)
+                .signal_semaphores(&semaphores_finished);
+        }
+        let submit_info = [submit_info.build()];
         unsafe {
             self.device
                 .queue_submit(
@@ -1385,7 +2349,12 @@ impl VulkanManager {
     pub(crate) fn present(&mut self, image_index: u32) {
         profile_function!();
 
-        let swapchains = [self.swapchain.swapchain];
+        let swapchain = match &self.output {
+            FrameOutput::Swapchain(swapchain) => swapchain,
+            FrameOutput::Offscreen(_) => return,
+        };
+
+        let swapchains = [swapchain.swapchain];
         let indices = [image_index];
         let wait_semaphores = [self.render_finished_semaphores[self.current_frame_index as usize]];
         let present_info = vk::PresentInfoKHR::builder()
@@ -1393,8 +2362,7 @@ impl VulkanManager {
             .swapchains(&swapchains)
             .image_indices(&indices);
         unsafe {
-            match &self
-                .swapchain
+            match &swapchain
                 .swapchain_loader
                 .queue_present(self.queues.graphics_queue, &present_info)
             {
@@ -1433,7 +2401,6 @@ impl Drop for VulkanManager {
                 .destroy_descriptor_set_layout(self.desc_layout_ui, None);
             self.device
                 .destroy_pipeline_layout(self.pipe_layout_ui, None);
-            self.device.destroy_render_pass(self.renderpass_ui, None);
             self.device.destroy_pipeline(self.pipeline_ui, None);
             self.device
                 .destroy_pipeline(self.pipeline_ui_wireframe, None);
@@ -1449,6 +2416,7 @@ impl Drop for VulkanManager {
 
             self.lighting_pipelines.clear();
             self.pp_effects.clear();
+            self.compute_pipelines.clear();
 
             for s in &self.image_acquire_semaphores {
                 self.device.destroy_semaphore(*s, None);
@@ -1465,14 +2433,25 @@ impl Drop for VulkanManager {
             self.uploader.destroy();
             std::mem::ManuallyDrop::drop(&mut self.uploader);
 
+            // meshes still alive after this point keep the pool alive, but must not be used anymore
+            std::mem::ManuallyDrop::drop(&mut self.mesh_pool);
+
             self.uniform_buffer.destroy(&self.allocator);
+            self.indirect_draw_data.destroy(&self.allocator);
+            // only left over if the frame was never submitted
+            for readback in self.readbacks.drain(..) {
+                readback.destroy(&self.allocator);
+            }
 
             self.pools.cleanup(&self.device);
 
-            self.device.destroy_render_pass(self.renderpass, None);
-            self.device.destroy_render_pass(self.renderpass_pp, None);
+            // also destroys renderpass and renderpass_pp
+            self.render_graph.destroy(&self.allocator);
             // --segfault
-            self.swapchain.cleanup(&self.device, &self.allocator);
+            match &mut self.output {
+                FrameOutput::Swapchain(swapchain) => swapchain.cleanup(&self.device),
+                FrameOutput::Offscreen(target) => target.cleanup(&self.device, &self.allocator),
+            }
 
             self.device.destroy_sampler(self.sampler_linear, None);
 
@@ -1488,6 +2467,12 @@ impl Drop for VulkanManager {
             self.device
                 .destroy_pipeline_layout(self.pipe_layout_pp, None);
 
+            pipeline_cache::destroy(
+                &self.device,
+                self.pipeline_cache,
+                &self.physical_device_properties,
+            );
+
             std::mem::ManuallyDrop::drop(&mut self.allocator);
 
             self.device.destroy_device(None);
@@ -1497,11 +2482,3 @@ impl Drop for VulkanManager {
         };
     }
 }
-
-struct ImageTransition {
-    image: vk::Image,
-    from: vk::ImageLayout,
-    to: vk::ImageLayout,
-    wait_access: vk::AccessFlags,
-    dst_access: vk::AccessFlags,
-}
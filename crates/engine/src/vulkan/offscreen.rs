@@ -0,0 +1,58 @@
+use ash::vk;
+use gpu_allocator::{vulkan::Allocation, MemoryLocation};
+
+use super::{allocator::Allocator, GraphicsResult};
+
+/// The format of [`OffscreenTarget`] images, matches the sRGB conversion of a typical swapchain.
+pub(crate) const OFFSCREEN_FORMAT: vk::Format = vk::Format::R8G8B8A8_SRGB;
+
+/// An image that takes the place of the swapchain when rendering without a window.
+///
+/// At the end of every frame, the image is left in `TRANSFER_SRC_OPTIMAL` layout, so that it can be copied.
+pub(crate) struct OffscreenTarget {
+    pub(crate) image: vk::Image,
+    pub(crate) alloc: Allocation,
+    pub(crate) imageview: vk::ImageView,
+    pub(crate) extent: vk::Extent2D,
+}
+
+impl OffscreenTarget {
+    pub(crate) fn new(
+        logical_device: &ash::Device,
+        allocator: &Allocator,
+        extent: vk::Extent2D,
+    ) -> GraphicsResult<Self> {
+        let (image, alloc) = allocator.create_image(
+            extent.width,
+            extent.height,
+            OFFSCREEN_FORMAT,
+            vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::TRANSFER_SRC,
+            MemoryLocation::GpuOnly,
+        )?;
+
+        let subresource_range = vk::ImageSubresourceRange::builder()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .base_mip_level(0)
+            .level_count(1)
+            .base_array_layer(0)
+            .layer_count(1);
+        let imageview_create_info = vk::ImageViewCreateInfo::builder()
+            .image(image)
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(OFFSCREEN_FORMAT)
+            .subresource_range(*subresource_range);
+        let imageview = unsafe { logical_device.create_image_view(&imageview_create_info, None) }?;
+
+        Ok(Self {
+            image,
+            alloc,
+            imageview,
+            extent,
+        })
+    }
+
+    pub(crate) unsafe fn cleanup(&self, logical_device: &ash::Device, allocator: &Allocator) {
+        logical_device.destroy_image_view(self.imageview, None);
+        allocator.destroy_image(self.image, self.alloc.clone());
+    }
+}
@@ -1,103 +1,284 @@
+use std::mem::size_of;
+
 use ash::vk;
+use ve_format::mesh::Vertex;
+use ve_shader_reflect::BlockMemberType;
 
 use crate::assets::shader;
 
-/// Loads a vertex and fragment shader from the filesystem and creates a [`vk::ShaderModule`] for each.
+use super::{
+    error::{GraphicsError, GraphicsResult},
+    pipeline_cache,
+};
+
+/// A single attribute of a [`VertexLayout`].
+#[derive(Debug, PartialEq, Eq)]
+pub struct VertexAttribute {
+    pub name: &'static str,
+    /// The component type and count, e.g. [`FloatVector(3)`](BlockMemberType::FloatVector)
+    pub kind: BlockMemberType,
+    pub format: vk::Format,
+    pub offset: u32,
+}
+
+/// The attributes of the vertices of a mesh, which vertex shader inputs are matched against.
+///
+/// All meshes use [`Vertex`], so [`VertexLayout::MESH`] is currently the only layout.
+/// Further attributes, e.g. tangents or skin weights, need a new vertex format in `ve_format`
+/// and a layout describing it, shaders that read them are rejected until then.
+#[derive(Debug, PartialEq, Eq)]
+pub struct VertexLayout {
+    pub attributes: &'static [VertexAttribute],
+    pub stride: u32,
+}
+
+impl VertexLayout {
+    /// The layout of [`Vertex`].
+    pub const MESH: VertexLayout = VertexLayout {
+        attributes: &[
+            VertexAttribute {
+                name: "position",
+                kind: BlockMemberType::FloatVector(3),
+                format: vk::Format::R32G32B32_SFLOAT,
+                offset: 0,
+            },
+            VertexAttribute {
+                name: "color",
+                kind: BlockMemberType::FloatVector(3),
+                format: vk::Format::R32G32B32_SFLOAT,
+                offset: 12,
+            },
+            VertexAttribute {
+                name: "normal",
+                kind: BlockMemberType::FloatVector(3),
+                format: vk::Format::R32G32B32_SFLOAT,
+                offset: 24,
+            },
+            VertexAttribute {
+                name: "uv",
+                kind: BlockMemberType::FloatVector(2),
+                format: vk::Format::R32G32_SFLOAT,
+                offset: 36,
+            },
+        ],
+        stride: size_of::<Vertex>() as u32,
+    };
+}
+
+/// Returns the scalar type and component count of a scalar or vector type.
+fn components(kind: &BlockMemberType) -> Option<(BlockMemberType, u32)> {
+    match *kind {
+        BlockMemberType::Float | BlockMemberType::Int | BlockMemberType::UInt => {
+            Some((kind.clone(), 1))
+        }
+        BlockMemberType::FloatVector(count) => Some((BlockMemberType::Float, count)),
+        BlockMemberType::IntVector(count) => Some((BlockMemberType::Int, count)),
+        BlockMemberType::UIntVector(count) => Some((BlockMemberType::UInt, count)),
+        _ => None,
+    }
+}
+
+/// Loads a vertex and fragment shader from the asset root and creates a [`vk::ShaderModule`] for each.
 pub(crate) fn create_shader_modules(
     shader: &str,
     device: &ash::Device,
     out_spv_vert: &mut Vec<u32>,
     out_spv_frag: &mut Vec<u32>,
-) -> Result<(vk::ShaderModule, vk::ShaderModule), vk::Result> {
-    let vertexshader_createinfo = shader::load(shader, shader::ShaderKind::Vertex, out_spv_vert);
+) -> GraphicsResult<(vk::ShaderModule, vk::ShaderModule)> {
+    *out_spv_vert = shader::load(shader, shader::ShaderKind::Vertex)?;
+    *out_spv_frag = shader::load(shader, shader::ShaderKind::Fragment)?;
+
+    let vertexshader_createinfo = vk::ShaderModuleCreateInfo::builder().code(out_spv_vert);
     let vertexshader_module =
         unsafe { device.create_shader_module(&vertexshader_createinfo, None)? };
 
-    let fragmentshader_createinfo =
-        shader::load(shader, shader::ShaderKind::Fragment, out_spv_frag);
+    let fragmentshader_createinfo = vk::ShaderModuleCreateInfo::builder().code(out_spv_frag);
     let fragmentshader_module =
         unsafe { device.create_shader_module(&fragmentshader_createinfo, None)? };
 
     Ok((vertexshader_module, fragmentshader_module))
 }
 
+/// Reflects a vertex and fragment shader and merges the results, see [`ve_shader_reflect::merge`].
+pub(crate) fn reflect_shader_modules(
+    spv_vert: &[u32],
+    spv_frag: &[u32],
+) -> GraphicsResult<ve_shader_reflect::ShaderInfo> {
+    let refl_vertex = ve_shader_reflect::reflect_shader(spv_vert)?;
+    let refl_fragment = ve_shader_reflect::reflect_shader(spv_frag)?;
+    Ok(ve_shader_reflect::merge(
+        refl_vertex,
+        &refl_fragment,
+        false,
+    )?)
+}
+
+/// Maps the inputs of a vertex shader to the attributes of `layout`.
+///
+/// Inputs are matched by name, ignoring case, an `in_` prefix and everything up to the last `.`,
+/// so both `vIn.normal` and `in_Normal` read the normal of a vertex.
+/// The component types have to match, the component counts may differ, e.g. a `float4` input reads `w = 1` from a 3 component attribute.
+///
+/// # Errors
+/// - [`GraphicsError::MissingVertexAttribute`] when an input does not match any attribute
+/// - [`GraphicsError::InvalidVertexAttributeType`] when an input has a different component type than its attribute
+pub(crate) fn vertex_attributes(
+    shader: &str,
+    inputs: &[ve_shader_reflect::StageInput],
+    layout: &VertexLayout,
+) -> GraphicsResult<Vec<vk::VertexInputAttributeDescription>> {
+    inputs
+        .iter()
+        .map(|input| {
+            let name = input.name.rsplit('.').next().unwrap_or_default();
+            let name = name.strip_prefix("in_").unwrap_or(name);
+            let attribute = layout
+                .attributes
+                .iter()
+                .find(|attribute| attribute.name.eq_ignore_ascii_case(name))
+                .ok_or_else(|| GraphicsError::MissingVertexAttribute {
+                    shader: shader.to_owned(),
+                    attribute: input.name.clone(),
+                })?;
+
+            let input_type = components(&input.kind).map(|(scalar, _)| scalar);
+            if input_type.is_none()
+                || input_type != components(&attribute.kind).map(|(scalar, _)| scalar)
+            {
+                return Err(GraphicsError::InvalidVertexAttributeType {
+                    shader: shader.to_owned(),
+                    attribute: input.name.clone(),
+                    expected: attribute.kind.clone(),
+                });
+            }
+
+            Ok(vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: input.location,
+                offset: attribute.offset,
+                format: attribute.format,
+            })
+        })
+        .collect()
+}
+
+/// Converts reflected push constant blocks into the ranges of a [`vk::PipelineLayout`].
+///
+/// Blocks accessed from the same stages are combined into a single range that covers all of them.
+pub(crate) fn push_constant_ranges(
+    blocks: &[ve_shader_reflect::PushConstantRange],
+) -> Vec<vk::PushConstantRange> {
+    let mut ranges: Vec<vk::PushConstantRange> = Vec::new();
+    for block in blocks {
+        let stage_flags = vk::ShaderStageFlags::from_raw(block.stages.0);
+        match ranges.iter_mut().find(|r| r.stage_flags == stage_flags) {
+            Some(range) => {
+                let end = (range.offset + range.size).max(block.offset + block.size);
+                range.offset = range.offset.min(block.offset);
+                range.size = end - range.offset;
+            }
+            None => ranges.push(
+                vk::PushConstantRange::builder()
+                    .stage_flags(stage_flags)
+                    .offset(block.offset)
+                    .size(block.size)
+                    .build(),
+            ),
+        }
+    }
+    ranges
+}
+
+/// Loads a compute shader from the asset root and creates a [`vk::ShaderModule`] for it.
+pub(crate) fn create_compute_shader_module(
+    shader: &str,
+    device: &ash::Device,
+    out_spv_comp: &mut Vec<u32>,
+) -> GraphicsResult<vk::ShaderModule> {
+    *out_spv_comp = shader::load(shader, shader::ShaderKind::Compute)?;
+    let computeshader_createinfo = vk::ShaderModuleCreateInfo::builder().code(out_spv_comp);
+    Ok(unsafe { device.create_shader_module(&computeshader_createinfo, None) }?)
+}
+
+/// Creates a compute [`vk::Pipeline`] using the entry point `comp` of the given shader module.
+pub(crate) fn create_compute_pipeline(
+    layout: vk::PipelineLayout,
+    device: &ash::Device,
+    computeshader_module: vk::ShaderModule,
+) -> Result<vk::Pipeline, vk::Result> {
+    let comp_func_name = std::ffi::CString::new("comp").unwrap();
+
+    let computeshader_stage = vk::PipelineShaderStageCreateInfo::builder()
+        .stage(vk::ShaderStageFlags::COMPUTE)
+        .module(computeshader_module)
+        .name(&comp_func_name);
+
+    let pipeline_info = vk::ComputePipelineCreateInfo::builder()
+        .stage(computeshader_stage.build())
+        .layout(layout);
+    let computepipeline = unsafe {
+        device
+            .create_compute_pipelines(pipeline_cache::current(), &[pipeline_info.build()], None)
+            .map_err(|(_, err)| err)?
+    }[0];
+    Ok(computepipeline)
+}
+
 /// Creates a [`vk::Pipeline`] with the given options.
 ///
 /// Used to reduce code duplication.
 ///
 /// # Parameters
-/// - `uses_vertex_attribs`: set to true if the Pipeline expects vertex data to be passed into the vertex shader, false is useful for PP Effects.
+/// - `vertex_attributes`: the mesh attributes read by the vertex shader, see [`vertex_attributes`]. Empty if the Pipeline does not use vertex data, which is useful for PP Effects.
+/// - `vertex_layout`: the layout of the vertices the attributes are read from.
 /// - `attachment_count`: the number of color attachments the pipeline expects.
 /// - `blend_func`: a single [`vk::PipelineColorBlendAttachmentState`] to be used for every color attachment.
-/// - `depth_test`: true if the Pipeline should have depth testing and writing enabled, false otherwise.
+/// - `depth_test`: the compare op if the Pipeline should have depth testing and writing enabled, `None` otherwise.
 /// - `stencil_func`: an optional [`vk::StencilOpState`] to be used for stencil testing.
+/// - `specialization_info`: the values of specialization constants, used for both shader stages.
 #[allow(clippy::too_many_arguments)]
 pub(crate) fn create_pipeline(
     layout: vk::PipelineLayout,
     renderpass: vk::RenderPass,
     subpass: u32,
-    uses_vertex_attribs: bool,
+    vertex_attributes: &[vk::VertexInputAttributeDescription],
+    vertex_layout: &VertexLayout,
     attachment_count: usize,
     blend_func: vk::PipelineColorBlendAttachmentState,
-    depth_test: bool,
+    depth_test: Option<vk::CompareOp>,
     stencil_func: Option<vk::StencilOpState>,
     device: &ash::Device,
     vertexshader_module: vk::ShaderModule,
     fragmentshader_module: vk::ShaderModule,
+    specialization_info: Option<&vk::SpecializationInfo>,
     wireframe: bool,
 ) -> Result<vk::Pipeline, vk::Result> {
     let vert_func_name = std::ffi::CString::new("vert").unwrap();
     let frag_func_name = std::ffi::CString::new("frag").unwrap();
 
-    let vertexshader_stage = vk::PipelineShaderStageCreateInfo::builder()
+    let mut vertexshader_stage = vk::PipelineShaderStageCreateInfo::builder()
         .stage(vk::ShaderStageFlags::VERTEX)
         .module(vertexshader_module)
         .name(&vert_func_name);
-    let fragmentshader_stage = vk::PipelineShaderStageCreateInfo::builder()
+    let mut fragmentshader_stage = vk::PipelineShaderStageCreateInfo::builder()
         .stage(vk::ShaderStageFlags::FRAGMENT)
         .module(fragmentshader_module)
         .name(&frag_func_name);
+    if let Some(specialization_info) = specialization_info {
+        vertexshader_stage = vertexshader_stage.specialization_info(specialization_info);
+        fragmentshader_stage = fragmentshader_stage.specialization_info(specialization_info);
+    }
     let shader_stages = [vertexshader_stage.build(), fragmentshader_stage.build()];
 
-    let vertex_attrib_descs = [
-        // position
-        vk::VertexInputAttributeDescription {
-            binding: 0,
-            location: 0,
-            offset: 0,
-            format: vk::Format::R32G32B32_SFLOAT,
-        },
-        // color
-        vk::VertexInputAttributeDescription {
-            binding: 0,
-            location: 1,
-            offset: 12,
-            format: vk::Format::R32G32B32_SFLOAT,
-        },
-        // normal
-        vk::VertexInputAttributeDescription {
-            binding: 0,
-            location: 2,
-            offset: 24,
-            format: vk::Format::R32G32B32_SFLOAT,
-        },
-        // uv
-        vk::VertexInputAttributeDescription {
-            binding: 0,
-            location: 3,
-            offset: 36,
-            format: vk::Format::R32G32_SFLOAT,
-        },
-    ];
     let vertex_binding_descs = [vk::VertexInputBindingDescription {
         binding: 0,
-        stride: 44,
+        stride: vertex_layout.stride,
         input_rate: vk::VertexInputRate::VERTEX,
     }];
     let mut vertex_input_info = vk::PipelineVertexInputStateCreateInfo::builder();
-    if uses_vertex_attribs {
+    if !vertex_attributes.is_empty() {
         vertex_input_info = vertex_input_info
-            .vertex_attribute_descriptions(&vertex_attrib_descs)
+            .vertex_attribute_descriptions(vertex_attributes)
             .vertex_binding_descriptions(&vertex_binding_descs);
     }
     let vertex_input_info = vertex_input_info.build();
@@ -144,9 +325,9 @@ pub(crate) fn create_pipeline(
         vk::PipelineColorBlendStateCreateInfo::builder().attachments(&colourblend_attachments);
 
     let mut depth_stencil_info = vk::PipelineDepthStencilStateCreateInfo::builder()
-        .depth_test_enable(depth_test)
-        .depth_write_enable(depth_test)
-        .depth_compare_op(vk::CompareOp::LESS_OR_EQUAL)
+        .depth_test_enable(depth_test.is_some())
+        .depth_write_enable(depth_test.is_some())
+        .depth_compare_op(depth_test.unwrap_or(vk::CompareOp::ALWAYS))
         .stencil_test_enable(stencil_func.is_some());
     if let Some(stencil_func) = stencil_func {
         depth_stencil_info = depth_stencil_info.front(stencil_func);
@@ -176,7 +357,7 @@ pub(crate) fn create_pipeline(
         .subpass(subpass);
     let graphicspipeline = unsafe {
         device
-            .create_graphics_pipelines(vk::PipelineCache::null(), &[pipeline_info.build()], None)
+            .create_graphics_pipelines(pipeline_cache::current(), &[pipeline_info.build()], None)
             .expect("A problem with the pipeline creation")
     }[0];
     Ok(graphicspipeline)
@@ -185,13 +366,13 @@ pub(crate) fn create_pipeline(
 pub(crate) fn create_ui_pipeline(
     device: &ash::Device,
     sampler_linear: vk::Sampler,
-) -> (
+    renderpass: vk::RenderPass,
+) -> GraphicsResult<(
     vk::DescriptorSetLayout,
     vk::PipelineLayout,
-    vk::RenderPass,
     vk::Pipeline,
     vk::Pipeline,
-) {
+)> {
     let desc_set_layout = {
         let samplers = [sampler_linear];
         let bindings = [vk::DescriptorSetLayoutBinding::builder()
@@ -208,13 +389,14 @@ pub(crate) fn create_ui_pipeline(
         unsafe { device.create_descriptor_set_layout(&info, None) }.unwrap()
     };
 
-    let ranges = [vk::PushConstantRange::builder()
-        .stage_flags(vk::ShaderStageFlags::VERTEX)
-        .offset(0)
-        .size(64)
-        .build()];
+    let mut spv_vert = Vec::new();
+    let mut spv_frag = Vec::new();
+    let (vert_mod, frag_mod) = create_shader_modules("ui", device, &mut spv_vert, &mut spv_frag)?;
 
     let pipeline_layout = {
+        let refl = reflect_shader_modules(&spv_vert, &spv_frag)?;
+        let ranges = push_constant_ranges(&refl.push_constants);
+
         let sets = [desc_set_layout];
         let info = vk::PipelineLayoutCreateInfo::builder()
             .set_layouts(&sets)
@@ -223,39 +405,10 @@ pub(crate) fn create_ui_pipeline(
         unsafe { device.create_pipeline_layout(&info, None) }.unwrap()
     };
 
-    let renderpass = {
-        let attachments = [vk::AttachmentDescription::builder()
-            .format(vk::Format::R16G16B16A16_SFLOAT)
-            .samples(vk::SampleCountFlags::TYPE_1)
-            .load_op(vk::AttachmentLoadOp::LOAD)
-            .store_op(vk::AttachmentStoreOp::STORE)
-            .initial_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
-            .final_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
-            .build()];
-        let refs = [vk::AttachmentReference::builder()
-            .attachment(0)
-            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
-            .build()];
-        let subpasses = [vk::SubpassDescription::builder()
-            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
-            .color_attachments(&refs)
-            .build()];
-        let info = vk::RenderPassCreateInfo::builder()
-            .attachments(&attachments)
-            .subpasses(&subpasses)
-            .build();
-        unsafe { device.create_render_pass(&info, None) }.unwrap()
-    };
-
     let (pipeline, pipeline_wireframe) = {
         let vert_func_name = std::ffi::CString::new("vert").unwrap();
         let frag_func_name = std::ffi::CString::new("frag").unwrap();
 
-        let mut spv_vert = Vec::new();
-        let mut spv_frag = Vec::new();
-        let (vert_mod, frag_mod) =
-            create_shader_modules("ui", device, &mut spv_vert, &mut spv_frag).unwrap();
-
         let mut spv_vert_wireframe = Vec::new();
         let mut spv_frag_wireframe = Vec::new();
         let (vert_mod_wireframe, frag_mod_wireframe) = create_shader_modules(
@@ -263,8 +416,7 @@ pub(crate) fn create_ui_pipeline(
             device,
             &mut spv_vert_wireframe,
             &mut spv_frag_wireframe,
-        )
-        .unwrap();
+        )?;
 
         let stages = [
             vk::PipelineShaderStageCreateInfo::builder()
@@ -392,7 +544,7 @@ pub(crate) fn create_ui_pipeline(
 
         let res = unsafe {
             device.create_graphics_pipelines(
-                vk::PipelineCache::null(),
+                pipeline_cache::current(),
                 &[info, info_wireframe],
                 None,
             )
@@ -410,11 +562,61 @@ pub(crate) fn create_ui_pipeline(
         (res[0], res[1])
     };
 
-    (
+    Ok((
         desc_set_layout,
         pipeline_layout,
-        renderpass,
         pipeline,
         pipeline_wireframe,
-    )
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use ve_shader_reflect::{BlockMemberType, StageInput};
+
+    use super::{vertex_attributes, VertexLayout};
+    use crate::vulkan::error::GraphicsError;
+
+    fn input(location: u32, name: &str) -> StageInput {
+        StageInput {
+            location,
+            kind: BlockMemberType::FloatVector(3),
+            name: name.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_vertex_attributes() {
+        let attributes = vertex_attributes(
+            "test",
+            &[input(0, "vIn.position"), input(1, "in_UV")],
+            &VertexLayout::MESH,
+        )
+        .unwrap();
+        assert_eq!(attributes.len(), 2);
+        assert_eq!((attributes[0].location, attributes[0].offset), (0, 0));
+        assert_eq!((attributes[1].location, attributes[1].offset), (1, 36));
+
+        let missing = vertex_attributes("test", &[input(0, "vIn.tangent")], &VertexLayout::MESH);
+        assert!(matches!(
+            missing,
+            Err(GraphicsError::MissingVertexAttribute { attribute, .. }) if attribute == "vIn.tangent"
+        ));
+
+        // the component count may differ, the component type may not
+        let mut position = input(0, "position");
+        position.kind = BlockMemberType::FloatVector(4);
+        assert!(vertex_attributes("test", &[position], &VertexLayout::MESH).is_ok());
+        for kind in [
+            BlockMemberType::IntVector(3),
+            BlockMemberType::FloatMatrix(3),
+        ] {
+            let mut normal = input(0, "normal");
+            normal.kind = kind;
+            assert!(matches!(
+                vertex_attributes("test", &[normal], &VertexLayout::MESH),
+                Err(GraphicsError::InvalidVertexAttributeType { attribute, .. }) if attribute == "normal"
+            ));
+        }
+    }
 }
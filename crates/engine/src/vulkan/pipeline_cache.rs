@@ -0,0 +1,104 @@
+use std::cell::Cell;
+
+use ash::vk;
+
+use super::error::GraphicsResult;
+
+/// Pipeline cache data is stored next to `engine.toml`.
+const CACHE_FILE: &str = "pipeline_cache.bin";
+const MAGIC: &[u8; 4] = b"VEPC";
+
+thread_local! {
+    /// The cache all pipelines are created with, see [`current`].
+    static CURRENT: Cell<vk::PipelineCache> = const { Cell::new(vk::PipelineCache::null()) };
+}
+
+/// Returns the pipeline cache of the renderer, or a null handle if there is none.
+pub(crate) fn current() -> vk::PipelineCache {
+    CURRENT.with(Cell::get)
+}
+
+/// Identifies the device and driver a cache was created with, as data of other drivers can't be used.
+fn header(properties: &vk::PhysicalDeviceProperties) -> Vec<u8> {
+    let mut header = MAGIC.to_vec();
+    header.extend_from_slice(&properties.vendor_id.to_le_bytes());
+    header.extend_from_slice(&properties.device_id.to_le_bytes());
+    header.extend_from_slice(&properties.driver_version.to_le_bytes());
+    header.extend_from_slice(&properties.pipeline_cache_uuid);
+    header
+}
+
+/// Returns the cache data of `file`, or `None` if it was written for a different device or driver.
+fn strip_header<'a>(file: &'a [u8], properties: &vk::PhysicalDeviceProperties) -> Option<&'a [u8]> {
+    file.strip_prefix(header(properties).as_slice())
+}
+
+/// Creates the pipeline cache, with the data saved by a previous run if it matches this device and driver.
+pub(crate) fn create(
+    device: &ash::Device,
+    properties: &vk::PhysicalDeviceProperties,
+) -> GraphicsResult<vk::PipelineCache> {
+    let file = std::fs::read(CACHE_FILE).unwrap_or_default();
+    let initial_data = match strip_header(&file, properties) {
+        Some(data) => data,
+        None => {
+            if !file.is_empty() {
+                log::info!("Discarding pipeline cache of a different device or driver");
+            }
+            &[]
+        }
+    };
+
+    let info = vk::PipelineCacheCreateInfo::builder().initial_data(initial_data);
+    let cache = unsafe { device.create_pipeline_cache(&info, None)? };
+    CURRENT.with(|current| current.set(cache));
+    Ok(cache)
+}
+
+/// Saves the data of `cache` to disk and destroys it.
+pub(crate) fn destroy(
+    device: &ash::Device,
+    cache: vk::PipelineCache,
+    properties: &vk::PhysicalDeviceProperties,
+) {
+    match unsafe { device.get_pipeline_cache_data(cache) } {
+        Ok(data) => {
+            let mut file = header(properties);
+            file.extend_from_slice(&data);
+            if let Err(e) = std::fs::write(CACHE_FILE, file) {
+                log::warn!("Failed to save the pipeline cache: {}", e);
+            }
+        }
+        Err(e) => log::warn!("Failed to read the pipeline cache: {}", e),
+    }
+
+    CURRENT.with(|current| current.set(vk::PipelineCache::null()));
+    unsafe { device.destroy_pipeline_cache(cache, None) };
+}
+
+#[cfg(test)]
+mod test {
+    use ash::vk;
+
+    use super::{header, strip_header};
+
+    #[test]
+    fn test_stale_cache_is_discarded() {
+        let properties = vk::PhysicalDeviceProperties {
+            vendor_id: 0x10DE,
+            device_id: 0x2204,
+            driver_version: 1,
+            ..Default::default()
+        };
+        let mut file = header(&properties);
+        file.extend_from_slice(&[1, 2, 3]);
+        assert_eq!(strip_header(&file, &properties), Some(&[1, 2, 3][..]));
+
+        let updated_driver = vk::PhysicalDeviceProperties {
+            driver_version: 2,
+            ..properties
+        };
+        assert_eq!(strip_header(&file, &updated_driver), None);
+        assert_eq!(strip_header(&[], &properties), None);
+    }
+}
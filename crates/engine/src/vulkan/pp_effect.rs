@@ -1,13 +1,27 @@
-use std::rc::Rc;
+use std::{
+    cell::{Cell, RefCell},
+    rc::{Rc, Weak},
+};
 
 use ash::vk;
 
-use super::pipeline;
+use super::{
+    error::GraphicsResult,
+    pipeline::{self, VertexLayout},
+};
+
+thread_local! {
+    /// All PPEffects that are alive, including those only used by a single camera, so that they can be reloaded when their shader changes.
+    static LIVE_EFFECTS: RefCell<Vec<Weak<PPEffect>>> = const { RefCell::new(Vec::new()) };
+}
 
 /// This struct holds the necessary information about a single post processing effect.
 pub struct PPEffect {
     /// The [`vk::Pipeline`] used by this post processing effect.
-    pub(crate) pipeline: vk::Pipeline,
+    pub(crate) pipeline: Cell<vk::Pipeline>,
+    shader: String,
+    pipe_layout: vk::PipelineLayout,
+    renderpass: vk::RenderPass,
     device: Rc<ash::Device>,
 }
 
@@ -22,7 +36,36 @@ impl PPEffect {
         pipe_layout: vk::PipelineLayout,
         renderpass: vk::RenderPass,
         device: Rc<ash::Device>,
-    ) -> Result<Rc<PPEffect>, vk::Result> {
+    ) -> GraphicsResult<Rc<PPEffect>> {
+        let pipeline = Self::create_pipeline(shader, pipe_layout, renderpass, &device)?;
+
+        let effect = Rc::new(Self {
+            pipeline: Cell::new(pipeline),
+            shader: shader.to_owned(),
+            pipe_layout,
+            renderpass,
+            device,
+        });
+        LIVE_EFFECTS.with(|effects| effects.borrow_mut().push(Rc::downgrade(&effect)));
+
+        Ok(effect)
+    }
+
+    /// Returns all PPEffects that are still in use.
+    pub(crate) fn live_effects() -> Vec<Rc<PPEffect>> {
+        LIVE_EFFECTS.with(|effects| {
+            let mut effects = effects.borrow_mut();
+            effects.retain(|effect| effect.strong_count() > 0);
+            effects.iter().filter_map(Weak::upgrade).collect()
+        })
+    }
+
+    fn create_pipeline(
+        shader: &str,
+        pipe_layout: vk::PipelineLayout,
+        renderpass: vk::RenderPass,
+        device: &ash::Device,
+    ) -> GraphicsResult<vk::Pipeline> {
         let blend_func = vk::PipelineColorBlendAttachmentState::builder()
             .blend_enable(false)
             .color_write_mask(
@@ -37,7 +80,7 @@ impl PPEffect {
         let mut fragmentshader_code = Vec::new();
         let (vertex_shader, fragment_shader) = pipeline::create_shader_modules(
             shader,
-            &device,
+            device,
             &mut vertexshader_code,
             &mut fragmentshader_code,
         )?;
@@ -46,30 +89,54 @@ impl PPEffect {
             pipe_layout,
             renderpass,
             0,
-            false,
+            &[],
+            &VertexLayout::MESH,
             1,
             blend_func,
-            false,
             None,
-            &device,
+            None,
+            device,
             vertex_shader,
             fragment_shader,
+            None,
             false,
-        )?;
+        );
 
         unsafe {
             device.destroy_shader_module(vertex_shader, None);
             device.destroy_shader_module(fragment_shader, None);
         }
 
-        Ok(Rc::new(Self { pipeline, device }))
+        Ok(pipeline?)
+    }
+
+    /// Returns true if this effect was created with the shader `name`.
+    pub(crate) fn uses_shader(&self, name: &str) -> bool {
+        self.shader == name
+    }
+
+    /// Recreates the pipeline from the current shader code, the old pipeline is kept if this fails.
+    ///
+    /// The pipeline must not be in use by the GPU.
+    pub(crate) fn reload(&self) -> GraphicsResult<()> {
+        let pipeline = Self::create_pipeline(
+            &self.shader,
+            self.pipe_layout,
+            self.renderpass,
+            &self.device,
+        )?;
+        unsafe {
+            self.device
+                .destroy_pipeline(self.pipeline.replace(pipeline), None);
+        }
+        Ok(())
     }
 }
 
 impl Drop for PPEffect {
     fn drop(&mut self) {
         unsafe {
-            self.device.destroy_pipeline(self.pipeline, None);
+            self.device.destroy_pipeline(self.pipeline.get(), None);
         }
     }
 }
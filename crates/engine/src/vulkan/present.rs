@@ -0,0 +1,89 @@
+use ash::vk;
+use serde::{Deserialize, Serialize};
+
+/// Upper bound of [`PresentConfig::frames_in_flight`], per-frame resources are created for this many frames.
+pub const MAX_FRAMES_IN_FLIGHT: u8 = 4;
+
+/// How finished frames are handed to the window, see [`PresentConfig::present_mode`].
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PresentMode {
+    /// Waits for vertical blank, the only mode that is supported everywhere
+    Fifo,
+    /// Waits for vertical blank but replaces queued frames with newer ones, no tearing with low latency
+    #[default]
+    Mailbox,
+    /// Presents immediately, which may tear
+    Immediate,
+}
+
+impl PresentMode {
+    pub(crate) fn to_vk(self) -> vk::PresentModeKHR {
+        match self {
+            PresentMode::Fifo => vk::PresentModeKHR::FIFO,
+            PresentMode::Mailbox => vk::PresentModeKHR::MAILBOX,
+            PresentMode::Immediate => vk::PresentModeKHR::IMMEDIATE,
+        }
+    }
+
+    pub(crate) fn from_vk(mode: vk::PresentModeKHR) -> Option<Self> {
+        match mode {
+            vk::PresentModeKHR::FIFO => Some(PresentMode::Fifo),
+            vk::PresentModeKHR::MAILBOX => Some(PresentMode::Mailbox),
+            vk::PresentModeKHR::IMMEDIATE => Some(PresentMode::Immediate),
+            _ => None,
+        }
+    }
+}
+
+/// Frame pacing settings of the renderer, applied with [`VulkanManager::set_present_config`](super::VulkanManager::set_present_config).
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PresentConfig {
+    /// Falls back to [`PresentMode::Fifo`] if the surface does not support it.
+    pub present_mode: PresentMode,
+    /// Requested number of swapchain images, clamped to what the surface supports.
+    pub swapchain_image_count: u32,
+    /// Number of frames the CPU may record ahead of the GPU, between 1 and [`MAX_FRAMES_IN_FLIGHT`].
+    pub frames_in_flight: u8,
+    /// Maximum frames per second, unlimited if `None`.
+    pub frame_rate_limit: Option<f32>,
+}
+
+impl Default for PresentConfig {
+    fn default() -> Self {
+        Self {
+            present_mode: PresentMode::default(),
+            swapchain_image_count: 3,
+            frames_in_flight: 3,
+            frame_rate_limit: None,
+        }
+    }
+}
+
+impl PresentConfig {
+    /// Returns a copy with all values in their valid ranges.
+    pub(crate) fn clamped(mut self) -> Self {
+        self.swapchain_image_count = self.swapchain_image_count.max(1);
+        self.frames_in_flight = self.frames_in_flight.clamp(1, MAX_FRAMES_IN_FLIGHT);
+        self.frame_rate_limit = self.frame_rate_limit.filter(|limit| *limit > 0.0);
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{PresentConfig, PresentMode, MAX_FRAMES_IN_FLIGHT};
+
+    #[test]
+    fn test_partial_config() {
+        let config: PresentConfig =
+            toml::from_str("present_mode = \"fifo\"\nframes_in_flight = 9").unwrap();
+        assert_eq!(config.present_mode, PresentMode::Fifo);
+        assert_eq!(config.swapchain_image_count, 3);
+        assert_eq!(config.frame_rate_limit, None);
+
+        let config = config.clamped();
+        assert_eq!(config.frames_in_flight, MAX_FRAMES_IN_FLIGHT);
+    }
+}
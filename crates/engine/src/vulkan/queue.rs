@@ -49,10 +49,11 @@ pub struct QueueFamilies {
 }
 
 impl QueueFamilies {
+    /// Selects a graphics queue family that can present to `surface`, if given.
     pub fn init(
         instance: &ash::Instance,
         physical_device: vk::PhysicalDevice,
-        surface: &surface::SurfaceWrapper,
+        surface: Option<&surface::SurfaceWrapper>,
     ) -> GraphicsResult<QueueFamilies> {
         let queues = QueueFamilies::find_suitable_queue_family(instance, physical_device, surface)?;
         Ok(QueueFamilies {
@@ -63,15 +64,19 @@ impl QueueFamilies {
     fn find_suitable_queue_family(
         instance: &ash::Instance,
         physical_device: vk::PhysicalDevice,
-        surface: &surface::SurfaceWrapper,
+        surface: Option<&surface::SurfaceWrapper>,
     ) -> GraphicsResult<u32> {
         let queuefamilyproperties =
             unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
 
         let mut found_graphics_q_index = None;
         for (index, qfam) in queuefamilyproperties.iter().enumerate() {
-            let surface_support =
-                surface.get_physical_device_surface_support(physical_device, index)?;
+            let surface_support = match surface {
+                Some(surface) => {
+                    surface.get_physical_device_surface_support(physical_device, index)?
+                }
+                None => true,
+            };
 
             if qfam.queue_flags.contains(vk::QueueFlags::GRAPHICS) && surface_support {
                 // found perfect queue family, break
@@ -95,17 +100,27 @@ pub fn init_device_and_queues(
     instance: &ash::Instance,
     physical_device: vk::PhysicalDevice,
     queue_families: &QueueFamilies,
+    swapchain: bool,
     ext_memory_budget: bool,
-) -> GraphicsResult<(ash::Device, Queues)> {
+) -> GraphicsResult<(ash::Device, Queues, vk::PhysicalDeviceFeatures)> {
     let ext_memory_budget_name = CString::new("VK_EXT_memory_budget").unwrap();
 
-    let device_extension_names_raw = [
-        khr::Swapchain::name().as_ptr(),
-        ext_memory_budget_name.as_ptr(),
-    ];
+    let mut device_extension_names_raw = Vec::new();
+    if swapchain {
+        device_extension_names_raw.push(khr::Swapchain::name().as_ptr());
+    }
+    if ext_memory_budget {
+        device_extension_names_raw.push(ext_memory_budget_name.as_ptr());
+    }
     // https://www.khronos.org/registry/vulkan/specs/1.2-extensions/man/html/VkPhysicalDeviceFeatures.html
     // required for wireframe fill mode
-    let features = vk::PhysicalDeviceFeatures::builder().fill_mode_non_solid(true); // TODO: check if feature is supported before force-enabling it
+    let supported_features = unsafe { instance.get_physical_device_features(physical_device) };
+    let features = vk::PhysicalDeviceFeatures::builder()
+        .fill_mode_non_solid(true) // TODO: check if feature is supported before force-enabling it
+        // optional, used for indirect drawing
+        .multi_draw_indirect(supported_features.multi_draw_indirect == vk::TRUE)
+        .draw_indirect_first_instance(supported_features.draw_indirect_first_instance == vk::TRUE)
+        .build();
     let priorities = [1.0];
 
     let queue_info = [vk::DeviceQueueCreateInfo::builder()
@@ -115,11 +130,7 @@ pub fn init_device_and_queues(
 
     let device_create_info = vk::DeviceCreateInfo::builder()
         .queue_create_infos(&queue_info)
-        .enabled_extension_names(if ext_memory_budget {
-            &device_extension_names_raw
-        } else {
-            &device_extension_names_raw[0..=0]
-        })
+        .enabled_extension_names(&device_extension_names_raw)
         .enabled_features(&features);
 
     let logical_device: ash::Device =
@@ -128,5 +139,5 @@ pub fn init_device_and_queues(
     let graphics_queue =
         unsafe { logical_device.get_device_queue(queue_families.graphics_q_index, 0) };
 
-    Ok((logical_device, Queues { graphics_queue }))
+    Ok((logical_device, Queues { graphics_queue }, features))
 }
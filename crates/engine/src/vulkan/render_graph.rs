@@ -0,0 +1,1381 @@
+//! A render graph that schedules the passes of a frame.
+//!
+//! Every frame, the passes are declared in execution order together with the images they read and write.
+//! The graph allocates all declared images, inserts the barriers between passes and
+//! creates renderpasses and framebuffers for all passes that draw into attachments.
+//!
+//! Images, renderpasses and framebuffers are cached across frames.
+//! When the output extent changes, the affected images and framebuffers are recreated automatically.
+//! Renderpasses only depend on formats, layouts and load/store operations, so their handles stay
+//! valid and pipelines created against them keep working after a resize.
+
+use std::{collections::HashMap, rc::Rc};
+
+use ash::vk;
+use gpu_allocator::{vulkan::Allocation, MemoryLocation};
+
+use super::{allocator::Allocator, error::GraphicsResult};
+
+/// Handle to an image declared in a [`RenderGraph`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct ImageId(usize);
+
+/// The size of an image declared in a [`RenderGraph`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum ImageSize {
+    /// The extent of the graph output, see [`RenderGraph::set_output_extent`].
+    Output,
+    Absolute(u32, u32),
+}
+
+/// Describes how a pass uses an image.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum ImageAccess {
+    ColorAttachment,
+    DepthStencilAttachment,
+    /// Depth and stencil testing without writes, can be combined with reading the image as input attachment.
+    DepthStencilReadOnly,
+    InputAttachment,
+    /// Sampled in a fragment shader.
+    Sampled,
+    TransferSrc,
+    TransferDst,
+    Present,
+}
+
+impl ImageAccess {
+    fn layout(self, format: vk::Format) -> vk::ImageLayout {
+        match self {
+            ImageAccess::ColorAttachment => vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            ImageAccess::DepthStencilAttachment => {
+                vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+            }
+            ImageAccess::DepthStencilReadOnly => vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+            ImageAccess::InputAttachment | ImageAccess::Sampled if is_depth_format(format) => {
+                vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL
+            }
+            ImageAccess::InputAttachment | ImageAccess::Sampled => {
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+            }
+            ImageAccess::TransferSrc => vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            ImageAccess::TransferDst => vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            ImageAccess::Present => vk::ImageLayout::PRESENT_SRC_KHR,
+        }
+    }
+
+    fn stages(self) -> vk::PipelineStageFlags {
+        match self {
+            ImageAccess::ColorAttachment => vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            ImageAccess::DepthStencilAttachment | ImageAccess::DepthStencilReadOnly => {
+                vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                    | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS
+            }
+            ImageAccess::InputAttachment | ImageAccess::Sampled => {
+                vk::PipelineStageFlags::FRAGMENT_SHADER
+            }
+            ImageAccess::TransferSrc | ImageAccess::TransferDst => vk::PipelineStageFlags::TRANSFER,
+            ImageAccess::Present => vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+        }
+    }
+
+    fn access(self) -> vk::AccessFlags {
+        match self {
+            ImageAccess::ColorAttachment => {
+                vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+            }
+            ImageAccess::DepthStencilAttachment => {
+                vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                    | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE
+            }
+            ImageAccess::DepthStencilReadOnly => vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ,
+            ImageAccess::InputAttachment => vk::AccessFlags::INPUT_ATTACHMENT_READ,
+            ImageAccess::Sampled => vk::AccessFlags::SHADER_READ,
+            ImageAccess::TransferSrc => vk::AccessFlags::TRANSFER_READ,
+            ImageAccess::TransferDst => vk::AccessFlags::TRANSFER_WRITE,
+            ImageAccess::Present => vk::AccessFlags::empty(),
+        }
+    }
+
+    fn usage(self) -> vk::ImageUsageFlags {
+        match self {
+            ImageAccess::ColorAttachment => vk::ImageUsageFlags::COLOR_ATTACHMENT,
+            ImageAccess::DepthStencilAttachment | ImageAccess::DepthStencilReadOnly => {
+                vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
+            }
+            ImageAccess::InputAttachment => vk::ImageUsageFlags::INPUT_ATTACHMENT,
+            ImageAccess::Sampled => vk::ImageUsageFlags::SAMPLED,
+            ImageAccess::TransferSrc => vk::ImageUsageFlags::TRANSFER_SRC,
+            ImageAccess::TransferDst => vk::ImageUsageFlags::TRANSFER_DST,
+            ImageAccess::Present => vk::ImageUsageFlags::empty(),
+        }
+    }
+
+    fn is_write(self) -> bool {
+        matches!(
+            self,
+            ImageAccess::ColorAttachment
+                | ImageAccess::DepthStencilAttachment
+                | ImageAccess::TransferDst
+        )
+    }
+}
+
+fn is_depth_format(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::D16_UNORM
+            | vk::Format::X8_D24_UNORM_PACK32
+            | vk::Format::D32_SFLOAT
+            | vk::Format::D16_UNORM_S8_UINT
+            | vk::Format::D24_UNORM_S8_UINT
+            | vk::Format::D32_SFLOAT_S8_UINT
+    )
+}
+
+fn has_stencil(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::D16_UNORM_S8_UINT
+            | vk::Format::D24_UNORM_S8_UINT
+            | vk::Format::D32_SFLOAT_S8_UINT
+    )
+}
+
+fn aspect_mask(format: vk::Format) -> vk::ImageAspectFlags {
+    if has_stencil(format) {
+        vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+    } else if is_depth_format(format) {
+        vk::ImageAspectFlags::DEPTH
+    } else {
+        vk::ImageAspectFlags::COLOR
+    }
+}
+
+/// The attachments of a single subpass of a graphics pass.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SubpassDesc {
+    pub(crate) colors: Vec<ImageId>,
+    /// Either [`ImageAccess::DepthStencilAttachment`] or [`ImageAccess::DepthStencilReadOnly`].
+    pub(crate) depth_stencil: Option<(ImageId, ImageAccess)>,
+    pub(crate) inputs: Vec<ImageId>,
+}
+
+/// Declares a single pass of a [`RenderGraph`].
+///
+/// A pass with subpasses is recorded inside of a renderpass, all other passes are recorded outside.
+pub(crate) struct PassDesc<P> {
+    /// Identifies the pass when it is recorded.
+    pub(crate) tag: P,
+    pub(crate) subpasses: Vec<SubpassDesc>,
+    /// Images used by the pass in other ways than as attachment.
+    pub(crate) accesses: Vec<(ImageId, ImageAccess)>,
+    /// Attachments that are cleared at the beginning of the pass.
+    pub(crate) clear_values: Vec<(ImageId, vk::ClearValue)>,
+    /// Images whose previous contents are completely overwritten by this pass.
+    pub(crate) discard: Vec<ImageId>,
+}
+
+impl<P> PassDesc<P> {
+    pub(crate) fn new(tag: P) -> Self {
+        Self {
+            tag,
+            subpasses: Vec::new(),
+            accesses: Vec::new(),
+            clear_values: Vec::new(),
+            discard: Vec::new(),
+        }
+    }
+
+    /// Returns true if the previous contents of `image` are not needed by this pass.
+    fn overwrites(&self, image: ImageId) -> bool {
+        self.discard.contains(&image) || self.clear_values.iter().any(|(id, _)| *id == image)
+    }
+
+    fn uses(&self, image: ImageId) -> bool {
+        self.accesses.iter().any(|(id, _)| *id == image)
+            || self.subpasses.iter().any(|s| {
+                s.colors.contains(&image)
+                    || s.inputs.contains(&image)
+                    || matches!(s.depth_stencil, Some((id, _)) if id == image)
+            })
+    }
+}
+
+struct ImportedImage {
+    image: vk::Image,
+    view: vk::ImageView,
+    extent: vk::Extent2D,
+    initial_access: Option<ImageAccess>,
+    final_access: ImageAccess,
+}
+
+struct ImageDecl {
+    name: String,
+    format: vk::Format,
+    size: ImageSize,
+    imported: Option<ImportedImage>,
+}
+
+/// The way an image is used within a single subpass, or within a whole non-renderpass pass.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct ImageUse {
+    layout: vk::ImageLayout,
+    stages: vk::PipelineStageFlags,
+    access: vk::AccessFlags,
+    write: bool,
+}
+
+impl ImageUse {
+    fn new(access: ImageAccess, format: vk::Format) -> Self {
+        Self {
+            layout: access.layout(format),
+            stages: access.stages(),
+            access: access.access(),
+            write: access.is_write(),
+        }
+    }
+
+    fn merge(self, other: ImageUse) -> Self {
+        Self {
+            layout: self.layout,
+            stages: self.stages | other.stages,
+            access: self.access | other.access,
+            write: self.write || other.write,
+        }
+    }
+}
+
+/// The state of an image after the passes scheduled so far.
+#[derive(Debug, Copy, Clone)]
+struct ImageState {
+    last_use: Option<ImageUse>,
+    written: bool,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct ScheduledBarrier {
+    image: ImageId,
+    old_layout: vk::ImageLayout,
+    new_layout: vk::ImageLayout,
+    src_access: vk::AccessFlags,
+    dst_access: vk::AccessFlags,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct AttachmentKey {
+    format: vk::Format,
+    load_op: vk::AttachmentLoadOp,
+    store_op: vk::AttachmentStoreOp,
+    stencil_load_op: vk::AttachmentLoadOp,
+    stencil_store_op: vk::AttachmentStoreOp,
+    initial_layout: vk::ImageLayout,
+    final_layout: vk::ImageLayout,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct SubpassKey {
+    colors: Vec<(u32, vk::ImageLayout)>,
+    depth_stencil: Option<(u32, vk::ImageLayout)>,
+    inputs: Vec<(u32, vk::ImageLayout)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct DependencyKey {
+    src_subpass: u32,
+    dst_subpass: u32,
+    src_stages: vk::PipelineStageFlags,
+    dst_stages: vk::PipelineStageFlags,
+    src_access: vk::AccessFlags,
+    dst_access: vk::AccessFlags,
+}
+
+/// Everything that is needed to create a [`vk::RenderPass`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct RenderpassKey {
+    attachments: Vec<AttachmentKey>,
+    subpasses: Vec<SubpassKey>,
+    dependencies: Vec<DependencyKey>,
+}
+
+#[derive(Debug, Clone)]
+struct ScheduledRenderpass {
+    key: RenderpassKey,
+    /// The image of every attachment in `key`.
+    images: Vec<ImageId>,
+}
+
+/// The result of scheduling a single pass, independent of any Vulkan objects.
+#[derive(Debug, Clone)]
+struct ScheduledPass {
+    src_stages: vk::PipelineStageFlags,
+    dst_stages: vk::PipelineStageFlags,
+    barriers: Vec<ScheduledBarrier>,
+    renderpass: Option<ScheduledRenderpass>,
+}
+
+impl ScheduledPass {
+    fn new() -> Self {
+        Self {
+            src_stages: vk::PipelineStageFlags::empty(),
+            dst_stages: vk::PipelineStageFlags::empty(),
+            barriers: Vec::new(),
+            renderpass: None,
+        }
+    }
+
+    /// Adds a barrier that makes `image` ready for `next`, if one is needed.
+    fn transition(
+        &mut self,
+        image: ImageId,
+        state: &ImageState,
+        next: ImageUse,
+        discard_contents: bool,
+    ) {
+        let (old_layout, src_stages, src_access) = match state.last_use {
+            Some(last) => {
+                if last.layout == next.layout && !last.write && !next.write {
+                    // reading after reading needs no synchronization
+                    return;
+                }
+                (last.layout, last.stages, last.access)
+            }
+            // the image might still be in use by a previous frame
+            None => (
+                vk::ImageLayout::UNDEFINED,
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                vk::AccessFlags::empty(),
+            ),
+        };
+
+        self.src_stages |= src_stages;
+        self.dst_stages |= next.stages;
+        self.barriers.push(ScheduledBarrier {
+            image,
+            old_layout: if discard_contents {
+                vk::ImageLayout::UNDEFINED
+            } else {
+                old_layout
+            },
+            new_layout: next.layout,
+            src_access,
+            dst_access: next.access,
+        });
+    }
+}
+
+/// Computes barriers, load/store operations and subpass dependencies for the given passes.
+///
+/// Returns the scheduled passes and the final transitions of all imported images.
+fn schedule<P>(
+    images: &[ImageDecl],
+    passes: &[PassDesc<P>],
+) -> (Vec<ScheduledPass>, ScheduledPass) {
+    let mut states: Vec<ImageState> = images
+        .iter()
+        .map(|image| {
+            let initial_access = image.imported.as_ref().and_then(|i| i.initial_access);
+            ImageState {
+                last_use: initial_access.map(|access| ImageUse::new(access, image.format)),
+                written: initial_access.is_some(),
+            }
+        })
+        .collect();
+
+    // returns true if the contents of `image` are still needed after pass `pass_index`
+    let needs_store = |image: ImageId, pass_index: usize| match passes[pass_index + 1..]
+        .iter()
+        .find(|p| p.uses(image))
+    {
+        Some(next) => !next.overwrites(image),
+        None => images[image.0].imported.is_some(),
+    };
+
+    let mut scheduled_passes = Vec::with_capacity(passes.len());
+    for (pass_index, pass) in passes.iter().enumerate() {
+        let mut scheduled = ScheduledPass::new();
+
+        if !pass.subpasses.is_empty() {
+            // collect the attachments in order of first appearance, together with their use in every subpass
+            let mut attachments: Vec<ImageId> = Vec::new();
+            let mut uses: Vec<Vec<Option<ImageUse>>> = Vec::new();
+            for (subpass_index, subpass) in pass.subpasses.iter().enumerate() {
+                let mut add_use = |image: ImageId, access: ImageAccess| {
+                    let attachment = match attachments.iter().position(|a| *a == image) {
+                        Some(attachment) => attachment,
+                        None => {
+                            attachments.push(image);
+                            uses.push(vec![None; pass.subpasses.len()]);
+                            attachments.len() - 1
+                        }
+                    };
+                    let new_use = ImageUse::new(access, images[image.0].format);
+                    let slot = &mut uses[attachment][subpass_index];
+                    *slot = Some(match slot {
+                        Some(existing) => existing.merge(new_use),
+                        None => new_use,
+                    });
+                };
+
+                if let Some((image, access)) = subpass.depth_stencil {
+                    add_use(image, access);
+                }
+                for image in &subpass.colors {
+                    add_use(*image, ImageAccess::ColorAttachment);
+                }
+                for image in &subpass.inputs {
+                    add_use(*image, ImageAccess::InputAttachment);
+                }
+            }
+
+            let mut attachment_keys = Vec::with_capacity(attachments.len());
+            for (attachment, image) in attachments.iter().enumerate() {
+                let first_use = uses[attachment].iter().flatten().next().copied().unwrap();
+                let last_use = uses[attachment].iter().flatten().last().copied().unwrap();
+                let format = images[image.0].format;
+                let state = &mut states[image.0];
+
+                let load_op = if pass.clear_values.iter().any(|(id, _)| id == image) {
+                    vk::AttachmentLoadOp::CLEAR
+                } else if pass.discard.contains(image) || !state.written {
+                    vk::AttachmentLoadOp::DONT_CARE
+                } else {
+                    vk::AttachmentLoadOp::LOAD
+                };
+                let store_op = if needs_store(*image, pass_index) {
+                    vk::AttachmentStoreOp::STORE
+                } else {
+                    vk::AttachmentStoreOp::DONT_CARE
+                };
+
+                scheduled.transition(
+                    *image,
+                    state,
+                    first_use,
+                    load_op != vk::AttachmentLoadOp::LOAD,
+                );
+
+                attachment_keys.push(AttachmentKey {
+                    format,
+                    load_op,
+                    store_op,
+                    stencil_load_op: if has_stencil(format) {
+                        load_op
+                    } else {
+                        vk::AttachmentLoadOp::DONT_CARE
+                    },
+                    stencil_store_op: if has_stencil(format) {
+                        store_op
+                    } else {
+                        vk::AttachmentStoreOp::DONT_CARE
+                    },
+                    initial_layout: first_use.layout,
+                    final_layout: last_use.layout,
+                });
+
+                state.last_use = Some(last_use);
+                state.written |= uses[attachment].iter().flatten().any(|u| u.write);
+            }
+
+            let reference = |image: &ImageId, subpass_index: usize| {
+                let attachment = attachments.iter().position(|a| a == image).unwrap();
+                (
+                    attachment as u32,
+                    uses[attachment][subpass_index].unwrap().layout,
+                )
+            };
+            let subpass_keys = pass
+                .subpasses
+                .iter()
+                .enumerate()
+                .map(|(subpass_index, subpass)| SubpassKey {
+                    colors: subpass
+                        .colors
+                        .iter()
+                        .map(|image| reference(image, subpass_index))
+                        .collect(),
+                    depth_stencil: subpass
+                        .depth_stencil
+                        .map(|(image, _)| reference(&image, subpass_index)),
+                    inputs: subpass
+                        .inputs
+                        .iter()
+                        .map(|image| reference(image, subpass_index))
+                        .collect(),
+                })
+                .collect();
+
+            // every subpass waits for the latest previous subpass that used the same attachment
+            let mut dependencies: Vec<DependencyKey> = Vec::new();
+            for attachment_uses in &uses {
+                for dst in 1..attachment_uses.len() {
+                    let dst_use = match attachment_uses[dst] {
+                        Some(dst_use) => dst_use,
+                        None => continue,
+                    };
+                    let (src, src_use) = match (0..dst)
+                        .rev()
+                        .find_map(|src| attachment_uses[src].map(|u| (src, u)))
+                    {
+                        Some(found) => found,
+                        None => continue,
+                    };
+
+                    let dependency = match dependencies
+                        .iter_mut()
+                        .find(|d| d.src_subpass == src as u32 && d.dst_subpass == dst as u32)
+                    {
+                        Some(dependency) => dependency,
+                        None => {
+                            dependencies.push(DependencyKey {
+                                src_subpass: src as u32,
+                                dst_subpass: dst as u32,
+                                src_stages: vk::PipelineStageFlags::empty(),
+                                dst_stages: vk::PipelineStageFlags::empty(),
+                                src_access: vk::AccessFlags::empty(),
+                                dst_access: vk::AccessFlags::empty(),
+                            });
+                            dependencies.last_mut().unwrap()
+                        }
+                    };
+                    dependency.src_stages |= src_use.stages;
+                    dependency.dst_stages |= dst_use.stages;
+                    dependency.src_access |= src_use.access;
+                    dependency.dst_access |= dst_use.access;
+                }
+            }
+
+            scheduled.renderpass = Some(ScheduledRenderpass {
+                key: RenderpassKey {
+                    attachments: attachment_keys,
+                    subpasses: subpass_keys,
+                    dependencies,
+                },
+                images: attachments,
+            });
+        }
+
+        for (image, access) in &pass.accesses {
+            let next = ImageUse::new(*access, images[image.0].format);
+            let state = &mut states[image.0];
+            scheduled.transition(*image, state, next, pass.overwrites(*image));
+            state.last_use = Some(next);
+            state.written |= next.write;
+        }
+
+        scheduled_passes.push(scheduled);
+    }
+
+    let mut final_transitions = ScheduledPass::new();
+    for (index, image) in images.iter().enumerate() {
+        if let Some(imported) = &image.imported {
+            final_transitions.transition(
+                ImageId(index),
+                &states[index],
+                ImageUse::new(imported.final_access, image.format),
+                false,
+            );
+        }
+    }
+
+    (scheduled_passes, final_transitions)
+}
+
+/// An image owned by the graph.
+struct TransientImage {
+    format: vk::Format,
+    extent: vk::Extent2D,
+    usage: vk::ImageUsageFlags,
+    image: vk::Image,
+    alloc: Allocation,
+    view: vk::ImageView,
+    depth_view: vk::ImageView,
+}
+
+/// The Vulkan objects backing a declared image in the current frame.
+#[derive(Debug, Copy, Clone)]
+struct ResolvedImage {
+    image: vk::Image,
+    view: vk::ImageView,
+    depth_view: vk::ImageView,
+    extent: vk::Extent2D,
+}
+
+struct CompiledRenderpass {
+    renderpass: vk::RenderPass,
+    framebuffer: vk::Framebuffer,
+    extent: vk::Extent2D,
+    clear_values: Vec<vk::ClearValue>,
+}
+
+struct CompiledPass {
+    src_stages: vk::PipelineStageFlags,
+    dst_stages: vk::PipelineStageFlags,
+    barriers: Vec<vk::ImageMemoryBarrier>,
+    renderpass: Option<CompiledRenderpass>,
+}
+
+/// Schedules and records the passes of a frame.
+///
+/// `P` is the type used to identify passes, usually an enum listing every kind of pass.
+///
+/// # Usage
+/// 1. [`reset`](Self::reset) the graph at the beginning of a frame
+/// 2. declare images with [`create_image`](Self::create_image) and [`import_image`](Self::import_image)
+/// 3. add passes in execution order with [`add_pass`](Self::add_pass)
+/// 4. [`compile`](Self::compile) the graph
+/// 5. record every pass between [`begin_pass`](Self::begin_pass) and [`end_pass`](Self::end_pass), then call [`end`](Self::end)
+pub(crate) struct RenderGraph<P> {
+    device: Rc<ash::Device>,
+    output_extent: vk::Extent2D,
+
+    images: Vec<ImageDecl>,
+    passes: Vec<PassDesc<P>>,
+
+    resolved_images: Vec<ResolvedImage>,
+    compiled_passes: Vec<CompiledPass>,
+    final_transitions: Option<CompiledPass>,
+
+    transient_images: HashMap<String, TransientImage>,
+    renderpasses: HashMap<RenderpassKey, vk::RenderPass>,
+    framebuffers: HashMap<(vk::RenderPass, Vec<vk::ImageView>, u32, u32), vk::Framebuffer>,
+}
+
+impl<P: Copy> RenderGraph<P> {
+    pub(crate) fn new(device: Rc<ash::Device>) -> Self {
+        Self {
+            device,
+            output_extent: vk::Extent2D::default(),
+            images: Vec::new(),
+            passes: Vec::new(),
+            resolved_images: Vec::new(),
+            compiled_passes: Vec::new(),
+            final_transitions: None,
+            transient_images: HashMap::new(),
+            renderpasses: HashMap::new(),
+            framebuffers: HashMap::new(),
+        }
+    }
+
+    /// Removes all declared images and passes.
+    pub(crate) fn reset(&mut self) {
+        self.images.clear();
+        self.passes.clear();
+        self.resolved_images.clear();
+        self.compiled_passes.clear();
+        self.final_transitions = None;
+    }
+
+    /// Sets the extent of all images declared with [`ImageSize::Output`].
+    pub(crate) fn set_output_extent(&mut self, extent: vk::Extent2D) {
+        self.output_extent = extent;
+    }
+
+    /// Declares an image that is owned by the graph.
+    ///
+    /// Images are identified by `name` across frames, so the same name always refers to the same image.
+    /// The contents of the image are undefined at the beginning of every frame.
+    pub(crate) fn create_image(
+        &mut self,
+        name: impl Into<String>,
+        format: vk::Format,
+        size: ImageSize,
+    ) -> ImageId {
+        self.images.push(ImageDecl {
+            name: name.into(),
+            format,
+            size,
+            imported: None,
+        });
+        ImageId(self.images.len() - 1)
+    }
+
+    /// Declares an image that is owned by someone else, e.g. a swapchain image.
+    ///
+    /// The image is expected to be ready for `initial_access` at the beginning of the frame.
+    /// Without an initial access, its contents are discarded at the first use.
+    /// At the end of the frame, the image is transitioned for `final_access`.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn import_image(
+        &mut self,
+        name: impl Into<String>,
+        image: vk::Image,
+        view: vk::ImageView,
+        format: vk::Format,
+        extent: vk::Extent2D,
+        initial_access: Option<ImageAccess>,
+        final_access: ImageAccess,
+    ) -> ImageId {
+        self.images.push(ImageDecl {
+            name: name.into(),
+            format,
+            size: ImageSize::Absolute(extent.width, extent.height),
+            imported: Some(ImportedImage {
+                image,
+                view,
+                extent,
+                initial_access,
+                final_access,
+            }),
+        });
+        ImageId(self.images.len() - 1)
+    }
+
+    /// Adds a pass after all previously added passes.
+    pub(crate) fn add_pass(&mut self, pass: PassDesc<P>) {
+        self.passes.push(pass);
+    }
+
+    /// Allocates all declared images and creates the barriers, renderpasses and framebuffers of all passes.
+    pub(crate) fn compile(&mut self, allocator: &Allocator) -> GraphicsResult<()> {
+        profile_function!();
+
+        self.prepare_images(allocator)?;
+
+        let (scheduled_passes, final_transitions) = schedule(&self.images, &self.passes);
+
+        let mut compiled_passes = Vec::with_capacity(scheduled_passes.len());
+        for (pass_index, scheduled) in scheduled_passes.iter().enumerate() {
+            let renderpass = match &scheduled.renderpass {
+                Some(scheduled_renderpass) => {
+                    Some(self.compile_renderpass(scheduled_renderpass, pass_index)?)
+                }
+                None => None,
+            };
+            compiled_passes.push(CompiledPass {
+                src_stages: scheduled.src_stages,
+                dst_stages: scheduled.dst_stages,
+                barriers: self.compile_barriers(&scheduled.barriers),
+                renderpass,
+            });
+        }
+
+        self.final_transitions = Some(CompiledPass {
+            src_stages: final_transitions.src_stages,
+            dst_stages: final_transitions.dst_stages,
+            barriers: self.compile_barriers(&final_transitions.barriers),
+            renderpass: None,
+        });
+        self.compiled_passes = compiled_passes;
+
+        Ok(())
+    }
+
+    /// Creates or recreates all transient images whose description changed and resolves all declared images.
+    fn prepare_images(&mut self, allocator: &Allocator) -> GraphicsResult<()> {
+        let mut usages = vec![vk::ImageUsageFlags::empty(); self.images.len()];
+        for pass in &self.passes {
+            for subpass in &pass.subpasses {
+                for image in &subpass.colors {
+                    usages[image.0] |= ImageAccess::ColorAttachment.usage();
+                }
+                if let Some((image, access)) = subpass.depth_stencil {
+                    usages[image.0] |= access.usage();
+                }
+                for image in &subpass.inputs {
+                    usages[image.0] |= ImageAccess::InputAttachment.usage();
+                }
+            }
+            for (image, access) in &pass.accesses {
+                usages[image.0] |= access.usage();
+            }
+        }
+
+        // find images that do not match their description anymore, e.g. after a resize
+        let mut outdated = Vec::new();
+        for (decl, usage) in self.images.iter().zip(usages.iter_mut()) {
+            if decl.imported.is_some() {
+                continue;
+            }
+            let extent = self.resolve_size(decl.size);
+            if let Some(existing) = self.transient_images.get(&decl.name) {
+                if existing.format == decl.format && existing.extent == extent {
+                    if existing.usage.contains(*usage) {
+                        continue;
+                    }
+                    // keep previous usages to avoid recreating images that are used differently every other frame
+                    *usage |= existing.usage;
+                }
+                outdated.push(decl.name.clone());
+            }
+        }
+
+        if !outdated.is_empty() {
+            // the outdated images might still be used by frames in flight
+            unsafe {
+                self.device.device_wait_idle()?;
+            }
+            for name in outdated {
+                let image = self.transient_images.remove(&name).unwrap();
+                self.destroy_transient_image(image, allocator);
+            }
+            // framebuffers might reference destroyed views
+            for (_, framebuffer) in self.framebuffers.drain() {
+                unsafe {
+                    self.device.destroy_framebuffer(framebuffer, None);
+                }
+            }
+        }
+
+        let mut resolved_images = Vec::with_capacity(self.images.len());
+        for (decl, usage) in self.images.iter().zip(usages.iter()) {
+            let resolved = match &decl.imported {
+                Some(imported) => ResolvedImage {
+                    image: imported.image,
+                    view: imported.view,
+                    depth_view: imported.view,
+                    extent: imported.extent,
+                },
+                None => {
+                    if !self.transient_images.contains_key(&decl.name) {
+                        let extent = self.resolve_size(decl.size);
+                        let image =
+                            self.create_transient_image(decl.format, extent, *usage, allocator)?;
+                        self.transient_images.insert(decl.name.clone(), image);
+                    }
+                    let image = &self.transient_images[&decl.name];
+                    ResolvedImage {
+                        image: image.image,
+                        view: image.view,
+                        depth_view: image.depth_view,
+                        extent: image.extent,
+                    }
+                }
+            };
+            resolved_images.push(resolved);
+        }
+        self.resolved_images = resolved_images;
+
+        Ok(())
+    }
+
+    fn resolve_size(&self, size: ImageSize) -> vk::Extent2D {
+        match size {
+            ImageSize::Output => self.output_extent,
+            ImageSize::Absolute(width, height) => vk::Extent2D { width, height },
+        }
+    }
+
+    fn create_transient_image(
+        &self,
+        format: vk::Format,
+        extent: vk::Extent2D,
+        usage: vk::ImageUsageFlags,
+        allocator: &Allocator,
+    ) -> GraphicsResult<TransientImage> {
+        let (image, alloc) = allocator.create_image(
+            extent.width,
+            extent.height,
+            format,
+            usage,
+            MemoryLocation::GpuOnly,
+        )?;
+
+        let create_view = |aspect_mask: vk::ImageAspectFlags| {
+            let subresource_range = vk::ImageSubresourceRange::builder()
+                .aspect_mask(aspect_mask)
+                .base_mip_level(0)
+                .level_count(1)
+                .base_array_layer(0)
+                .layer_count(1);
+            let imageview_create_info = vk::ImageViewCreateInfo::builder()
+                .image(image)
+                .view_type(vk::ImageViewType::TYPE_2D)
+                .format(format)
+                .subresource_range(*subresource_range);
+            unsafe { self.device.create_image_view(&imageview_create_info, None) }
+        };
+
+        let view = create_view(aspect_mask(format))?;
+        // input attachments and samplers can only read the depth aspect
+        let depth_view = if has_stencil(format) {
+            create_view(vk::ImageAspectFlags::DEPTH)?
+        } else {
+            view
+        };
+
+        Ok(TransientImage {
+            format,
+            extent,
+            usage,
+            image,
+            alloc,
+            view,
+            depth_view,
+        })
+    }
+
+    fn destroy_transient_image(&self, image: TransientImage, allocator: &Allocator) {
+        unsafe {
+            if image.depth_view != image.view {
+                self.device.destroy_image_view(image.depth_view, None);
+            }
+            self.device.destroy_image_view(image.view, None);
+        }
+        allocator.destroy_image(image.image, image.alloc);
+    }
+
+    fn compile_barriers(&self, barriers: &[ScheduledBarrier]) -> Vec<vk::ImageMemoryBarrier> {
+        barriers
+            .iter()
+            .map(|barrier| {
+                vk::ImageMemoryBarrier::builder()
+                    .image(self.resolved_images[barrier.image.0].image)
+                    .old_layout(barrier.old_layout)
+                    .new_layout(barrier.new_layout)
+                    .src_access_mask(barrier.src_access)
+                    .dst_access_mask(barrier.dst_access)
+                    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .subresource_range(vk::ImageSubresourceRange {
+                        aspect_mask: aspect_mask(self.images[barrier.image.0].format),
+                        base_mip_level: 0,
+                        level_count: 1,
+                        base_array_layer: 0,
+                        layer_count: 1,
+                    })
+                    .build()
+            })
+            .collect()
+    }
+
+    fn compile_renderpass(
+        &mut self,
+        scheduled: &ScheduledRenderpass,
+        pass_index: usize,
+    ) -> GraphicsResult<CompiledRenderpass> {
+        let renderpass = match self.renderpasses.get(&scheduled.key) {
+            Some(renderpass) => *renderpass,
+            None => {
+                let renderpass = self.create_renderpass(&scheduled.key)?;
+                self.renderpasses.insert(scheduled.key.clone(), renderpass);
+                renderpass
+            }
+        };
+
+        let extent = self.resolved_images[scheduled.images[0].0].extent;
+        let views: Vec<vk::ImageView> = scheduled
+            .images
+            .iter()
+            .map(|image| self.resolved_images[image.0].view)
+            .collect();
+        let framebuffer_key = (renderpass, views, extent.width, extent.height);
+        let framebuffer = match self.framebuffers.get(&framebuffer_key) {
+            Some(framebuffer) => *framebuffer,
+            None => {
+                let fb_info = vk::FramebufferCreateInfo::builder()
+                    .render_pass(renderpass)
+                    .attachments(&framebuffer_key.1)
+                    .width(extent.width)
+                    .height(extent.height)
+                    .layers(1)
+                    .build();
+                let framebuffer = unsafe { self.device.create_framebuffer(&fb_info, None)? };
+                self.framebuffers.insert(framebuffer_key, framebuffer);
+                framebuffer
+            }
+        };
+
+        let clear_values = scheduled
+            .images
+            .iter()
+            .map(|image| {
+                self.passes[pass_index]
+                    .clear_values
+                    .iter()
+                    .find(|(id, _)| id == image)
+                    .map(|(_, value)| *value)
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        Ok(CompiledRenderpass {
+            renderpass,
+            framebuffer,
+            extent,
+            clear_values,
+        })
+    }
+
+    fn create_renderpass(&self, key: &RenderpassKey) -> Result<vk::RenderPass, vk::Result> {
+        let attachments: Vec<vk::AttachmentDescription> = key
+            .attachments
+            .iter()
+            .map(|a| {
+                vk::AttachmentDescription::builder()
+                    .format(a.format)
+                    .samples(vk::SampleCountFlags::TYPE_1)
+                    .load_op(a.load_op)
+                    .store_op(a.store_op)
+                    .stencil_load_op(a.stencil_load_op)
+                    .stencil_store_op(a.stencil_store_op)
+                    .initial_layout(a.initial_layout)
+                    .final_layout(a.final_layout)
+                    .build()
+            })
+            .collect();
+
+        let to_references = |refs: &[(u32, vk::ImageLayout)]| -> Vec<vk::AttachmentReference> {
+            refs.iter()
+                .map(|(attachment, layout)| vk::AttachmentReference {
+                    attachment: *attachment,
+                    layout: *layout,
+                })
+                .collect()
+        };
+        let color_references: Vec<_> = key
+            .subpasses
+            .iter()
+            .map(|s| to_references(&s.colors))
+            .collect();
+        let input_references: Vec<_> = key
+            .subpasses
+            .iter()
+            .map(|s| to_references(&s.inputs))
+            .collect();
+        let depth_references: Vec<_> = key
+            .subpasses
+            .iter()
+            .map(|s| {
+                s.depth_stencil
+                    .map(|(attachment, layout)| vk::AttachmentReference { attachment, layout })
+            })
+            .collect();
+
+        let subpasses: Vec<vk::SubpassDescription> = (0..key.subpasses.len())
+            .map(|i| {
+                let mut subpass = vk::SubpassDescription::builder()
+                    .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+                    .color_attachments(&color_references[i])
+                    .input_attachments(&input_references[i]);
+                if let Some(depth_reference) = &depth_references[i] {
+                    subpass = subpass.depth_stencil_attachment(depth_reference);
+                }
+                subpass.build()
+            })
+            .collect();
+
+        let dependencies: Vec<vk::SubpassDependency> = key
+            .dependencies
+            .iter()
+            .map(|d| {
+                vk::SubpassDependency::builder()
+                    .src_subpass(d.src_subpass)
+                    .dst_subpass(d.dst_subpass)
+                    .src_stage_mask(d.src_stages)
+                    .dst_stage_mask(d.dst_stages)
+                    .src_access_mask(d.src_access)
+                    .dst_access_mask(d.dst_access)
+                    .dependency_flags(vk::DependencyFlags::BY_REGION)
+                    .build()
+            })
+            .collect();
+
+        let renderpass_info = vk::RenderPassCreateInfo::builder()
+            .attachments(&attachments)
+            .subpasses(&subpasses)
+            .dependencies(&dependencies);
+        unsafe { self.device.create_render_pass(&renderpass_info, None) }
+    }
+
+    /// Returns the number of passes of the compiled graph.
+    pub(crate) fn pass_count(&self) -> usize {
+        self.compiled_passes.len()
+    }
+
+    /// Records the barriers of pass `index` and begins its renderpass, if it has one.
+    ///
+    /// Returns the tag of the pass, which identifies what has to be recorded.
+    pub(crate) fn begin_pass(&self, commandbuffer: vk::CommandBuffer, index: usize) -> P {
+        let compiled = &self.compiled_passes[index];
+        self.record_barriers(commandbuffer, compiled);
+
+        if let Some(renderpass) = &compiled.renderpass {
+            let info = vk::RenderPassBeginInfo::builder()
+                .render_pass(renderpass.renderpass)
+                .framebuffer(renderpass.framebuffer)
+                .render_area(vk::Rect2D {
+                    offset: vk::Offset2D { x: 0, y: 0 },
+                    extent: renderpass.extent,
+                })
+                .clear_values(&renderpass.clear_values);
+            unsafe {
+                self.device.cmd_begin_render_pass(
+                    commandbuffer,
+                    &info,
+                    vk::SubpassContents::INLINE,
+                );
+            }
+        }
+
+        self.passes[index].tag
+    }
+
+    /// Ends the renderpass of pass `index`, if it has one.
+    pub(crate) fn end_pass(&self, commandbuffer: vk::CommandBuffer, index: usize) {
+        if self.compiled_passes[index].renderpass.is_some() {
+            unsafe {
+                self.device.cmd_end_render_pass(commandbuffer);
+            }
+        }
+    }
+
+    /// Records the final transitions of all imported images.
+    pub(crate) fn end(&self, commandbuffer: vk::CommandBuffer) {
+        if let Some(final_transitions) = &self.final_transitions {
+            self.record_barriers(commandbuffer, final_transitions);
+        }
+    }
+
+    fn record_barriers(&self, commandbuffer: vk::CommandBuffer, compiled: &CompiledPass) {
+        if compiled.barriers.is_empty() {
+            return;
+        }
+
+        unsafe {
+            self.device.cmd_pipeline_barrier(
+                commandbuffer,
+                compiled.src_stages,
+                compiled.dst_stages,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &compiled.barriers,
+            );
+        }
+    }
+
+    /// Returns the renderpass of the first compiled pass whose tag matches `predicate`.
+    ///
+    /// Renderpasses are never destroyed before the graph itself, so the handle can be used to create pipelines.
+    pub(crate) fn get_renderpass(&self, predicate: impl Fn(&P) -> bool) -> Option<vk::RenderPass> {
+        self.passes
+            .iter()
+            .zip(self.compiled_passes.iter())
+            .find(|(pass, _)| predicate(&pass.tag))
+            .and_then(|(_, compiled)| compiled.renderpass.as_ref())
+            .map(|renderpass| renderpass.renderpass)
+    }
+
+    pub(crate) fn get_image(&self, image: ImageId) -> vk::Image {
+        self.resolved_images[image.0].image
+    }
+
+    pub(crate) fn get_view(&self, image: ImageId) -> vk::ImageView {
+        self.resolved_images[image.0].view
+    }
+
+    /// Returns a view of only the depth aspect of a depth-stencil image, as required by input attachments.
+    pub(crate) fn get_depth_view(&self, image: ImageId) -> vk::ImageView {
+        self.resolved_images[image.0].depth_view
+    }
+
+    pub(crate) fn get_extent(&self, image: ImageId) -> vk::Extent2D {
+        self.resolved_images[image.0].extent
+    }
+
+    /// Destroys all images, renderpasses and framebuffers owned by the graph.
+    pub(crate) fn destroy(&mut self, allocator: &Allocator) {
+        self.reset();
+
+        unsafe {
+            for (_, framebuffer) in self.framebuffers.drain() {
+                self.device.destroy_framebuffer(framebuffer, None);
+            }
+            for (_, renderpass) in self.renderpasses.drain() {
+                self.device.destroy_render_pass(renderpass, None);
+            }
+        }
+        let images: Vec<TransientImage> = self.transient_images.drain().map(|(_, i)| i).collect();
+        for image in images {
+            self.destroy_transient_image(image, allocator);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ash::vk;
+
+    use super::{
+        schedule, ImageAccess, ImageDecl, ImageId, ImageSize, ImportedImage, PassDesc, SubpassDesc,
+    };
+
+    fn decl(name: &str, format: vk::Format) -> ImageDecl {
+        ImageDecl {
+            name: name.to_owned(),
+            format,
+            size: ImageSize::Output,
+            imported: None,
+        }
+    }
+
+    #[test]
+    fn test_schedule_load_store_ops() {
+        let images = vec![
+            decl("color", vk::Format::R16G16B16A16_SFLOAT),
+            decl("depth", vk::Format::D24_UNORM_S8_UINT),
+            decl("post", vk::Format::R16G16B16A16_SFLOAT),
+        ];
+        let (color, depth, post) = (ImageId(0), ImageId(1), ImageId(2));
+
+        let scene = PassDesc {
+            subpasses: vec![SubpassDesc {
+                colors: vec![color],
+                depth_stencil: Some((depth, ImageAccess::DepthStencilAttachment)),
+                inputs: vec![],
+            }],
+            clear_values: vec![(depth, vk::ClearValue::default())],
+            ..PassDesc::new(0)
+        };
+        let post_process = PassDesc {
+            subpasses: vec![SubpassDesc {
+                colors: vec![post],
+                ..Default::default()
+            }],
+            accesses: vec![(color, ImageAccess::Sampled)],
+            discard: vec![post],
+            ..PassDesc::new(1)
+        };
+        let overlay = PassDesc {
+            subpasses: vec![SubpassDesc {
+                colors: vec![post],
+                ..Default::default()
+            }],
+            ..PassDesc::new(2)
+        };
+
+        let (passes, _) = schedule(&images, &[scene, post_process, overlay]);
+
+        // color is sampled later and has to be stored, depth is never used again
+        let scene_pass = passes[0].renderpass.as_ref().unwrap();
+        assert_eq!(scene_pass.images, vec![depth, color]);
+        let depth_attachment = &scene_pass.key.attachments[0];
+        assert_eq!(depth_attachment.load_op, vk::AttachmentLoadOp::CLEAR);
+        assert_eq!(
+            depth_attachment.stencil_load_op,
+            vk::AttachmentLoadOp::CLEAR
+        );
+        assert_eq!(depth_attachment.store_op, vk::AttachmentStoreOp::DONT_CARE);
+        let color_attachment = &scene_pass.key.attachments[1];
+        assert_eq!(color_attachment.load_op, vk::AttachmentLoadOp::DONT_CARE);
+        assert_eq!(color_attachment.store_op, vk::AttachmentStoreOp::STORE);
+
+        // sampling color requires a transition out of the attachment layout
+        let barrier = passes[1]
+            .barriers
+            .iter()
+            .find(|b| b.image == color)
+            .unwrap();
+        assert_eq!(
+            barrier.old_layout,
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+        );
+        assert_eq!(
+            barrier.new_layout,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+        );
+
+        // the overlay draws on top of the post processing result
+        let post_attachment = &passes[1].renderpass.as_ref().unwrap().key.attachments[0];
+        assert_eq!(post_attachment.load_op, vk::AttachmentLoadOp::DONT_CARE);
+        assert_eq!(post_attachment.store_op, vk::AttachmentStoreOp::STORE);
+        let overlay_attachment = &passes[2].renderpass.as_ref().unwrap().key.attachments[0];
+        assert_eq!(overlay_attachment.load_op, vk::AttachmentLoadOp::LOAD);
+    }
+
+    #[test]
+    fn test_schedule_imported_initial_access() {
+        let texture = ImageDecl {
+            imported: Some(ImportedImage {
+                image: vk::Image::null(),
+                view: vk::ImageView::null(),
+                extent: vk::Extent2D::default(),
+                initial_access: Some(ImageAccess::Sampled),
+                final_access: ImageAccess::Sampled,
+            }),
+            ..decl("texture", vk::Format::R8G8B8A8_SRGB)
+        };
+        let texture_id = ImageId(0);
+
+        let sample = PassDesc {
+            accesses: vec![(texture_id, ImageAccess::Sampled)],
+            ..PassDesc::new(0)
+        };
+        let draw = PassDesc {
+            subpasses: vec![SubpassDesc {
+                colors: vec![texture_id],
+                ..Default::default()
+            }],
+            ..PassDesc::new(1)
+        };
+
+        let (passes, final_transitions) = schedule(&[texture], &[sample, draw]);
+
+        // the texture is already in the sampled layout and keeps its contents
+        assert!(passes[0].barriers.is_empty());
+        let barrier = passes[1].barriers[0];
+        assert_eq!(
+            barrier.old_layout,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+        );
+        let attachment = &passes[1].renderpass.as_ref().unwrap().key.attachments[0];
+        assert_eq!(attachment.load_op, vk::AttachmentLoadOp::LOAD);
+        assert_eq!(attachment.store_op, vk::AttachmentStoreOp::STORE);
+
+        assert_eq!(
+            final_transitions.barriers[0].new_layout,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+        );
+    }
+
+    #[test]
+    fn test_schedule_subpass_dependencies() {
+        let images = vec![
+            decl("gbuffer", vk::Format::R16G16B16A16_SFLOAT),
+            decl("depth", vk::Format::D24_UNORM_S8_UINT),
+            decl("resolve", vk::Format::R16G16B16A16_SFLOAT),
+        ];
+        let (gbuffer, depth, resolve) = (ImageId(0), ImageId(1), ImageId(2));
+
+        let deferred = PassDesc {
+            subpasses: vec![
+                SubpassDesc {
+                    colors: vec![gbuffer],
+                    depth_stencil: Some((depth, ImageAccess::DepthStencilAttachment)),
+                    inputs: vec![],
+                },
+                SubpassDesc {
+                    colors: vec![resolve],
+                    depth_stencil: Some((depth, ImageAccess::DepthStencilReadOnly)),
+                    inputs: vec![gbuffer, depth],
+                },
+            ],
+            ..PassDesc::new(())
+        };
+
+        let (passes, _) = schedule(&images, &[deferred]);
+        let key = &passes[0].renderpass.as_ref().unwrap().key;
+
+        // depth is used as read only depth attachment and input attachment at the same time
+        let depth_reference = key.subpasses[1].depth_stencil.unwrap();
+        assert_eq!(
+            depth_reference.1,
+            vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL
+        );
+        assert!(key.subpasses[1].inputs.contains(&depth_reference));
+
+        // a single merged dependency from the gpass to the lighting pass
+        assert_eq!(key.dependencies.len(), 1);
+        let dependency = &key.dependencies[0];
+        assert_eq!((dependency.src_subpass, dependency.dst_subpass), (0, 1));
+        assert!(dependency
+            .src_access
+            .contains(vk::AccessFlags::COLOR_ATTACHMENT_WRITE));
+        assert!(dependency
+            .src_access
+            .contains(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE));
+        assert!(dependency
+            .dst_access
+            .contains(vk::AccessFlags::INPUT_ATTACHMENT_READ));
+    }
+}
@@ -0,0 +1,118 @@
+//! Ordering of renderables so that consecutive draws share as much GPU state as possible.
+//!
+//! Every renderable gets a 64-bit [`SortKey`] which packs (from most to least significant bits)
+//! the pipeline, material, mesh and a view depth bucket. Sorting by this key groups all draws
+//! using the same pipeline, then the same material, then the same mesh, and within those groups
+//! orders draws front to back.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+const PIPELINE_BITS: u32 = 12;
+const MATERIAL_BITS: u32 = 20;
+const MESH_BITS: u32 = 20;
+const DEPTH_BITS: u32 = 12;
+
+const DEPTH_SHIFT: u32 = 0;
+const MESH_SHIFT: u32 = DEPTH_SHIFT + DEPTH_BITS;
+const MATERIAL_SHIFT: u32 = MESH_SHIFT + MESH_BITS;
+const PIPELINE_SHIFT: u32 = MATERIAL_SHIFT + MATERIAL_BITS;
+
+/// Doublings of the distance to the near plane that are covered by the depth buckets,
+/// e.g. up to about 1600 km for a near plane at 0.1.
+const DEPTH_OCTAVES: f32 = 24.0;
+
+const fn mask(bits: u32) -> u64 {
+    (1 << bits) - 1
+}
+
+/// Hands out ids used to build [`SortKeys`](SortKey).
+///
+/// Ids only need to be unique among objects that are alive at the same time, so wrapping around
+/// after a very large number of allocations only costs some batching efficiency, never correctness.
+pub(crate) struct SortIdCounter(AtomicU32);
+
+impl SortIdCounter {
+    pub(crate) const fn new() -> Self {
+        Self(AtomicU32::new(0))
+    }
+
+    pub(crate) fn next(&self) -> u32 {
+        self.0.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+/// A 64-bit key describing the position of a renderable in the render order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SortKey(pub u64);
+
+impl SortKey {
+    /// Builds a new key.
+    ///
+    /// # Parameters
+    /// - `pipeline_id`, `material_id`, `mesh_id`: ids of the resources used by the renderable, only the lower bits are used
+    /// - `depth`: view depth normalized to `0.0..=1.0`, see [`normalized_depth`], values outside of this range are clamped
+    pub fn new(pipeline_id: u32, material_id: u32, mesh_id: u32, depth: f32) -> Self {
+        let depth_bucket = (depth.clamp(0.0, 1.0) * mask(DEPTH_BITS) as f32) as u64;
+
+        Self(
+            (pipeline_id as u64 & mask(PIPELINE_BITS)) << PIPELINE_SHIFT
+                | (material_id as u64 & mask(MATERIAL_BITS)) << MATERIAL_SHIFT
+                | (mesh_id as u64 & mask(MESH_BITS)) << MESH_SHIFT
+                | depth_bucket << DEPTH_SHIFT,
+        )
+    }
+}
+
+/// Normalizes a view space distance for [`SortKey::new`].
+///
+/// The distance is scaled logarithmically relative to the near plane, so that the depth buckets don't depend on
+/// the far plane, which [`DepthMode::ReversedInfinite`](super::DepthMode::ReversedInfinite) ignores,
+/// and keep the same relative precision at any distance.
+pub fn normalized_depth(distance: f32, near: f32) -> f32 {
+    (distance / near).max(1.0).log2() / DEPTH_OCTAVES
+}
+
+/// Sorts the given renderables by their [`SortKey`].
+///
+/// The sort is unstable, renderables with identical keys may end up in any order.
+pub fn sort<T>(renderables: &mut [(SortKey, T)]) {
+    profile_function!();
+
+    renderables.sort_unstable_by_key(|(key, _)| *key);
+}
+
+#[cfg(test)]
+mod test {
+    use super::{normalized_depth, SortKey};
+
+    #[test]
+    fn test_sort_key_order() {
+        // the pipeline takes precedence over everything else
+        assert!(SortKey::new(0, 5, 5, 1.0) < SortKey::new(1, 0, 0, 0.0));
+        // then the material
+        assert!(SortKey::new(1, 0, 5, 1.0) < SortKey::new(1, 1, 0, 0.0));
+        // then the mesh
+        assert!(SortKey::new(1, 1, 0, 1.0) < SortKey::new(1, 1, 1, 0.0));
+        // and finally front to back
+        assert!(SortKey::new(1, 1, 1, 0.25) < SortKey::new(1, 1, 1, 0.75));
+
+        // depth is clamped and ids are truncated instead of leaking into the neighbouring bits
+        assert_eq!(SortKey::new(0, 0, 0, 2.0), SortKey::new(0, 0, 0, 1.0));
+        assert_eq!(SortKey::new(0, 0, 0, -1.0), SortKey::new(0, 0, 0, 0.0));
+        assert_eq!(SortKey::new(0, 1 << 20, 0, 0.0), SortKey::new(0, 0, 0, 0.0));
+    }
+
+    #[test]
+    fn test_normalized_depth() {
+        let key = |distance: f32| SortKey::new(0, 0, 0, normalized_depth(distance, 0.1));
+
+        // objects far beyond a typical far plane are still sorted front to back
+        assert!(key(1.0) < key(1.1));
+        assert!(key(1000.0) < key(1010.0));
+        assert!(key(100_000.0) < key(101_000.0));
+
+        // objects behind the near plane all go first
+        assert_eq!(normalized_depth(-5.0, 0.1), 0.0);
+        assert_eq!(normalized_depth(0.05, 0.1), 0.0);
+    }
+}
@@ -0,0 +1,147 @@
+use serde::{Deserialize, Serialize};
+
+/// Scale of the internal render resolution relative to the window, see [`VulkanManager::resolution`](super::VulkanManager::resolution).
+///
+/// Cameras rendering into the window are rendered at the scaled resolution and upscaled before the UI is drawn.
+/// Cameras with a render target always use the size of their target.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum ResolutionScale {
+    /// Always renders at `scale` times the window resolution.
+    Fixed { scale: f32 },
+    /// Adjusts the scale between `min_scale` and `max_scale` so that frames take about `target_frame_time` milliseconds.
+    Dynamic {
+        target_frame_time: f32,
+        min_scale: f32,
+        max_scale: f32,
+    },
+}
+
+impl Default for ResolutionScale {
+    fn default() -> Self {
+        Self::Fixed { scale: 1.0 }
+    }
+}
+
+/// Scales are rounded to multiples of this, every change recreates the render targets.
+const SCALE_STEP: f32 = 0.05;
+/// Largest change of the scale in a single update, to avoid oscillating between extremes.
+const MAX_SCALE_CHANGE: f32 = 0.1;
+/// Frame times within this fraction of the target do not change the scale.
+const TOLERANCE: f32 = 0.05;
+
+impl ResolutionScale {
+    /// Returns a copy with all values in their valid ranges, `max_scale` is raised to at least `min_scale`.
+    pub(crate) fn clamped(self) -> Self {
+        // written so that NaN is replaced as well
+        let at_least = |value: f32, min: f32| if value >= min { value } else { min };
+
+        match self {
+            ResolutionScale::Fixed { scale } => ResolutionScale::Fixed {
+                scale: at_least(scale, SCALE_STEP),
+            },
+            ResolutionScale::Dynamic {
+                target_frame_time,
+                min_scale,
+                max_scale,
+            } => {
+                let min_scale = at_least(min_scale, SCALE_STEP);
+                ResolutionScale::Dynamic {
+                    target_frame_time: at_least(target_frame_time, 1.0),
+                    min_scale,
+                    max_scale: at_least(max_scale, min_scale),
+                }
+            }
+        }
+    }
+
+    /// Returns the scale for the next frames, given the current `scale` and the frame times in milliseconds since the last update.
+    pub(crate) fn next_scale(&self, scale: f32, frame_times: &[f32]) -> f32 {
+        match self.clamped() {
+            ResolutionScale::Fixed { scale } => scale,
+            ResolutionScale::Dynamic {
+                target_frame_time,
+                min_scale,
+                max_scale,
+            } => {
+                if frame_times.is_empty() {
+                    return scale.max(min_scale).min(max_scale);
+                }
+                let frame_time = frame_times.iter().sum::<f32>() / frame_times.len() as f32;
+                if (frame_time - target_frame_time).abs() <= target_frame_time * TOLERANCE {
+                    return scale.max(min_scale).min(max_scale);
+                }
+
+                // the cost of a frame mostly depends on the number of pixels, which grows with the square of the scale
+                let ideal = scale * (target_frame_time / frame_time).sqrt();
+                let next = ideal.clamp(scale - MAX_SCALE_CHANGE, scale + MAX_SCALE_CHANGE);
+                ((next / SCALE_STEP).round() * SCALE_STEP)
+                    .max(min_scale)
+                    .min(max_scale)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ResolutionScale;
+
+    #[test]
+    fn test_dynamic_scale() {
+        let resolution = ResolutionScale::Dynamic {
+            target_frame_time: 16.0,
+            min_scale: 0.5,
+            max_scale: 1.0,
+        };
+
+        // on target, the scale stays the same
+        assert_eq!(resolution.next_scale(0.8, &[16.0, 16.4, 15.8]), 0.8);
+
+        // too slow, the scale goes down by at most one change per update
+        let slower = resolution.next_scale(1.0, &[64.0]);
+        assert!((slower - 0.9).abs() < 1e-5);
+        let slower = resolution.next_scale(1.0, &[18.0]);
+        assert!((slower - 0.95).abs() < 1e-5);
+
+        // fast frames increase the scale up to the maximum
+        assert_eq!(resolution.next_scale(0.95, &[8.0]), 1.0);
+        assert_eq!(resolution.next_scale(0.5, &[200.0]), 0.5);
+    }
+
+    #[test]
+    fn test_invalid_dynamic_scale() {
+        // reversed limits don't panic, the maximum is raised to the minimum
+        let reversed = ResolutionScale::Dynamic {
+            target_frame_time: 16.0,
+            min_scale: 1.0,
+            max_scale: 0.5,
+        };
+        assert_eq!(reversed.next_scale(0.8, &[]), 1.0);
+        assert_eq!(reversed.next_scale(0.8, &[64.0]), 1.0);
+        assert_eq!(
+            reversed.clamped(),
+            ResolutionScale::Dynamic {
+                target_frame_time: 16.0,
+                min_scale: 1.0,
+                max_scale: 1.0,
+            }
+        );
+
+        // a maximum below the smallest step
+        let tiny = ResolutionScale::Dynamic {
+            target_frame_time: 16.0,
+            min_scale: 0.0,
+            max_scale: 0.01,
+        };
+        assert!((tiny.next_scale(1.0, &[64.0]) - 0.05).abs() < 1e-5);
+
+        // NaN values
+        let nan = ResolutionScale::Dynamic {
+            target_frame_time: f32::NAN,
+            min_scale: f32::NAN,
+            max_scale: f32::NAN,
+        };
+        assert!(nan.next_scale(1.0, &[16.0]).is_finite());
+    }
+}
@@ -0,0 +1,5 @@
+//! Structs with the layout of shader blocks that are filled from Rust, generated from the compiled shaders by `build.rs`.
+//!
+//! Changing a block in a shader changes its struct, so code that fills it in the old layout fails to compile.
+
+include!(concat!(env!("OUT_DIR"), "/shader_structs.rs"));
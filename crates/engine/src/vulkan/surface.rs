@@ -67,15 +67,15 @@ impl SurfaceWrapper {
         }
     }
 
+    /// Returns `requested` if the surface supports it, FIFO otherwise.
     pub fn choose_present_mode(
         &self,
         physical_device: vk::PhysicalDevice,
+        requested: vk::PresentModeKHR,
     ) -> Result<vk::PresentModeKHR, vk::Result> {
         let present_modes = self.get_present_modes(physical_device)?;
-        Ok(if present_modes.contains(&vk::PresentModeKHR::MAILBOX) {
-            vk::PresentModeKHR::MAILBOX
-        } else if present_modes.contains(&vk::PresentModeKHR::IMMEDIATE) {
-            vk::PresentModeKHR::IMMEDIATE
+        Ok(if present_modes.contains(&requested) {
+            requested
         } else {
             vk::PresentModeKHR::FIFO
         })
@@ -5,6 +5,9 @@ use gpu_allocator::vulkan::Allocation;
 
 use super::{allocator::Allocator, error::GraphicsResult, uploader::Uploader};
 
+/// The format of every [`Texture2D`].
+pub(crate) const TEXTURE_FORMAT: vk::Format = vk::Format::R8G8B8A8_SRGB;
+
 /// The filtering mode with which a [`Texture2D`] should be sampled.
 pub enum TextureFilterMode {
     /// Take the average of the surrounding texels
@@ -19,7 +22,7 @@ pub enum TextureFilterMode {
 pub struct Texture2D {
     allocator: Rc<Allocator>,
     device: Rc<ash::Device>,
-    image: vk::Image,
+    pub(crate) image: vk::Image,
     alloc: Allocation,
     /// The [`vk::ImageView`] that can be used to refer to this [`Texture2D`].
     pub(crate) view: vk::ImageView,
@@ -48,7 +51,7 @@ impl Texture2D {
         let (image, alloc) = allocator.create_image(
             width,
             height,
-            vk::Format::R8G8B8A8_SRGB,
+            TEXTURE_FORMAT,
             vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
             gpu_allocator::MemoryLocation::GpuOnly,
         )?;
@@ -64,7 +67,7 @@ impl Texture2D {
         let view_info = vk::ImageViewCreateInfo::builder()
             .image(image)
             .view_type(vk::ImageViewType::TYPE_2D)
-            .format(vk::Format::R8G8B8A8_SRGB)
+            .format(TEXTURE_FORMAT)
             .components(vk::ComponentMapping {
                 r: vk::ComponentSwizzle::IDENTITY,
                 g: vk::ComponentSwizzle::IDENTITY,
@@ -113,6 +116,20 @@ impl Texture2D {
             sampler,
         }))
     }
+
+    /// Creates a black [`Texture2D`] that a camera can render into, see
+    /// [`CameraComponent::set_render_target`](crate::scene::component::camera_component::CameraComponent::set_render_target).
+    pub fn new_render_target(
+        width: u32,
+        height: u32,
+        filter: TextureFilterMode,
+        allocator: Rc<Allocator>,
+        uploader: &mut Uploader,
+        device: Rc<ash::Device>,
+    ) -> GraphicsResult<Rc<Texture2D>> {
+        let pixels = vec![0u8; width as usize * height as usize * 4];
+        Self::new(width, height, &pixels, filter, allocator, uploader, device)
+    }
 }
 
 impl Drop for Texture2D {
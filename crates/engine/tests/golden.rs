@@ -0,0 +1,216 @@
+//! Golden image tests that render the example scenes without a window and compare them
+//! against the reference images in `tests/golden`.
+//!
+//! The rendering tests need a Vulkan device and are ignored by default, run them with
+//! `cargo test -p vulkan_engine --test golden -- --ignored`.
+//! Setting `VE_UPDATE_GOLDEN=1` replaces the reference images with the rendered ones.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use image::{Rgba, RgbaImage};
+use vulkan_engine::{
+    core::{
+        engine::{Engine, EngineInfo},
+        window::{Dimensions, InitialWindowInfo},
+    },
+    vulkan::capture::CaptureKind,
+};
+
+#[allow(dead_code)]
+#[path = "../examples/brdf/main.rs"]
+mod brdf;
+#[allow(dead_code)]
+#[path = "../examples/mesh/main.rs"]
+mod mesh;
+#[allow(dead_code)]
+#[path = "../examples/textured_material/main.rs"]
+mod textured_material;
+
+const WIDTH: u32 = 480;
+const HEIGHT: u32 = 270;
+/// frames rendered before the captured one, so that all uploads have finished
+const WARMUP_FRAMES: usize = 3;
+
+/// CIE76 color difference below which two pixels look the same
+const MAX_DELTA_E: f32 = 2.3;
+/// fraction of pixels that may exceed [`MAX_DELTA_E`], e.g. due to rasterization differences at edges
+const MAX_DIFFERING_PIXELS: f32 = 0.001;
+
+const REFERENCE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden");
+/// the examples load their assets relative to the workspace root
+const WORKSPACE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../..");
+
+/// only one engine is created at a time, the working directory is shared as well
+static ENGINE_LOCK: Mutex<()> = Mutex::new(());
+
+fn output_dir() -> PathBuf {
+    Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden")
+}
+
+/// Renders the scene created by `setup` and returns the final image.
+fn render_scene(name: &'static str, setup: fn(&mut Engine)) -> RgbaImage {
+    let _lock = ENGINE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    std::env::set_current_dir(WORKSPACE_DIR).unwrap();
+
+    let info = EngineInfo {
+        window_info: InitialWindowInfo {
+            initial_dimensions: Dimensions {
+                width: WIDTH,
+                height: HEIGHT,
+            },
+            title: name,
+        },
+        app_name: name,
+    };
+    let mut engine = Engine::new_headless(info).expect("Failed to create a headless engine");
+    setup(&mut engine);
+
+    // the scene is never advanced, animations would make the result depend on frame timing
+    for _ in 0..WARMUP_FRAMES {
+        engine.step(0.0);
+    }
+
+    std::fs::create_dir_all(output_dir()).unwrap();
+    let path = output_dir().join(format!("{name}-actual.png"));
+    engine
+        .vulkan_manager
+        .request_capture(CaptureKind::Final, &path);
+    engine.step(0.0);
+    drop(engine);
+
+    image::open(&path)
+        .unwrap_or_else(|e| panic!("Failed to read capture {}: {e}", path.display()))
+        .into_rgba8()
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let value = value as f32 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Converts an sRGB pixel to CIE L*a*b* with a D65 white point.
+fn to_lab(pixel: &Rgba<u8>) -> [f32; 3] {
+    let [r, g, b] = [
+        srgb_to_linear(pixel[0]),
+        srgb_to_linear(pixel[1]),
+        srgb_to_linear(pixel[2]),
+    ];
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+
+    let f = |t: f32| {
+        if t > 0.008856 {
+            t.cbrt()
+        } else {
+            7.787 * t + 16.0 / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+fn delta_e(a: &Rgba<u8>, b: &Rgba<u8>) -> f32 {
+    let (a, b) = (to_lab(a), to_lab(b));
+    a.iter()
+        .zip(b.iter())
+        .map(|(a, b)| (a - b) * (a - b))
+        .sum::<f32>()
+        .sqrt()
+}
+
+/// Returns the number of pixels that differ noticeably, and an image that highlights them in red
+/// on top of a faded version of `reference`.
+fn compare(reference: &RgbaImage, actual: &RgbaImage) -> (usize, RgbaImage) {
+    let mut differing_pixels = 0;
+    let diff = RgbaImage::from_fn(reference.width(), reference.height(), |x, y| {
+        let (expected, got) = (reference.get_pixel(x, y), actual.get_pixel(x, y));
+        let delta = delta_e(expected, got);
+        if delta > MAX_DELTA_E {
+            differing_pixels += 1;
+            let intensity = (128.0 + delta * 4.0).min(255.0) as u8;
+            Rgba([intensity, 0, 0, 255])
+        } else {
+            let luma = to_lab(expected)[0] / 100.0 * 64.0;
+            Rgba([luma as u8, luma as u8, luma as u8, 255])
+        }
+    });
+    (differing_pixels, diff)
+}
+
+fn check_golden(name: &'static str, setup: fn(&mut Engine)) {
+    let actual = render_scene(name, setup);
+
+    let reference_path = Path::new(REFERENCE_DIR).join(format!("{name}.png"));
+    if std::env::var_os("VE_UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(REFERENCE_DIR).unwrap();
+        actual.save(&reference_path).unwrap();
+        return;
+    }
+
+    let reference = image::open(&reference_path)
+        .unwrap_or_else(|e| {
+            panic!(
+                "Failed to read reference image {} ({e}), run with VE_UPDATE_GOLDEN=1 to create it",
+                reference_path.display()
+            )
+        })
+        .into_rgba8();
+    assert_eq!(
+        reference.dimensions(),
+        actual.dimensions(),
+        "{name}: reference image has a different size"
+    );
+
+    let (differing_pixels, diff) = compare(&reference, &actual);
+    let max_differing_pixels = (WIDTH * HEIGHT) as f32 * MAX_DIFFERING_PIXELS;
+    if differing_pixels as f32 > max_differing_pixels {
+        let diff_path = output_dir().join(format!("{name}-diff.png"));
+        diff.save(&diff_path).unwrap();
+        panic!(
+            "{name}: {differing_pixels} pixels differ from the reference (at most {} allowed), diff written to {}",
+            max_differing_pixels as usize,
+            diff_path.display()
+        );
+    }
+}
+
+#[test]
+#[ignore = "requires a Vulkan device"]
+fn test_golden_brdf() {
+    check_golden("brdf", brdf::setup);
+}
+
+#[test]
+#[ignore = "requires a Vulkan device"]
+fn test_golden_mesh() {
+    check_golden("mesh", mesh::setup);
+}
+
+#[test]
+#[ignore = "requires a Vulkan device"]
+fn test_golden_textured_material() {
+    check_golden("textured_material", textured_material::setup);
+}
+
+#[test]
+fn test_compare_tolerance() {
+    let reference = RgbaImage::from_pixel(4, 4, Rgba([100, 150, 200, 255]));
+
+    // a difference of one step per channel is not noticeable
+    let mut actual = RgbaImage::from_pixel(4, 4, Rgba([101, 149, 201, 255]));
+    assert_eq!(compare(&reference, &actual).0, 0);
+
+    actual.put_pixel(1, 2, Rgba([200, 150, 100, 255]));
+    let (differing_pixels, diff) = compare(&reference, &actual);
+    assert_eq!(differing_pixels, 1);
+    assert_eq!(diff.get_pixel(1, 2)[1], 0);
+    assert!(diff.get_pixel(1, 2)[0] >= 128);
+}
@@ -1,4 +1,4 @@
-use std::{fs, slice};
+use std::fs;
 
 use ve_shader_reflect::*;
 
@@ -8,58 +8,105 @@ fn main() {
         env!("CARGO_MANIFEST_DIR")
     );
     let spv = fs::read(path).unwrap();
-    let info = ve_shader_reflect::reflect_shader(unsafe {
-        slice::from_raw_parts(spv.as_ptr() as *const u32, spv.len() / 4)
-    })
-    .expect("Failed to reflect shader");
+    // SPIR-V files are little endian
+    let code: Vec<u32> = spv
+        .chunks_exact(4)
+        .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        .collect();
+    let info = ve_shader_reflect::reflect_shader(&code).expect("Failed to reflect shader");
 
     print_bindings(&info.set_bindings);
 }
 
 fn print_bindings(bindings: &[SetBinding]) {
     for b in bindings {
-        print!("layout (set={}, binding={}) uniform ", b.set, b.binding);
+        print!("layout (set={}, binding={}) ", b.set, b.binding);
 
         match &b.data {
-            SetBindingData::Sampler => {
-                print!("sampler ");
-            }
+            SetBindingData::Sampler => print!("uniform sampler "),
             SetBindingData::Image { dim } => match dim {
-                ImageDimension::One => print!("image1D "),
-                ImageDimension::Two => print!("image2D "),
-                ImageDimension::Three => print!("image3D "),
-                ImageDimension::Cube => print!("imageCube "),
-                ImageDimension::SubpassInput => print!("subpassInput "),
-            },
-            SetBindingData::SampledImage { dim } => match dim {
-                ImageDimension::One => print!("sampler1D "),
-                ImageDimension::Two => print!("sampler2D "),
-                ImageDimension::Three => print!("sampler3D "),
-                ImageDimension::Cube => print!("samplerCube "),
-                ImageDimension::SubpassInput => print!("samplerSubpassInput "),
+                ImageDimension::SubpassInput => print!("uniform subpassInput "),
+                ImageDimension::Buffer => print!("uniform textureBuffer "),
+                dim => print!("uniform texture{} ", dimension_suffix(dim)),
             },
+            SetBindingData::SampledImage { dim } => {
+                print!("uniform sampler{} ", dimension_suffix(dim))
+            }
+            SetBindingData::StorageImage { dim, format } => {
+                print!("uniform /* {format:?} */ image{} ", dimension_suffix(dim))
+            }
             SetBindingData::UniformBuffer { layout } => {
-                println!("{} {{", layout.block_name);
+                println!("uniform {} {{", layout.block_name);
                 print_block_layout(layout);
                 print!("}} ");
             }
+            SetBindingData::StorageBuffer { layout } => {
+                println!("buffer {} {{", layout.block_name);
+                print_block_layout(layout);
+                print!("}} ");
+            }
+            SetBindingData::Unsupported => print!("<unsupported> "),
         }
 
-        println!("{};", b.var_name);
+        match b.count {
+            1 => println!("{};", b.var_name),
+            0 => println!("{}[];", b.var_name),
+            count => println!("{}[{count}];", b.var_name),
+        }
+    }
+}
+
+fn dimension_suffix(dim: &ImageDimension) -> &'static str {
+    match dim {
+        ImageDimension::One => "1D",
+        ImageDimension::OneArray => "1DArray",
+        ImageDimension::Two => "2D",
+        ImageDimension::TwoArray => "2DArray",
+        ImageDimension::Three => "3D",
+        ImageDimension::Cube => "Cube",
+        ImageDimension::CubeArray => "CubeArray",
+        ImageDimension::SubpassInput => "SubpassInput",
+        ImageDimension::Buffer => "Buffer",
     }
 }
 
 fn print_block_layout(layout: &BlockLayout) {
     for member in &layout.members {
-        print!("\tlayout (Offset={}) ", member.offset);
+        println!(
+            "\tlayout (Offset={}) {} {}; // Size={}",
+            member.offset,
+            type_name(&member.kind),
+            member.name,
+            member.size
+        );
+    }
+}
 
-        match member.kind {
-            BlockMemberType::Unsupported => print!("<unsupported> "),
-            BlockMemberType::Float => print!("float "),
-            BlockMemberType::FloatVector(dim) => print!("vec{dim} "),
-            BlockMemberType::FloatMatrix(dim) => print!("mat{dim} "),
+fn type_name(kind: &BlockMemberType) -> String {
+    match kind {
+        BlockMemberType::Unsupported => String::from("<unsupported>"),
+        BlockMemberType::Float => String::from("float"),
+        BlockMemberType::FloatVector(dim) => format!("vec{dim}"),
+        BlockMemberType::FloatMatrix(dim) => format!("mat{dim}"),
+        BlockMemberType::Int => String::from("int"),
+        BlockMemberType::IntVector(dim) => format!("ivec{dim}"),
+        BlockMemberType::UInt => String::from("uint"),
+        BlockMemberType::UIntVector(dim) => format!("uvec{dim}"),
+        BlockMemberType::Bool => String::from("bool"),
+        BlockMemberType::Array {
+            element,
+            length,
+            stride,
+        } => format!("{}[{length}] /* Stride={stride} */", type_name(element)),
+        BlockMemberType::RuntimeArray { element, stride } => {
+            format!("{}[] /* Stride={stride} */", type_name(element))
+        }
+        BlockMemberType::Struct { members } => {
+            let members: Vec<_> = members
+                .iter()
+                .map(|m| format!("{} {};", type_name(&m.kind), m.name))
+                .collect();
+            format!("struct {{ {} }}", members.join(" "))
         }
-
-        println!("{}; // Size={}", member.name, member.size);
     }
 }
@@ -0,0 +1,209 @@
+//! Generates Rust structs with the layout of reflected blocks, e.g. from a build script.
+
+use std::fmt::Write;
+
+use crate::{BlockLayout, BlockMember, BlockMemberType};
+
+/// Identifiers that are not valid as field names and are written as raw identifiers instead
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "box", "break", "const", "continue", "crate", "dyn", "else", "enum",
+    "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+    "mut", "pub", "ref", "return", "static", "struct", "trait", "true", "type", "unsafe", "use",
+    "where", "while", "yield", "try",
+];
+
+/// Options for [`rust_struct`]
+#[derive(Debug, Clone, Default)]
+pub struct RustStructOptions {
+    /// Traits to derive, e.g. `Clone` and `Copy`
+    pub derives: Vec<String>,
+    /// Rust types used for members of the given type instead of arrays of scalars,
+    /// e.g. `gfx_maths::Mat4` for [`FloatMatrix(4)`](BlockMemberType::FloatMatrix).
+    ///
+    /// The types must have the size of the member type without padding and an alignment of at most 4,
+    /// which the generated code asserts.
+    /// Padded members, e.g. a vec3 that is the element of a std140 array, are still generated as arrays of scalars.
+    pub types: Vec<(BlockMemberType, String)>,
+}
+
+impl RustStructOptions {
+    fn type_override(&self, kind: &BlockMemberType) -> Option<&str> {
+        self.types
+            .iter()
+            .find(|(k, _)| k == kind)
+            .map(|(_, name)| name.as_str())
+    }
+}
+
+/// Generates a `#[repr(C)]` struct named `name` with the layout of `layout`.
+///
+/// Gaps between members are filled with public `_padN` fields, so that every member is at its reflected offset.
+/// Member names are converted to snake case, members of nested structs are named after their path,
+/// e.g. `lights[1].color` becomes `lights_1_color`.
+/// Members that can't be represented, e.g. runtime arrays, are left out.
+///
+/// The generated code asserts the size and alignment of the struct at compile time,
+/// so regenerating it from a changed shader fails to compile instead of uploading data with a different layout.
+pub fn rust_struct(name: &str, layout: &BlockLayout, options: &RustStructOptions) -> String {
+    let mut fields = String::new();
+    let mut asserts = Vec::new();
+    let mut offset = 0;
+    let mut padding = 0;
+
+    for member in &layout.members {
+        if member.offset < offset {
+            // overlaps the previous member, which can't be expressed in a struct
+            continue;
+        }
+        let kind = match rust_type(&member.kind, member.size, options, &mut asserts) {
+            Some(kind) if member.size > 0 => kind,
+            _ => {
+                writeln!(fields, "    // {}: not representable", member.name).unwrap();
+                continue;
+            }
+        };
+
+        if member.offset > offset {
+            writeln!(
+                fields,
+                "    pub _pad{padding}: [u8; {}],",
+                member.offset - offset
+            )
+            .unwrap();
+            padding += 1;
+        }
+        writeln!(fields, "    pub {}: {kind},", field_name(member)).unwrap();
+        offset = member.offset + member.size;
+    }
+    if layout.total_size > offset {
+        writeln!(
+            fields,
+            "    pub _pad{padding}: [u8; {}],",
+            layout.total_size - offset
+        )
+        .unwrap();
+    }
+
+    let mut code = format!(
+        "/// Layout of the shader block `{}`\n#[repr(C)]\n",
+        layout.block_name
+    );
+    if !options.derives.is_empty() {
+        writeln!(code, "#[derive({})]", options.derives.join(", ")).unwrap();
+    }
+    writeln!(code, "pub struct {name} {{\n{fields}}}\n").unwrap();
+
+    asserts.push(format!(
+        "std::mem::size_of::<{name}>() == {}",
+        layout.total_size
+    ));
+    asserts.push(format!("std::mem::align_of::<{name}>() <= 4"));
+    asserts.dedup();
+    for assert in asserts {
+        writeln!(code, "const _: () = assert!({assert});").unwrap();
+    }
+
+    code
+}
+
+/// Returns the Rust type of a member of type `kind` that is `size` bytes large,
+/// or `None` if it can't be represented.
+///
+/// Assertions for the size and alignment of overridden types are added to `asserts`.
+fn rust_type(
+    kind: &BlockMemberType,
+    size: u32,
+    options: &RustStructOptions,
+    asserts: &mut Vec<String>,
+) -> Option<String> {
+    let name = options
+        .type_override(kind)
+        .filter(|_| natural_size(kind) == Some(size));
+    if let Some(name) = name {
+        let size_assert = format!("std::mem::size_of::<{name}>() == {size}");
+        if !asserts.contains(&size_assert) {
+            asserts.push(size_assert);
+            asserts.push(format!("std::mem::align_of::<{name}>() <= 4"));
+        }
+        return Some(name.to_owned());
+    }
+
+    match kind {
+        BlockMemberType::Float => components("f32", 1, size),
+        BlockMemberType::Int => components("i32", 1, size),
+        BlockMemberType::UInt => components("u32", 1, size),
+        BlockMemberType::FloatVector(count) => components("f32", *count, size),
+        BlockMemberType::IntVector(count) => components("i32", *count, size),
+        BlockMemberType::UIntVector(count) => components("u32", *count, size),
+        // columns may be padded as well, e.g. to 16 bytes in std140
+        BlockMemberType::FloatMatrix(columns) if size / columns * columns == size => {
+            let column = components("f32", *columns, size / columns)?;
+            Some(format!("[{column}; {columns}]"))
+        }
+        BlockMemberType::Array {
+            element,
+            length,
+            stride,
+        } if *length > 0 && size == length * stride => {
+            let element = rust_type(element, *stride, options, asserts)?;
+            Some(format!("[{element}; {length}]"))
+        }
+        _ => None,
+    }
+}
+
+/// Returns the size of a member of type `kind` without any padding, or `None` if it has no fixed size.
+fn natural_size(kind: &BlockMemberType) -> Option<u32> {
+    match kind {
+        BlockMemberType::Float | BlockMemberType::Int | BlockMemberType::UInt => Some(4),
+        BlockMemberType::FloatVector(count)
+        | BlockMemberType::IntVector(count)
+        | BlockMemberType::UIntVector(count) => Some(count * 4),
+        BlockMemberType::FloatMatrix(columns) => Some(columns * columns * 4),
+        BlockMemberType::Array {
+            element,
+            length,
+            stride,
+        } if natural_size(element) == Some(*stride) => Some(length * stride),
+        _ => None,
+    }
+}
+
+/// Returns `count` components of type `scalar` in `size` bytes, which may include padding,
+/// e.g. a vec3 that is the element of a std140 array becomes `[f32; 4]`.
+fn components(scalar: &str, count: u32, size: u32) -> Option<String> {
+    if size < count * 4 || size & 3 != 0 {
+        return None;
+    }
+    Some(match size / 4 {
+        1 => scalar.to_owned(),
+        n => format!("[{scalar}; {n}]"),
+    })
+}
+
+/// Converts the name of a member into a snake case Rust identifier, e.g. `lights[1].invModelMatrix` into `lights_1_inv_model_matrix`.
+fn field_name(member: &BlockMember) -> String {
+    let mut name = String::new();
+    let mut previous_lowercase = false;
+    for c in member.name.chars() {
+        if c.is_ascii_alphanumeric() {
+            if c.is_ascii_uppercase() && previous_lowercase {
+                name.push('_');
+            }
+            name.push(c.to_ascii_lowercase());
+            previous_lowercase = c.is_ascii_lowercase() || c.is_ascii_digit();
+        } else if !name.is_empty() && !name.ends_with('_') {
+            name.push('_');
+            previous_lowercase = false;
+        }
+    }
+    let name = name.trim_end_matches('_');
+
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{name}")
+    } else if KEYWORDS.contains(&name) {
+        format!("r#{name}")
+    } else {
+        name.to_owned()
+    }
+}
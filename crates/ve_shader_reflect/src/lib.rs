@@ -1,10 +1,13 @@
-use spirv_layout::Module;
+use spirv::{decoration, storage_class, Module, Type};
 use thiserror::Error;
 
+pub mod codegen;
+mod spirv;
+
 #[derive(Error, Debug, Clone)]
 pub enum Error {
     #[error("Reflection error: {0}")]
-    ReflectError(#[from] spirv_layout::Error),
+    ReflectError(String),
     #[error("Incompatible shader property names: {0} and {1}")]
     IncompatiblePropertyNames(String, String),
     #[error("Incompatible shader properties: {0} and {1}")]
@@ -14,6 +17,7 @@ pub enum Error {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum BlockMemberType {
     Unsupported,
     Float,
@@ -21,10 +25,42 @@ pub enum BlockMemberType {
     FloatVector(u32),
     /// A mat3 or mat4, indicated by the u32
     FloatMatrix(u32),
+    Int,
+    /// An ivec2, ivec3 or ivec4, indicated by the u32
+    IntVector(u32),
+    UInt,
+    /// A uvec2, uvec3 or uvec4, indicated by the u32
+    UIntVector(u32),
+    /// A boolean [`SpecializationConstant`].
+    ///
+    /// Blocks can't contain booleans, glslang and DXC store them as uint, so they are reflected as [`UInt`](BlockMemberType::UInt).
+    Bool,
+    /// A fixed-size array of `length` elements, each `stride` bytes apart
+    Array {
+        element: Box<BlockMemberType>,
+        length: u32,
+        stride: u32,
+    },
+    /// An array without a fixed size, e.g. the contents of a `StructuredBuffer`, with elements `stride` bytes apart
+    RuntimeArray {
+        element: Box<BlockMemberType>,
+        stride: u32,
+    },
+    /// A struct that is the element of a [`RuntimeArray`](BlockMemberType::RuntimeArray).
+    ///
+    /// Other nested structs are flattened into their surrounding block, see [`BlockMember`].
+    /// The offsets of the members are relative to the start of the struct.
+    Struct {
+        members: Vec<BlockMember>,
+    },
 }
 
 /// Describes a single member of a struct
+///
+/// Members of nested structs are flattened into their surrounding block and named with their path,
+/// e.g. `light.color` or `lights[1].color`.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct BlockMember {
     pub kind: BlockMemberType,
     pub offset: u32,
@@ -41,6 +77,7 @@ impl BlockMember {
 
 /// Describes the layout of a struct
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct BlockLayout {
     pub members: Vec<BlockMember>,
     pub block_name: String,
@@ -65,33 +102,161 @@ impl BlockLayout {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum ImageDimension {
     One,
+    OneArray,
     Two,
+    TwoArray,
     Three,
     Cube,
+    CubeArray,
     SubpassInput,
+    /// A texel buffer
+    Buffer,
+}
+
+/// The format of a storage image, as declared in the shader
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ImageFormat {
+    /// No format was declared, reads and writes need the `shaderStorageImage*WithoutFormat` features
+    Unknown,
+    Rgba32f,
+    Rgba16f,
+    R32f,
+    Rgba8,
+    Rgba8Snorm,
+    Rg32f,
+    Rg16f,
+    R11fG11fB10f,
+    R16f,
+    Rgba16,
+    Rgb10A2,
+    Rg16,
+    Rg8,
+    R16,
+    R8,
+    Rgba16Snorm,
+    Rg16Snorm,
+    Rg8Snorm,
+    R16Snorm,
+    R8Snorm,
+    Rgba32i,
+    Rgba16i,
+    Rgba8i,
+    R32i,
+    Rg32i,
+    Rg16i,
+    Rg8i,
+    R16i,
+    R8i,
+    Rgba32ui,
+    Rgba16ui,
+    Rgba8ui,
+    R32ui,
+    Rgb10a2ui,
+    Rg32ui,
+    Rg16ui,
+    Rg8ui,
+    R16ui,
+    R8ui,
+    /// A format this crate does not know, with its SPIR-V `ImageFormat` value
+    Other(u32),
+}
+
+impl ImageFormat {
+    /// Converts a SPIR-V `ImageFormat` into an [`ImageFormat`].
+    fn from_spirv(format: u32) -> ImageFormat {
+        use ImageFormat::*;
+        const FORMATS: [ImageFormat; 40] = [
+            Unknown,
+            Rgba32f,
+            Rgba16f,
+            R32f,
+            Rgba8,
+            Rgba8Snorm,
+            Rg32f,
+            Rg16f,
+            R11fG11fB10f,
+            R16f,
+            Rgba16,
+            Rgb10A2,
+            Rg16,
+            Rg8,
+            R16,
+            R8,
+            Rgba16Snorm,
+            Rg16Snorm,
+            Rg8Snorm,
+            R16Snorm,
+            R8Snorm,
+            Rgba32i,
+            Rgba16i,
+            Rgba8i,
+            R32i,
+            Rg32i,
+            Rg16i,
+            Rg8i,
+            R16i,
+            R8i,
+            Rgba32ui,
+            Rgba16ui,
+            Rgba8ui,
+            R32ui,
+            Rgb10a2ui,
+            Rg32ui,
+            Rg16ui,
+            Rg8ui,
+            R16ui,
+            R8ui,
+        ];
+        FORMATS
+            .get(format as usize)
+            .copied()
+            .unwrap_or(Other(format))
+    }
 }
 
 /// Describes the contents of a single binding in a DescriptorSet
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum SetBindingData {
     Sampler,
-    Image { dim: ImageDimension },
-    SampledImage { dim: ImageDimension },
-    UniformBuffer { layout: BlockLayout },
+    /// A sampled image without a sampler, a texel buffer if `dim` is [`Buffer`](ImageDimension::Buffer)
+    Image {
+        dim: ImageDimension,
+    },
+    SampledImage {
+        dim: ImageDimension,
+    },
+    /// A storage image, or a storage texel buffer if `dim` is [`Buffer`](ImageDimension::Buffer)
+    StorageImage {
+        dim: ImageDimension,
+        format: ImageFormat,
+    },
+    UniformBuffer {
+        layout: BlockLayout,
+    },
+    StorageBuffer {
+        layout: BlockLayout,
+    },
+    /// A resource this crate can't reflect, e.g. an acceleration structure
+    Unsupported,
 }
 
 impl SetBindingData {
-    /// Compares two [`SetBindingData`], ignoring the names contained in [`UniformBuffer`](SetBindingData::UniformBuffer)
+    /// Compares two [`SetBindingData`], ignoring the names contained in [`UniformBuffer`](SetBindingData::UniformBuffer) and [`StorageBuffer`](SetBindingData::StorageBuffer)
     fn equal_ignore_names(&self, r: &SetBindingData) -> bool {
-        match self {
-            SetBindingData::UniformBuffer { layout } => match r {
-                SetBindingData::UniformBuffer { layout: r_layout } => {
-                    layout.equal_ignore_names(r_layout)
-                }
-                _ => false,
-            },
+        match (self, r) {
+            (
+                SetBindingData::UniformBuffer { layout },
+                SetBindingData::UniformBuffer { layout: r_layout },
+            )
+            | (
+                SetBindingData::StorageBuffer { layout },
+                SetBindingData::StorageBuffer { layout: r_layout },
+            ) => layout.equal_ignore_names(r_layout),
             _ => self == r,
         }
     }
@@ -99,9 +264,12 @@ impl SetBindingData {
 
 /// Describes a single binding of a in a DescriptorSet
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct SetBinding {
     pub set: u32,
     pub binding: u32,
+    /// The number of descriptors for arrays of resources, 1 for single resources and 0 for arrays without a fixed size
+    pub count: u32,
     pub data: SetBindingData,
     pub var_name: String,
 }
@@ -109,15 +277,136 @@ pub struct SetBinding {
 impl SetBinding {
     /// Compares two [`SetBinding`], ignoring `var_name` and any names contained in `data`
     fn equal_ignore_names(&self, r: &SetBinding) -> bool {
-        self.set == r.set && self.binding == r.binding && self.data.equal_ignore_names(&r.data)
+        self.set == r.set
+            && self.binding == r.binding
+            && self.count == r.count
+            && self.data.equal_ignore_names(&r.data)
+    }
+}
+
+/// A set of shader stages, using the bit values of `VkShaderStageFlagBits`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ShaderStages(pub u32);
+
+impl ShaderStages {
+    pub const VERTEX: ShaderStages = ShaderStages(0x1);
+    pub const TESSELLATION_CONTROL: ShaderStages = ShaderStages(0x2);
+    pub const TESSELLATION_EVALUATION: ShaderStages = ShaderStages(0x4);
+    pub const GEOMETRY: ShaderStages = ShaderStages(0x8);
+    pub const FRAGMENT: ShaderStages = ShaderStages(0x10);
+    pub const COMPUTE: ShaderStages = ShaderStages(0x20);
+
+    /// Converts a SPIR-V `ExecutionModel` into the stage it runs in.
+    fn from_execution_model(execution_model: u32) -> ShaderStages {
+        match execution_model {
+            0 => ShaderStages::VERTEX,
+            1 => ShaderStages::TESSELLATION_CONTROL,
+            2 => ShaderStages::TESSELLATION_EVALUATION,
+            3 => ShaderStages::GEOMETRY,
+            4 => ShaderStages::FRAGMENT,
+            5 => ShaderStages::COMPUTE,
+            _ => ShaderStages::default(),
+        }
+    }
+
+    /// Returns true if all stages of `other` are contained in `self`
+    pub fn contains(self, other: ShaderStages) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+/// Serializes the stages by name, e.g. `["vertex", "fragment"]`
+#[cfg(feature = "serde")]
+impl serde::Serialize for ShaderStages {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        const NAMES: [(ShaderStages, &str); 6] = [
+            (ShaderStages::VERTEX, "vertex"),
+            (ShaderStages::TESSELLATION_CONTROL, "tessellation_control"),
+            (
+                ShaderStages::TESSELLATION_EVALUATION,
+                "tessellation_evaluation",
+            ),
+            (ShaderStages::GEOMETRY, "geometry"),
+            (ShaderStages::FRAGMENT, "fragment"),
+            (ShaderStages::COMPUTE, "compute"),
+        ];
+        serializer.collect_seq(
+            NAMES
+                .iter()
+                .filter(|(stage, _)| self.contains(*stage))
+                .map(|(_, name)| name),
+        )
+    }
+}
+
+impl std::ops::BitOr for ShaderStages {
+    type Output = ShaderStages;
+
+    fn bitor(self, r: ShaderStages) -> ShaderStages {
+        ShaderStages(self.0 | r.0)
+    }
+}
+
+/// Describes a push constant block and the stages it is accessed from
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PushConstantRange {
+    pub stages: ShaderStages,
+    /// The offset of the first member of the block
+    pub offset: u32,
+    /// The size from `offset` to the end of the last member, rounded up to a multiple of 4 as required by Vulkan
+    pub size: u32,
+    pub layout: BlockLayout,
+}
+
+/// Describes an input variable of a shader stage, e.g. a vertex attribute
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct StageInput {
+    pub location: u32,
+    pub kind: BlockMemberType,
+    /// The variable name, members of HLSL input structs are named `param.member`
+    pub name: String,
+}
+
+/// Describes a specialization constant, e.g. `layout (constant_id = 0) const bool USE_TEXTURE = true;`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SpecializationConstant {
+    /// The `constant_id` the value is specified with when creating a pipeline
+    pub id: u32,
+    /// [`Bool`](BlockMemberType::Bool), [`Int`](BlockMemberType::Int), [`UInt`](BlockMemberType::UInt)
+    /// or [`Float`](BlockMemberType::Float), anything else is [`Unsupported`](BlockMemberType::Unsupported)
+    pub kind: BlockMemberType,
+    /// The bits of the value used if none is specified, 0 or 1 for booleans
+    pub default_value: u32,
+    pub name: String,
+}
+
+impl SpecializationConstant {
+    /// Compares two [`SpecializationConstant`], ignoring their names
+    fn equal_ignore_names(&self, r: &SpecializationConstant) -> bool {
+        self.id == r.id && self.kind == r.kind && self.default_value == r.default_value
     }
 }
 
 /// Describes the layout of a ShaderModule
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ShaderInfo {
+    /// The stage of the entry point, or all stages of the merged modules
+    pub stage: ShaderStages,
     pub set_bindings: Vec<SetBinding>,
-    pub push_constants: Option<BlockLayout>,
+    pub push_constants: Vec<PushConstantRange>,
+    /// The inputs of the stage sorted by location, built-in inputs like `SV_VertexID` are not included
+    pub inputs: Vec<StageInput>,
+    /// The specialization constants sorted by id
+    pub specialization_constants: Vec<SpecializationConstant>,
+    /// The number of invocations per workgroup, only present for compute shaders
+    pub workgroup_size: Option<[u32; 3]>,
 }
 
 type Result<T> = std::result::Result<T, Error>;
@@ -126,70 +415,95 @@ type Result<T> = std::result::Result<T, Error>;
 pub fn reflect_shader(spv_code: &[u32]) -> Result<ShaderInfo> {
     let module = Module::from_words(spv_code)?;
 
+    let entry_point = module
+        .get_entry_points()
+        .first()
+        .ok_or(Error::NoEntryPoint)?;
+
+    let stage = ShaderStages::from_execution_model(entry_point.execution_model);
     let set_bindings = reflect_shader_bindings(&module)?;
+    let push_constants = reflect_push_constants(&module, stage)?;
+    let inputs = reflect_inputs(&module);
+    let specialization_constants = reflect_specialization_constants(&module);
 
     Ok(ShaderInfo {
+        stage,
         set_bindings,
-        push_constants: None,
+        push_constants,
+        inputs,
+        specialization_constants,
+        workgroup_size: entry_point.workgroup_size,
     })
 }
 
 fn reflect_shader_bindings(module: &Module) -> Result<Vec<SetBinding>> {
     let mut set_bindings = Vec::new();
 
-    let entry_point = module
-        .get_entry_points()
-        .first()
-        .ok_or(Error::NoEntryPoint)?;
+    for var in module.get_variables() {
+        let set = module.get_decoration(var.id, decoration::DESCRIPTOR_SET);
+        let binding = module.get_decoration(var.id, decoration::BINDING);
+        let (set, binding) = match (set, binding) {
+            (Some([set]), Some([binding])) => (*set, *binding),
+            _ => continue,
+        };
 
-    for var in &entry_point.uniforms {
-        let var_name = var.name.clone().unwrap_or_else(|| "".to_owned());
+        let var_name = module.get_name(var.id).unwrap_or_default().to_owned();
 
-        let data = match module.get_type(var.type_id).unwrap() {
-            spirv_layout::Type::Image2D { .. } => SetBindingData::Image {
-                dim: ImageDimension::Two,
+        // arrays of resources use a descriptor per element
+        let (type_id, count) = match module.get_type(var.type_id) {
+            Some(Type::Array {
+                element_type,
+                length_id,
+            }) => {
+                let count = module.get_constant_u32(*length_id).ok_or_else(|| {
+                    Error::ReflectError(format!("Could not compute length of {var_name}"))
+                })?;
+                (*element_type, count)
+            }
+            Some(Type::RuntimeArray { element_type }) => (*element_type, 0),
+            _ => (var.type_id, 1),
+        };
+
+        let data = match module.get_type(type_id) {
+            Some(Type::Image {
+                sampled: 2,
+                dim,
+                arrayed,
+                format,
+            }) => SetBindingData::StorageImage {
+                dim: image_dimension(*dim, *arrayed),
+                format: ImageFormat::from_spirv(*format),
             },
-            spirv_layout::Type::Sampler => SetBindingData::Sampler,
-            spirv_layout::Type::SampledImage { .. } => SetBindingData::SampledImage {
-                dim: ImageDimension::Two,
+            Some(Type::Image { dim, arrayed, .. }) => SetBindingData::Image {
+                dim: image_dimension(*dim, *arrayed),
             },
-            spirv_layout::Type::Struct { name, elements } => {
-                let total_size = module.get_var_size(var).unwrap();
-                let mut members = Vec::new();
-
-                for member in elements {
-                    let kind = match module.get_type(member.type_id).unwrap() {
-                        spirv_layout::Type::Float32 => BlockMemberType::Float,
-                        spirv_layout::Type::Vec2 => BlockMemberType::FloatVector(2),
-                        spirv_layout::Type::Vec3 => BlockMemberType::FloatVector(3),
-                        spirv_layout::Type::Vec4 => BlockMemberType::FloatVector(4),
-                        spirv_layout::Type::Mat3 => BlockMemberType::FloatMatrix(3),
-                        spirv_layout::Type::Mat4 => BlockMemberType::FloatMatrix(4),
-                        _ => BlockMemberType::Unsupported,
-                    };
-
-                    members.push(BlockMember {
-                        kind,
-                        offset: member.offset.unwrap(),
-                        size: module.get_member_size(member).unwrap(),
-                        name: member.name.clone().unwrap(),
-                    });
-                }
+            Some(Type::Sampler) => SetBindingData::Sampler,
+            Some(Type::SampledImage { image_type }) => match module.get_type(*image_type) {
+                Some(Type::Image { dim, arrayed, .. }) => SetBindingData::SampledImage {
+                    dim: image_dimension(*dim, *arrayed),
+                },
+                _ => SetBindingData::Unsupported,
+            },
+            Some(Type::Struct { .. }) => {
+                let layout = reflect_block_layout(module, type_id)?;
 
-                SetBindingData::UniformBuffer {
-                    layout: BlockLayout {
-                        members,
-                        block_name: name.clone().unwrap_or_default(),
-                        total_size,
-                    },
+                let is_storage_buffer = var.storage_class == storage_class::STORAGE_BUFFER
+                    || module
+                        .get_decoration(type_id, decoration::BUFFER_BLOCK)
+                        .is_some();
+                if is_storage_buffer {
+                    SetBindingData::StorageBuffer { layout }
+                } else {
+                    SetBindingData::UniformBuffer { layout }
                 }
             }
-            _ => SetBindingData::Sampler,
+            _ => SetBindingData::Unsupported,
         };
 
         set_bindings.push(SetBinding {
-            set: var.set,
-            binding: var.binding,
+            set,
+            binding,
+            count,
             data,
             var_name,
         });
@@ -198,13 +512,312 @@ fn reflect_shader_bindings(module: &Module) -> Result<Vec<SetBinding>> {
     Ok(set_bindings)
 }
 
+fn reflect_inputs(module: &Module) -> Vec<StageInput> {
+    let mut inputs: Vec<_> = module
+        .get_variables()
+        .iter()
+        .filter(|var| {
+            var.storage_class == storage_class::INPUT
+                && module
+                    .get_decoration(var.id, decoration::BUILT_IN)
+                    .is_none()
+        })
+        .filter_map(|var| {
+            let location = *module
+                .get_decoration(var.id, decoration::LOCATION)?
+                .first()?;
+            Some(StageInput {
+                location,
+                kind: reflect_member_type(module, var.type_id),
+                name: module.get_name(var.id).unwrap_or_default().to_owned(),
+            })
+        })
+        .collect();
+    inputs.sort_by_key(|input| input.location);
+    inputs
+}
+
+fn reflect_specialization_constants(module: &Module) -> Vec<SpecializationConstant> {
+    let mut constants: Vec<_> = module
+        .get_specialization_constants()
+        .filter_map(|constant| {
+            let id = *module
+                .get_decoration(constant.id, decoration::SPEC_ID)?
+                .first()?;
+            let kind = match reflect_member_type(module, constant.type_id) {
+                kind @ (BlockMemberType::Bool
+                | BlockMemberType::Int
+                | BlockMemberType::UInt
+                | BlockMemberType::Float) => kind,
+                _ => BlockMemberType::Unsupported,
+            };
+            Some(SpecializationConstant {
+                id,
+                kind,
+                default_value: constant.value.first().copied().unwrap_or(0),
+                name: module.get_name(constant.id).unwrap_or_default().to_owned(),
+            })
+        })
+        .collect();
+    constants.sort_by_key(|constant| constant.id);
+    constants
+}
+
+fn reflect_push_constants(module: &Module, stage: ShaderStages) -> Result<Vec<PushConstantRange>> {
+    let mut push_constants = Vec::new();
+
+    for var in module.get_variables() {
+        if var.storage_class != storage_class::PUSH_CONSTANT {
+            continue;
+        }
+
+        let layout = reflect_block_layout(module, var.type_id)?;
+        let offset = layout.members.iter().map(|m| m.offset).min().unwrap_or(0);
+        let size = (layout.total_size.saturating_sub(offset) + 3) & !3;
+
+        push_constants.push(PushConstantRange {
+            stages: stage,
+            offset,
+            size,
+            layout,
+        });
+    }
+
+    Ok(push_constants)
+}
+
+fn reflect_block_layout(module: &Module, struct_id: u32) -> Result<BlockLayout> {
+    let total_size = module
+        .get_type_size(struct_id)
+        .ok_or_else(|| Error::ReflectError(format!("Could not compute size of {struct_id}")))?;
+
+    let mut members = Vec::new();
+    let mut budget = MAX_FLATTENED_MEMBERS;
+    reflect_block_members(module, struct_id, 0, "", &mut members, &mut budget)?;
+
+    Ok(BlockLayout {
+        members,
+        block_name: module.get_name(struct_id).unwrap_or_default().to_owned(),
+        total_size,
+    })
+}
+
+/// Upper limit for the number of members and array elements visited while flattening a block,
+/// so that malformed modules with huge nested arrays fail instead of exhausting memory.
+const MAX_FLATTENED_MEMBERS: u32 = 1 << 16;
+
+/// Appends the members of struct `struct_id` to `members`, recursing into nested structs.
+///
+/// `base_offset` is the offset of the struct in the surrounding block and `prefix` is prepended to every member name.
+/// `budget` is decremented for every visited member and array element.
+fn reflect_block_members(
+    module: &Module,
+    struct_id: u32,
+    base_offset: u32,
+    prefix: &str,
+    members: &mut Vec<BlockMember>,
+    budget: &mut u32,
+) -> Result<()> {
+    let member_types = match module.get_type(struct_id) {
+        Some(Type::Struct { member_types }) => member_types,
+        _ => {
+            return Err(Error::ReflectError(format!(
+                "Type {struct_id} is not a struct"
+            )))
+        }
+    };
+
+    for (index, member_type) in member_types.iter().enumerate() {
+        let index = index as u32;
+        *budget = budget.checked_sub(1).ok_or_else(|| {
+            Error::ReflectError(format!("Block {struct_id} has too many members"))
+        })?;
+
+        let name = format!(
+            "{prefix}{}",
+            module.get_member_name(struct_id, index).unwrap_or_default()
+        );
+        let offset = module
+            .get_member_decoration(struct_id, index, decoration::OFFSET)
+            .and_then(|d| d.first().copied())
+            .unwrap_or(0)
+            .checked_add(base_offset)
+            .ok_or_else(|| Error::ReflectError(format!("Offset of {name} is out of range")))?;
+
+        match module.get_type(*member_type) {
+            Some(Type::Struct { .. }) => {
+                reflect_block_members(
+                    module,
+                    *member_type,
+                    offset,
+                    &format!("{name}."),
+                    members,
+                    budget,
+                )?;
+            }
+            Some(Type::Array { element_type, .. })
+                if matches!(module.get_type(*element_type), Some(Type::Struct { .. })) =>
+            {
+                let (length, stride) = array_length_and_stride(module, *member_type)
+                    .filter(|(length, stride)| {
+                        *stride > 0
+                            && length
+                                .checked_mul(*stride)
+                                .and_then(|size| size.checked_add(offset))
+                                .is_some()
+                    })
+                    .ok_or_else(|| {
+                        Error::ReflectError(format!("Could not compute layout of array {name}"))
+                    })?;
+                for i in 0..length {
+                    *budget = budget.checked_sub(1).ok_or_else(|| {
+                        Error::ReflectError(format!("Array {name} has too many elements"))
+                    })?;
+                    reflect_block_members(
+                        module,
+                        *element_type,
+                        offset + i * stride,
+                        &format!("{name}[{i}]."),
+                        members,
+                        budget,
+                    )?;
+                }
+            }
+            _ => members.push(BlockMember {
+                kind: reflect_member_type(module, *member_type),
+                offset,
+                size: module.get_member_size(struct_id, index).unwrap_or(0),
+                name,
+            }),
+        }
+    }
+
+    Ok(())
+}
+
+fn reflect_member_type(module: &Module, type_id: u32) -> BlockMemberType {
+    match module.get_type(type_id) {
+        Some(Type::Float { width: 32 }) => BlockMemberType::Float,
+        Some(Type::Int {
+            width: 32,
+            signed: true,
+        }) => BlockMemberType::Int,
+        Some(Type::Int {
+            width: 32,
+            signed: false,
+        }) => BlockMemberType::UInt,
+        Some(Type::Bool) => BlockMemberType::Bool,
+        Some(Type::Vector {
+            component_type,
+            count,
+        }) => match reflect_member_type(module, *component_type) {
+            BlockMemberType::Float => BlockMemberType::FloatVector(*count),
+            BlockMemberType::Int => BlockMemberType::IntVector(*count),
+            BlockMemberType::UInt => BlockMemberType::UIntVector(*count),
+            _ => BlockMemberType::Unsupported,
+        },
+        Some(Type::Matrix {
+            column_type,
+            column_count,
+        }) => match module.get_type(*column_type) {
+            Some(Type::Vector {
+                component_type,
+                count,
+            }) if is_float(module, *component_type) && count == column_count => {
+                BlockMemberType::FloatMatrix(*column_count)
+            }
+            _ => BlockMemberType::Unsupported,
+        },
+        Some(Type::RuntimeArray { element_type }) => {
+            let stride = module
+                .get_decoration(type_id, decoration::ARRAY_STRIDE)
+                .and_then(|d| d.first().copied());
+            let element = match module.get_type(*element_type) {
+                Some(Type::Struct { .. }) => {
+                    let mut members = Vec::new();
+                    let mut budget = MAX_FLATTENED_MEMBERS;
+                    match reflect_block_members(
+                        module,
+                        *element_type,
+                        0,
+                        "",
+                        &mut members,
+                        &mut budget,
+                    ) {
+                        Ok(()) => BlockMemberType::Struct { members },
+                        Err(_) => BlockMemberType::Unsupported,
+                    }
+                }
+                _ => reflect_member_type(module, *element_type),
+            };
+            match stride {
+                Some(stride) => BlockMemberType::RuntimeArray {
+                    element: Box::new(element),
+                    stride,
+                },
+                None => BlockMemberType::Unsupported,
+            }
+        }
+        Some(Type::Array { element_type, .. }) => match array_length_and_stride(module, type_id) {
+            Some((length, stride)) => BlockMemberType::Array {
+                element: Box::new(reflect_member_type(module, *element_type)),
+                length,
+                stride,
+            },
+            None => BlockMemberType::Unsupported,
+        },
+        _ => BlockMemberType::Unsupported,
+    }
+}
+
+/// Returns the length and `ArrayStride` of a fixed-size array type.
+fn array_length_and_stride(module: &Module, array_id: u32) -> Option<(u32, u32)> {
+    let length_id = match module.get_type(array_id)? {
+        Type::Array { length_id, .. } => *length_id,
+        _ => return None,
+    };
+    let length = module.get_constant_u32(length_id)?;
+    let stride = module
+        .get_decoration(array_id, decoration::ARRAY_STRIDE)?
+        .first()
+        .copied()?;
+    Some((length, stride))
+}
+
+fn is_float(module: &Module, type_id: u32) -> bool {
+    matches!(module.get_type(type_id), Some(Type::Float { width: 32 }))
+}
+
+/// Converts a SPIR-V `Dim` and the `Arrayed` flag of an image into an [`ImageDimension`].
+fn image_dimension(dim: u32, arrayed: bool) -> ImageDimension {
+    match (dim, arrayed) {
+        (0, false) => ImageDimension::One,
+        (0, true) => ImageDimension::OneArray,
+        (2, _) => ImageDimension::Three,
+        (3, false) => ImageDimension::Cube,
+        (3, true) => ImageDimension::CubeArray,
+        (5, _) => ImageDimension::Buffer,
+        (6, _) => ImageDimension::SubpassInput,
+        (_, false) => ImageDimension::Two,
+        (_, true) => ImageDimension::TwoArray,
+    }
+}
+
 /// Merges the reflection data of two ShaderModules.
 ///
 /// Should only be used on vertex and fragment shaders that belong together.
 ///
+/// Push constant blocks with the same offset and size are combined into a single range used by the stages of both modules.
+/// The inputs of the earlier stage are kept, i.e. the vertex attributes when merging a vertex and fragment shader.
+/// Specialization constants with the same id have to match in both modules.
+///
 /// `names_must_match` indicates whether variable, block and member names must match in both shader modules.
 pub fn merge(a: ShaderInfo, b: &ShaderInfo, names_must_match: bool) -> Result<ShaderInfo> {
     let mut res = a;
+    if b.stage.0 < res.stage.0 {
+        res.inputs = b.inputs.clone();
+    }
+    res.stage = res.stage | b.stage;
 
     for binding in &b.set_bindings {
         let mut found = false;
@@ -236,5 +849,64 @@ pub fn merge(a: ShaderInfo, b: &ShaderInfo, names_must_match: bool) -> Result<Sh
         }
     }
 
+    for range in &b.push_constants {
+        let a_range = res
+            .push_constants
+            .iter_mut()
+            .find(|r| r.offset == range.offset && r.size == range.size);
+        let a_range = match a_range {
+            Some(a_range) => a_range,
+            None => {
+                res.push_constants.push(range.clone());
+                continue;
+            }
+        };
+
+        if names_must_match {
+            if a_range.layout != range.layout {
+                return Err(Error::IncompatiblePropertyNames(
+                    range.layout.block_name.clone(),
+                    a_range.layout.block_name.clone(),
+                ));
+            }
+        } else if !a_range.layout.equal_ignore_names(&range.layout) {
+            return Err(Error::IncompatibleProperties(
+                range.layout.block_name.clone(),
+                a_range.layout.block_name.clone(),
+            ));
+        }
+
+        a_range.stages = a_range.stages | range.stages;
+    }
+
+    for constant in &b.specialization_constants {
+        let a_constant = match res
+            .specialization_constants
+            .iter()
+            .find(|c| c.id == constant.id)
+        {
+            Some(a_constant) => a_constant,
+            None => {
+                res.specialization_constants.push(constant.clone());
+                continue;
+            }
+        };
+
+        if names_must_match {
+            if a_constant != constant {
+                return Err(Error::IncompatiblePropertyNames(
+                    constant.name.clone(),
+                    a_constant.name.clone(),
+                ));
+            }
+        } else if !a_constant.equal_ignore_names(constant) {
+            return Err(Error::IncompatibleProperties(
+                constant.name.clone(),
+                a_constant.name.clone(),
+            ));
+        }
+    }
+    res.specialization_constants.sort_by_key(|c| c.id);
+
     Ok(res)
 }
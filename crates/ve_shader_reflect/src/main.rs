@@ -0,0 +1,177 @@
+use std::{fs, io, path::PathBuf, str::FromStr};
+
+use structopt::StructOpt;
+use ve_shader_reflect::ShaderInfo;
+
+// Cli arguments
+#[derive(StructOpt, Debug)]
+#[structopt(name = "ve_shader_reflect")]
+struct CliArgs {
+    /// The SPIR-V files to reflect, the stages of a pipeline are merged into a single description
+    #[structopt(required = true, parse(from_os_str))]
+    input: Vec<PathBuf>,
+    /// Output format, either json or toml
+    #[structopt(short = "f", long = "format", default_value = "json")]
+    format: Format,
+    /// Output file, the description is printed to stdout if omitted
+    #[structopt(short = "o", long = "output", parse(from_os_str))]
+    output: Option<PathBuf>,
+    /// Fail if the variable, block or member names of the stages differ
+    #[structopt(long = "match-names")]
+    match_names: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Json,
+    Toml,
+}
+
+impl FromStr for Format {
+    type Err = CliError;
+
+    fn from_str(s: &str) -> Result<Format, CliError> {
+        match s.to_ascii_lowercase().as_ref() {
+            "json" => Ok(Format::Json),
+            "toml" => Ok(Format::Toml),
+            _ => Err(CliError::UnknownFormat(s.to_owned())),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+enum CliError {
+    #[error("Unknown output format {0}, expected json or toml")]
+    UnknownFormat(String),
+    #[error("Error reading {}: {1}", .0.display())]
+    Read(PathBuf, io::Error),
+    #[error("Error reflecting {}: {1}", .0.display())]
+    Reflect(PathBuf, ve_shader_reflect::Error),
+    #[error("Error merging the shader stages: {0}")]
+    Merge(ve_shader_reflect::Error),
+    #[error("Error writing JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Error writing TOML: {0}")]
+    Toml(#[from] toml::ser::Error),
+    #[error("Error writing {}: {1}", .0.display())]
+    Write(PathBuf, io::Error),
+}
+
+fn main() {
+    let args = CliArgs::from_args();
+
+    if let Err(e) = run(&args) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(args: &CliArgs) -> Result<(), CliError> {
+    let info = reflect_files(&args.input, args.match_names)?;
+    let output = serialize(&info, args.format)?;
+
+    match &args.output {
+        Some(path) => fs::write(path, output).map_err(|e| CliError::Write(path.clone(), e)),
+        None => {
+            println!("{}", output);
+            Ok(())
+        }
+    }
+}
+
+/// Reflects every file and merges the results, see [`ve_shader_reflect::merge`].
+fn reflect_files(paths: &[PathBuf], match_names: bool) -> Result<ShaderInfo, CliError> {
+    let mut merged: Option<ShaderInfo> = None;
+    for path in paths {
+        let bytes = fs::read(path).map_err(|e| CliError::Read(path.clone(), e))?;
+        // SPIR-V words are little-endian, the file is not necessarily aligned for a u32 slice
+        let code: Vec<u32> = bytes
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect();
+        let info = ve_shader_reflect::reflect_shader(&code)
+            .map_err(|e| CliError::Reflect(path.clone(), e))?;
+
+        merged = Some(match merged {
+            Some(merged) => {
+                ve_shader_reflect::merge(merged, &info, match_names).map_err(CliError::Merge)?
+            }
+            None => info,
+        });
+    }
+
+    // structopt requires at least one input
+    Ok(merged.expect("No input files"))
+}
+
+fn serialize(info: &ShaderInfo, format: Format) -> Result<String, CliError> {
+    match format {
+        Format::Json => Ok(serde_json::to_string_pretty(info)?),
+        // the TOML serializer can't represent enums with data or `None`, so the JSON tree is converted instead
+        Format::Toml => {
+            let json = serde_json::to_value(info)?;
+            let toml = json_to_toml(json).unwrap_or_else(|| toml::Value::Table(Default::default()));
+            Ok(toml::to_string_pretty(&toml)?)
+        }
+    }
+}
+
+/// Converts a JSON value into a TOML value, returns `None` for `null`, which TOML has no equivalent for.
+fn json_to_toml(json: serde_json::Value) -> Option<toml::Value> {
+    Some(match json {
+        serde_json::Value::Null => return None,
+        serde_json::Value::Bool(b) => toml::Value::Boolean(b),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => toml::Value::Integer(i),
+            None => toml::Value::Float(n.as_f64()?),
+        },
+        serde_json::Value::String(s) => toml::Value::String(s),
+        serde_json::Value::Array(a) => {
+            toml::Value::Array(a.into_iter().filter_map(json_to_toml).collect())
+        }
+        serde_json::Value::Object(o) => toml::Value::Table(
+            o.into_iter()
+                .filter_map(|(key, value)| Some((key, json_to_toml(value)?)))
+                .collect(),
+        ),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::{reflect_files, serialize, Format};
+
+    fn shader(name: &str) -> PathBuf {
+        PathBuf::from(format!(
+            "{}/tests/shaders/{}.spv",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        ))
+    }
+
+    #[test]
+    fn test_serialize() {
+        let info = reflect_files(
+            &[shader("gpass_simple_vert"), shader("gpass_simple_frag")],
+            false,
+        )
+        .unwrap();
+
+        let json: serde_json::Value =
+            serde_json::from_str(&serialize(&info, Format::Json).unwrap()).unwrap();
+        assert_eq!(json["stage"], serde_json::json!(["vertex", "fragment"]));
+        assert_eq!(json["set_bindings"][0]["var_name"], "u_FrameData");
+
+        let toml: toml::Value = toml::from_str(&serialize(&info, Format::Toml).unwrap()).unwrap();
+        assert_eq!(
+            toml["set_bindings"][0]["var_name"].as_str(),
+            Some("u_FrameData")
+        );
+        assert_eq!(
+            toml["set_bindings"][0]["data"]["UniformBuffer"]["layout"]["total_size"].as_integer(),
+            json["set_bindings"][0]["data"]["UniformBuffer"]["layout"]["total_size"].as_i64()
+        );
+    }
+}
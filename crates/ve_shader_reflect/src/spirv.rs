@@ -0,0 +1,712 @@
+//! A minimal SPIR-V parser that only extracts the information needed for reflection.
+//!
+//! It replaces `spirv-layout`, whose latest release (0.4) can't reflect compute shaders:
+//! it rejects modules with entry points that are neither vertex nor fragment shaders,
+//! and it ignores the `StorageBuffer` storage class and the `LocalSize` execution mode.
+//!
+//! Parsing stops at the first function, as all types, decorations and global variables are declared before.
+
+use std::collections::HashMap;
+
+use crate::{Error, Result};
+
+const MAGIC: u32 = 0x0723_0203;
+
+mod op {
+    pub const NAME: u32 = 5;
+    pub const MEMBER_NAME: u32 = 6;
+    pub const ENTRY_POINT: u32 = 15;
+    pub const EXECUTION_MODE: u32 = 16;
+    pub const TYPE_VOID: u32 = 19;
+    pub const TYPE_BOOL: u32 = 20;
+    pub const TYPE_INT: u32 = 21;
+    pub const TYPE_FLOAT: u32 = 22;
+    pub const TYPE_VECTOR: u32 = 23;
+    pub const TYPE_MATRIX: u32 = 24;
+    pub const TYPE_IMAGE: u32 = 25;
+    pub const TYPE_SAMPLER: u32 = 26;
+    pub const TYPE_SAMPLED_IMAGE: u32 = 27;
+    pub const TYPE_ARRAY: u32 = 28;
+    pub const TYPE_RUNTIME_ARRAY: u32 = 29;
+    pub const TYPE_STRUCT: u32 = 30;
+    pub const TYPE_POINTER: u32 = 32;
+    pub const CONSTANT_TRUE: u32 = 41;
+    pub const CONSTANT_FALSE: u32 = 42;
+    pub const CONSTANT: u32 = 43;
+    pub const SPEC_CONSTANT_TRUE: u32 = 48;
+    pub const SPEC_CONSTANT_FALSE: u32 = 49;
+    pub const SPEC_CONSTANT: u32 = 50;
+    pub const FUNCTION: u32 = 54;
+    pub const VARIABLE: u32 = 59;
+    pub const DECORATE: u32 = 71;
+    pub const MEMBER_DECORATE: u32 = 72;
+}
+
+pub(crate) mod decoration {
+    pub const SPEC_ID: u32 = 1;
+    pub const BUFFER_BLOCK: u32 = 3;
+    pub const ROW_MAJOR: u32 = 4;
+    pub const ARRAY_STRIDE: u32 = 6;
+    pub const MATRIX_STRIDE: u32 = 7;
+    pub const BUILT_IN: u32 = 11;
+    pub const LOCATION: u32 = 30;
+    pub const BINDING: u32 = 33;
+    pub const DESCRIPTOR_SET: u32 = 34;
+    pub const OFFSET: u32 = 35;
+}
+
+pub(crate) mod storage_class {
+    pub const INPUT: u32 = 1;
+    pub const PUSH_CONSTANT: u32 = 9;
+    pub const STORAGE_BUFFER: u32 = 12;
+}
+
+const EXECUTION_MODE_LOCAL_SIZE: u32 = 17;
+
+/// Returns the minimum number of operands, i.e. words after the opcode, of the instructions that are parsed.
+fn min_operand_count(opcode: u32) -> usize {
+    match opcode {
+        op::TYPE_VOID | op::TYPE_BOOL | op::TYPE_SAMPLER | op::TYPE_STRUCT => 1,
+        op::NAME
+        | op::EXECUTION_MODE
+        | op::DECORATE
+        | op::TYPE_FLOAT
+        | op::TYPE_SAMPLED_IMAGE
+        | op::TYPE_RUNTIME_ARRAY
+        | op::CONSTANT_TRUE
+        | op::CONSTANT_FALSE
+        | op::SPEC_CONSTANT_TRUE
+        | op::SPEC_CONSTANT_FALSE => 2,
+        op::MEMBER_NAME
+        | op::ENTRY_POINT
+        | op::MEMBER_DECORATE
+        | op::TYPE_INT
+        | op::TYPE_VECTOR
+        | op::TYPE_MATRIX
+        | op::TYPE_ARRAY
+        | op::TYPE_POINTER
+        | op::CONSTANT
+        | op::SPEC_CONSTANT
+        | op::VARIABLE => 3,
+        op::TYPE_IMAGE => 8,
+        _ => 0,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Type {
+    Void,
+    Bool,
+    Int {
+        width: u32,
+        signed: bool,
+    },
+    Float {
+        width: u32,
+    },
+    Vector {
+        component_type: u32,
+        count: u32,
+    },
+    Matrix {
+        column_type: u32,
+        column_count: u32,
+    },
+    Image {
+        /// SPIR-V `Dim`: 0 = 1D, 1 = 2D, 2 = 3D, 3 = Cube, 4 = Rect, 5 = Buffer, 6 = SubpassData
+        dim: u32,
+        arrayed: bool,
+        /// 1 if used together with a sampler, 2 if used as storage image
+        sampled: u32,
+        /// SPIR-V `ImageFormat`, 0 = Unknown
+        format: u32,
+    },
+    Sampler,
+    SampledImage {
+        image_type: u32,
+    },
+    Array {
+        element_type: u32,
+        length_id: u32,
+    },
+    RuntimeArray {
+        element_type: u32,
+    },
+    Struct {
+        member_types: Vec<u32>,
+    },
+    Pointer {
+        storage_class: u32,
+        pointee_type: u32,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Variable {
+    pub(crate) id: u32,
+    /// The type of the variable itself, i.e. not the pointer type
+    pub(crate) type_id: u32,
+    pub(crate) storage_class: u32,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct EntryPoint {
+    pub(crate) id: u32,
+    /// SPIR-V `ExecutionModel`: 0 = Vertex, 1 = TessellationControl, 2 = TessellationEvaluation, 3 = Geometry, 4 = Fragment, 5 = GLCompute
+    pub(crate) execution_model: u32,
+    pub(crate) workgroup_size: Option<[u32; 3]>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Constant {
+    pub(crate) id: u32,
+    pub(crate) type_id: u32,
+    pub(crate) value: Vec<u32>,
+    /// Whether this is a specialization constant, whose value can be overridden when creating a pipeline
+    pub(crate) specialization: bool,
+}
+
+#[derive(Default)]
+pub(crate) struct Module {
+    entry_points: Vec<EntryPoint>,
+    names: HashMap<u32, String>,
+    member_names: HashMap<(u32, u32), String>,
+    decorations: HashMap<(u32, u32), Vec<u32>>,
+    member_decorations: HashMap<(u32, u32, u32), Vec<u32>>,
+    types: HashMap<u32, Type>,
+    constants: HashMap<u32, Constant>,
+    variables: Vec<Variable>,
+}
+
+impl Module {
+    pub(crate) fn from_words(words: &[u32]) -> Result<Self> {
+        if words.len() < 5 || words[0] != MAGIC {
+            return Err(Error::ReflectError(
+                "Code is not a valid SPIR-V module".to_owned(),
+            ));
+        }
+
+        let mut module = Module::default();
+        let mut pointer_types = HashMap::new();
+        let mut raw_variables = Vec::new();
+
+        let mut pos = 5;
+        while pos < words.len() {
+            let word_count = (words[pos] >> 16) as usize;
+            let opcode = words[pos] & 0xFFFF;
+            if word_count == 0 || pos + word_count > words.len() {
+                return Err(Error::ReflectError(format!(
+                    "Invalid instruction length at word {pos}"
+                )));
+            }
+            let args = &words[pos + 1..pos + word_count];
+            if args.len() < min_operand_count(opcode) {
+                return Err(Error::ReflectError(format!(
+                    "Instruction with opcode {opcode} at word {pos} is too short"
+                )));
+            }
+            pos += word_count;
+
+            if (op::TYPE_VOID..=op::TYPE_POINTER).contains(&opcode)
+                && module.types.contains_key(&args[0])
+            {
+                return Err(Error::ReflectError(format!(
+                    "Type {} is declared twice",
+                    args[0]
+                )));
+            }
+            // types have to be declared before they are used, which also rules out cyclic types
+            let referenced_types: &[u32] = match opcode {
+                op::TYPE_VECTOR
+                | op::TYPE_MATRIX
+                | op::TYPE_SAMPLED_IMAGE
+                | op::TYPE_ARRAY
+                | op::TYPE_RUNTIME_ARRAY => &args[1..2],
+                op::TYPE_STRUCT => &args[1..],
+                _ => &[],
+            };
+            if let Some(undeclared) = referenced_types
+                .iter()
+                .find(|id| !module.types.contains_key(id))
+            {
+                return Err(Error::ReflectError(format!(
+                    "Type {} references undeclared type {undeclared}",
+                    args[0]
+                )));
+            }
+
+            match opcode {
+                op::NAME => {
+                    module.names.insert(args[0], parse_string(&args[1..]));
+                }
+                op::MEMBER_NAME => {
+                    module
+                        .member_names
+                        .insert((args[0], args[1]), parse_string(&args[2..]));
+                }
+                op::ENTRY_POINT => {
+                    module.entry_points.push(EntryPoint {
+                        id: args[1],
+                        execution_model: args[0],
+                        workgroup_size: None,
+                    });
+                }
+                op::EXECUTION_MODE if args[1] == EXECUTION_MODE_LOCAL_SIZE => {
+                    if args.len() < 5 {
+                        return Err(Error::ReflectError(
+                            "LocalSize execution mode is missing its operands".to_owned(),
+                        ));
+                    }
+                    if let Some(entry_point) =
+                        module.entry_points.iter_mut().find(|e| e.id == args[0])
+                    {
+                        entry_point.workgroup_size = Some([args[2], args[3], args[4]]);
+                    }
+                }
+                op::DECORATE => {
+                    module
+                        .decorations
+                        .insert((args[0], args[1]), args[2..].to_vec());
+                }
+                op::MEMBER_DECORATE => {
+                    module
+                        .member_decorations
+                        .insert((args[0], args[1], args[2]), args[3..].to_vec());
+                }
+                op::TYPE_VOID => {
+                    module.types.insert(args[0], Type::Void);
+                }
+                op::TYPE_BOOL => {
+                    module.types.insert(args[0], Type::Bool);
+                }
+                op::TYPE_INT => {
+                    module.types.insert(
+                        args[0],
+                        Type::Int {
+                            width: args[1],
+                            signed: args[2] != 0,
+                        },
+                    );
+                }
+                op::TYPE_FLOAT => {
+                    module.types.insert(args[0], Type::Float { width: args[1] });
+                }
+                op::TYPE_VECTOR => {
+                    module.types.insert(
+                        args[0],
+                        Type::Vector {
+                            component_type: args[1],
+                            count: args[2],
+                        },
+                    );
+                }
+                op::TYPE_MATRIX => {
+                    module.types.insert(
+                        args[0],
+                        Type::Matrix {
+                            column_type: args[1],
+                            column_count: args[2],
+                        },
+                    );
+                }
+                op::TYPE_IMAGE => {
+                    module.types.insert(
+                        args[0],
+                        Type::Image {
+                            dim: args[2],
+                            arrayed: args[4] != 0,
+                            sampled: args[6],
+                            format: args[7],
+                        },
+                    );
+                }
+                op::TYPE_SAMPLER => {
+                    module.types.insert(args[0], Type::Sampler);
+                }
+                op::TYPE_SAMPLED_IMAGE => {
+                    module.types.insert(
+                        args[0],
+                        Type::SampledImage {
+                            image_type: args[1],
+                        },
+                    );
+                }
+                op::TYPE_ARRAY => {
+                    module.types.insert(
+                        args[0],
+                        Type::Array {
+                            element_type: args[1],
+                            length_id: args[2],
+                        },
+                    );
+                }
+                op::TYPE_RUNTIME_ARRAY => {
+                    module.types.insert(
+                        args[0],
+                        Type::RuntimeArray {
+                            element_type: args[1],
+                        },
+                    );
+                }
+                op::TYPE_STRUCT => {
+                    module.types.insert(
+                        args[0],
+                        Type::Struct {
+                            member_types: args[1..].to_vec(),
+                        },
+                    );
+                }
+                op::TYPE_POINTER => {
+                    pointer_types.insert(args[0], (args[1], args[2]));
+                    module.types.insert(
+                        args[0],
+                        Type::Pointer {
+                            storage_class: args[1],
+                            pointee_type: args[2],
+                        },
+                    );
+                }
+                op::CONSTANT | op::SPEC_CONSTANT => {
+                    module.constants.insert(
+                        args[1],
+                        Constant {
+                            id: args[1],
+                            type_id: args[0],
+                            value: args[2..].to_vec(),
+                            specialization: opcode == op::SPEC_CONSTANT,
+                        },
+                    );
+                }
+                op::CONSTANT_TRUE | op::SPEC_CONSTANT_TRUE => {
+                    module.constants.insert(
+                        args[1],
+                        Constant {
+                            id: args[1],
+                            type_id: args[0],
+                            value: vec![1],
+                            specialization: opcode == op::SPEC_CONSTANT_TRUE,
+                        },
+                    );
+                }
+                op::CONSTANT_FALSE | op::SPEC_CONSTANT_FALSE => {
+                    module.constants.insert(
+                        args[1],
+                        Constant {
+                            id: args[1],
+                            type_id: args[0],
+                            value: vec![0],
+                            specialization: opcode == op::SPEC_CONSTANT_FALSE,
+                        },
+                    );
+                }
+                op::VARIABLE => {
+                    raw_variables.push((args[0], args[1], args[2]));
+                }
+                op::FUNCTION => break,
+                _ => {}
+            }
+        }
+
+        for (pointer_type, id, storage_class) in raw_variables {
+            let (_, type_id) = *pointer_types.get(&pointer_type).ok_or_else(|| {
+                Error::ReflectError(format!("Variable {id} does not have a pointer type"))
+            })?;
+            module.variables.push(Variable {
+                id,
+                type_id,
+                storage_class,
+            });
+        }
+
+        Ok(module)
+    }
+
+    pub(crate) fn get_entry_points(&self) -> &[EntryPoint] {
+        &self.entry_points
+    }
+
+    pub(crate) fn get_variables(&self) -> &[Variable] {
+        &self.variables
+    }
+
+    pub(crate) fn get_type(&self, id: u32) -> Option<&Type> {
+        self.types.get(&id)
+    }
+
+    pub(crate) fn get_name(&self, id: u32) -> Option<&str> {
+        self.names.get(&id).map(|s| s.as_str())
+    }
+
+    pub(crate) fn get_member_name(&self, struct_id: u32, member: u32) -> Option<&str> {
+        self.member_names
+            .get(&(struct_id, member))
+            .map(|s| s.as_str())
+    }
+
+    /// Returns the literal operands of the given decoration or `None` if `id` is not decorated with it.
+    pub(crate) fn get_decoration(&self, id: u32, decoration: u32) -> Option<&[u32]> {
+        self.decorations
+            .get(&(id, decoration))
+            .map(|v| v.as_slice())
+    }
+
+    /// Returns the literal operands of the given member decoration or `None` if the member is not decorated with it.
+    pub(crate) fn get_member_decoration(
+        &self,
+        struct_id: u32,
+        member: u32,
+        decoration: u32,
+    ) -> Option<&[u32]> {
+        self.member_decorations
+            .get(&(struct_id, member, decoration))
+            .map(|v| v.as_slice())
+    }
+
+    /// Returns all specialization constants declared with `OpSpecConstant`, `OpSpecConstantTrue` or `OpSpecConstantFalse`.
+    pub(crate) fn get_specialization_constants(&self) -> impl Iterator<Item = &Constant> {
+        self.constants.values().filter(|c| c.specialization)
+    }
+
+    /// Returns the first word of a (specialization) constant.
+    pub(crate) fn get_constant_u32(&self, id: u32) -> Option<u32> {
+        self.constants
+            .get(&id)
+            .and_then(|c| c.value.first().copied())
+    }
+
+    /// Returns the size in bytes of the given type, as laid out in a buffer.
+    ///
+    /// Runtime arrays have a size of zero.
+    pub(crate) fn get_type_size(&self, id: u32) -> Option<u32> {
+        self.get_size(id, None, false)
+    }
+
+    /// Returns the size in bytes of the given struct member, taking matrix layout decorations into account.
+    pub(crate) fn get_member_size(&self, struct_id: u32, member: u32) -> Option<u32> {
+        let member_type = match self.get_type(struct_id)? {
+            Type::Struct { member_types } => *member_types.get(member as usize)?,
+            _ => return None,
+        };
+
+        let matrix_stride = self
+            .get_member_decoration(struct_id, member, decoration::MATRIX_STRIDE)
+            .and_then(|d| d.first().copied());
+        let row_major = self
+            .get_member_decoration(struct_id, member, decoration::ROW_MAJOR)
+            .is_some();
+
+        self.get_size(member_type, matrix_stride, row_major)
+    }
+
+    fn get_size(&self, id: u32, matrix_stride: Option<u32>, row_major: bool) -> Option<u32> {
+        match self.get_type(id)? {
+            Type::Bool => Some(4),
+            Type::Int { width, .. } | Type::Float { width } => Some(width / 8),
+            Type::Vector {
+                component_type,
+                count,
+            } => self
+                .get_size(*component_type, None, false)?
+                .checked_mul(*count),
+            Type::Matrix {
+                column_type,
+                column_count,
+            } => {
+                let column_size = self.get_size(*column_type, None, false)?;
+                match (matrix_stride, row_major) {
+                    (Some(stride), false) => stride.checked_mul(*column_count),
+                    (Some(stride), true) => match self.get_type(*column_type)? {
+                        Type::Vector { count, .. } => stride.checked_mul(*count),
+                        _ => None,
+                    },
+                    (None, _) => column_size.checked_mul(*column_count),
+                }
+            }
+            Type::Array {
+                element_type,
+                length_id,
+            } => {
+                let length = self.get_constant_u32(*length_id)?;
+                let stride = match self
+                    .get_decoration(id, decoration::ARRAY_STRIDE)
+                    .and_then(|d| d.first().copied())
+                {
+                    Some(stride) => stride,
+                    None => self.get_size(*element_type, matrix_stride, row_major)?,
+                };
+                stride.checked_mul(length)
+            }
+            Type::RuntimeArray { .. } => Some(0),
+            Type::Struct { member_types } => {
+                let mut size = 0;
+                for member in 0..member_types.len() as u32 {
+                    let offset = self
+                        .get_member_decoration(id, member, decoration::OFFSET)
+                        .and_then(|d| d.first().copied())
+                        .unwrap_or(0);
+                    size = size.max(offset.checked_add(self.get_member_size(id, member)?)?);
+                }
+                Some(size)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Decodes a nul-terminated UTF-8 literal string.
+fn parse_string(words: &[u32]) -> String {
+    let mut bytes = Vec::with_capacity(words.len() * 4);
+    'outer: for word in words {
+        for byte in word.to_le_bytes() {
+            if byte == 0 {
+                break 'outer;
+            }
+            bytes.push(byte);
+        }
+    }
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decoration, op, parse_string, storage_class, Module, Type, MAGIC};
+
+    /// Encodes an instruction with the given operands.
+    fn instruction(opcode: u32, operands: &[u32]) -> Vec<u32> {
+        let mut words = vec![((operands.len() as u32 + 1) << 16) | opcode];
+        words.extend_from_slice(operands);
+        words
+    }
+
+    /// Encodes a nul-terminated literal string.
+    fn string(s: &str) -> Vec<u32> {
+        let mut bytes = s.as_bytes().to_vec();
+        bytes.resize(s.len() / 4 * 4 + 4, 0);
+        bytes
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect()
+    }
+
+    fn module(instructions: &[Vec<u32>]) -> Vec<u32> {
+        let mut words = vec![MAGIC, 0x0001_0500, 0, 32, 0];
+        for instruction in instructions {
+            words.extend_from_slice(instruction);
+        }
+        words
+    }
+
+    #[test]
+    fn test_parse_string() {
+        assert_eq!(parse_string(&string("main")), "main");
+        assert_eq!(parse_string(&string("u_Data")), "u_Data");
+        // a string without terminator ends with the instruction
+        assert_eq!(parse_string(&[u32::from_le_bytes(*b"comp")]), "comp");
+        assert_eq!(parse_string(&[]), "");
+    }
+
+    #[test]
+    fn test_compute_module() {
+        // RWStructuredBuffer<uint> b_Data : register(u1, space0), [numthreads(64, 2, 1)]
+        let code = module(&[
+            instruction(
+                op::ENTRY_POINT,
+                &[[5, 1].as_slice(), &string("comp"), &[10]].concat(),
+            ),
+            instruction(op::EXECUTION_MODE, &[1, 17, 64, 2, 1]),
+            instruction(op::NAME, &[[10].as_slice(), &string("b_Data")].concat()),
+            instruction(op::DECORATE, &[3, decoration::ARRAY_STRIDE, 4]),
+            instruction(op::MEMBER_DECORATE, &[4, 0, decoration::OFFSET, 16]),
+            instruction(op::MEMBER_DECORATE, &[4, 1, decoration::OFFSET, 0]),
+            instruction(op::DECORATE, &[10, decoration::DESCRIPTOR_SET, 0]),
+            instruction(op::DECORATE, &[10, decoration::BINDING, 1]),
+            instruction(op::TYPE_INT, &[2, 32, 0]),
+            instruction(op::TYPE_RUNTIME_ARRAY, &[3, 2]),
+            instruction(op::TYPE_VECTOR, &[6, 2, 3]),
+            instruction(op::TYPE_STRUCT, &[4, 3, 6]),
+            instruction(op::TYPE_POINTER, &[5, storage_class::STORAGE_BUFFER, 4]),
+            instruction(op::VARIABLE, &[5, 10, storage_class::STORAGE_BUFFER]),
+            instruction(op::FUNCTION, &[7, 1, 0, 8]),
+            // the body is not parsed
+            vec![0xFFFF_0000],
+        ]);
+        let module = Module::from_words(&code).unwrap();
+
+        let entry_point = &module.get_entry_points()[0];
+        assert_eq!(entry_point.execution_model, 5);
+        assert_eq!(entry_point.workgroup_size, Some([64, 2, 1]));
+
+        let variable = &module.get_variables()[0];
+        assert_eq!((variable.id, variable.type_id), (10, 4));
+        assert_eq!(variable.storage_class, storage_class::STORAGE_BUFFER);
+        assert_eq!(module.get_name(10), Some("b_Data"));
+        assert_eq!(
+            module.get_decoration(10, decoration::BINDING),
+            Some(&[1][..])
+        );
+
+        // the runtime array at offset 16 adds nothing to the size, the uvec3 at offset 0 is 12 bytes large
+        assert_eq!(
+            module.get_type(3),
+            Some(&Type::RuntimeArray { element_type: 2 })
+        );
+        assert_eq!(module.get_member_size(4, 0), Some(0));
+        assert_eq!(module.get_type_size(4), Some(16));
+    }
+
+    #[test]
+    fn test_type_sizes() {
+        let code = module(&[
+            instruction(op::MEMBER_DECORATE, &[6, 0, decoration::OFFSET, 0]),
+            instruction(op::MEMBER_DECORATE, &[6, 0, decoration::MATRIX_STRIDE, 16]),
+            instruction(op::MEMBER_DECORATE, &[6, 1, decoration::OFFSET, 48]),
+            instruction(op::MEMBER_DECORATE, &[6, 1, decoration::ROW_MAJOR]),
+            instruction(op::MEMBER_DECORATE, &[6, 1, decoration::MATRIX_STRIDE, 16]),
+            instruction(op::MEMBER_DECORATE, &[6, 2, decoration::OFFSET, 96]),
+            instruction(op::DECORATE, &[5, decoration::ARRAY_STRIDE, 16]),
+            instruction(op::TYPE_FLOAT, &[1, 32]),
+            instruction(op::TYPE_VECTOR, &[2, 1, 3]),
+            // mat2x3: two columns of three components
+            instruction(op::TYPE_MATRIX, &[3, 2, 2]),
+            instruction(op::TYPE_INT, &[7, 32, 0]),
+            instruction(op::CONSTANT, &[7, 4, 3]),
+            instruction(op::TYPE_ARRAY, &[5, 1, 4]),
+            instruction(op::TYPE_STRUCT, &[6, 3, 3, 5]),
+        ]);
+        let module = Module::from_words(&code).unwrap();
+
+        assert_eq!(module.get_type_size(2), Some(12));
+        // without a stride, columns are tightly packed
+        assert_eq!(module.get_type_size(3), Some(24));
+        // column major: one stride per column, row major: one stride per row
+        assert_eq!(module.get_member_size(6, 0), Some(32));
+        assert_eq!(module.get_member_size(6, 1), Some(48));
+        assert_eq!(module.get_constant_u32(4), Some(3));
+        assert_eq!(module.get_member_size(6, 2), Some(48));
+        assert_eq!(module.get_type_size(6), Some(144));
+    }
+
+    #[test]
+    fn test_invalid_modules() {
+        assert!(Module::from_words(&[]).is_err());
+        assert!(Module::from_words(&[MAGIC, 0x0001_0500, 0, 32]).is_err());
+        // a header alone is a valid, empty module
+        assert!(Module::from_words(&module(&[])).is_ok());
+
+        // LocalSize without all three dimensions
+        assert!(
+            Module::from_words(&module(&[instruction(op::EXECUTION_MODE, &[1, 17, 64])])).is_err()
+        );
+        // a variable whose type is not a pointer
+        assert!(Module::from_words(&module(&[
+            instruction(op::TYPE_FLOAT, &[1, 32]),
+            instruction(op::VARIABLE, &[1, 2, storage_class::INPUT]),
+        ]))
+        .is_err());
+        // an instruction that is longer than the module
+        let mut truncated = module(&[instruction(op::TYPE_FLOAT, &[1, 32])]);
+        truncated.pop();
+        assert!(Module::from_words(&truncated).is_err());
+    }
+}
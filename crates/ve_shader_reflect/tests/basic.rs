@@ -1,22 +1,29 @@
-use core::slice;
 use std::fs;
 
 use ve_shader_reflect::*;
 
+/// Loads a compiled shader from `tests/shaders`, SPIR-V files are little endian.
+fn load_spv(name: &str) -> Vec<u32> {
+    let spv = fs::read(format!(
+        "{}/tests/shaders/{name}.spv",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .unwrap();
+    spv.chunks_exact(4)
+        .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        .collect()
+}
+
 #[test]
 pub fn test_gpass_simple() {
-    let root = env!("CARGO_MANIFEST_DIR");
-
     {
-        let vert_spv = fs::read(format!("{root}/tests/shaders/gpass_simple_vert.spv")).unwrap();
-        let info_vert = reflect_shader(unsafe {
-            slice::from_raw_parts(vert_spv.as_ptr() as *const u32, vert_spv.len() / 4)
-        })
-        .expect("Failed to reflect vertex shader");
+        let info_vert = reflect_shader(&load_spv("gpass_simple_vert"))
+            .expect("Failed to reflect vertex shader");
 
         assert!(info_vert.set_bindings.contains(&SetBinding {
             set: 0,
             binding: 0,
+            count: 1,
             var_name: String::from("u_FrameData"),
             data: SetBindingData::UniformBuffer {
                 layout: BlockLayout {
@@ -54,15 +61,13 @@ pub fn test_gpass_simple() {
     }
 
     {
-        let frag_spv = fs::read(format!("{root}/tests/shaders/gpass_simple_frag.spv")).unwrap();
-        let info_frag = reflect_shader(unsafe {
-            slice::from_raw_parts(frag_spv.as_ptr() as *const u32, frag_spv.len() / 4)
-        })
-        .expect("Failed to reflect fragment shader");
+        let info_frag = reflect_shader(&load_spv("gpass_simple_frag"))
+            .expect("Failed to reflect fragment shader");
 
         assert!(info_frag.set_bindings.contains(&SetBinding {
             set: 1,
             binding: 1,
+            count: 1,
             var_name: String::from("u_AlbedoTex"),
             data: SetBindingData::SampledImage {
                 dim: ImageDimension::Two,
@@ -72,6 +77,7 @@ pub fn test_gpass_simple() {
         assert!(info_frag.set_bindings.contains(&SetBinding {
             set: 1,
             binding: 0,
+            count: 1,
             var_name: String::from("u_Material"),
             data: SetBindingData::UniformBuffer {
                 layout: BlockLayout {
@@ -102,3 +108,356 @@ pub fn test_gpass_simple() {
         }));
     }
 }
+
+#[test]
+pub fn test_push_constants() {
+    let info_vert =
+        reflect_shader(&load_spv("gpass_simple_vert")).expect("Failed to reflect vertex shader");
+    let info_frag =
+        reflect_shader(&load_spv("gpass_simple_frag")).expect("Failed to reflect fragment shader");
+
+    assert_eq!(info_vert.stage, ShaderStages::VERTEX);
+    assert_eq!(info_frag.stage, ShaderStages::FRAGMENT);
+    assert!(info_frag.push_constants.is_empty());
+
+    let model_data = PushConstantRange {
+        stages: ShaderStages::VERTEX,
+        offset: 0,
+        size: 128,
+        layout: BlockLayout {
+            block_name: String::from("ModelData"),
+            total_size: 128,
+            members: vec![
+                BlockMember {
+                    kind: BlockMemberType::FloatMatrix(4),
+                    offset: 0,
+                    size: 64,
+                    name: String::from("modelMatrix"),
+                },
+                BlockMember {
+                    kind: BlockMemberType::FloatMatrix(4),
+                    offset: 64,
+                    size: 64,
+                    name: String::from("invModelMatrix"),
+                },
+            ],
+        },
+    };
+    assert_eq!(info_vert.push_constants, vec![model_data.clone()]);
+
+    let merged = merge(info_vert.clone(), &info_frag, true).unwrap();
+    assert_eq!(merged.stage, ShaderStages::VERTEX | ShaderStages::FRAGMENT);
+    assert_eq!(merged.push_constants, vec![model_data.clone()]);
+
+    // a block with the same layout in another stage is shared with it
+    let mut info_frag_pushing = info_frag;
+    info_frag_pushing.push_constants = vec![PushConstantRange {
+        stages: ShaderStages::FRAGMENT,
+        ..model_data
+    }];
+    let merged = merge(info_vert, &info_frag_pushing, true).unwrap();
+    assert_eq!(merged.push_constants.len(), 1);
+    assert!(merged.push_constants[0]
+        .stages
+        .contains(ShaderStages::VERTEX | ShaderStages::FRAGMENT));
+}
+
+#[test]
+pub fn test_block_types() {
+    let info = reflect_shader(&load_spv("block_types_frag")).expect("Failed to reflect shader");
+
+    let layout = match &info.set_bindings[0].data {
+        SetBindingData::UniformBuffer { layout } => layout,
+        data => panic!("Expected a uniform buffer, got {data:?}"),
+    };
+    assert_eq!(layout.block_name, "Types");
+    assert_eq!(layout.total_size, 272);
+
+    let member = |name: &str, kind: BlockMemberType, offset: u32, size: u32| BlockMember {
+        kind,
+        offset,
+        size,
+        name: String::from(name),
+    };
+    let array = |element: BlockMemberType, length: u32, stride: u32| BlockMemberType::Array {
+        element: Box::new(element),
+        length,
+        stride,
+    };
+    assert_eq!(
+        layout.members,
+        vec![
+            member("count", BlockMemberType::Int, 0, 4),
+            member("mask", BlockMemberType::UInt, 4, 4),
+            // glslang stores booleans in blocks as uint
+            member("enabled", BlockMemberType::UInt, 8, 4),
+            member("tile", BlockMemberType::IntVector(2), 16, 8),
+            member("flags", BlockMemberType::UIntVector(3), 32, 12),
+            member("channels", BlockMemberType::UIntVector(4), 48, 16),
+            member("weights", array(BlockMemberType::Float, 4, 16), 64, 64),
+            member(
+                "colors",
+                array(BlockMemberType::FloatVector(4), 2, 16),
+                128,
+                32
+            ),
+            member("sun.color", BlockMemberType::FloatVector(3), 160, 12),
+            member("sun.intensity", BlockMemberType::Float, 172, 4),
+            member("lamps[0].color", BlockMemberType::FloatVector(3), 176, 12),
+            member("lamps[0].intensity", BlockMemberType::Float, 188, 4),
+            member("lamps[1].color", BlockMemberType::FloatVector(3), 192, 12),
+            member("lamps[1].intensity", BlockMemberType::Float, 204, 4),
+            member("transform", BlockMemberType::FloatMatrix(4), 208, 64),
+        ]
+    );
+}
+
+#[test]
+pub fn test_resources() {
+    let info = reflect_shader(&load_spv("resources_comp")).expect("Failed to reflect shader");
+    assert_eq!(info.workgroup_size, Some([8, 8, 1]));
+
+    let binding = |binding: u32| {
+        info.set_bindings
+            .iter()
+            .find(|b| b.binding == binding)
+            .unwrap_or_else(|| panic!("Binding {binding} not found"))
+    };
+
+    assert_eq!(
+        binding(0).data,
+        SetBindingData::StorageBuffer {
+            layout: BlockLayout {
+                block_name: String::from("Particles"),
+                total_size: 16,
+                members: vec![
+                    BlockMember {
+                        kind: BlockMemberType::UInt,
+                        offset: 0,
+                        size: 4,
+                        name: String::from("count"),
+                    },
+                    BlockMember {
+                        kind: BlockMemberType::RuntimeArray {
+                            element: Box::new(BlockMemberType::Struct {
+                                members: vec![
+                                    BlockMember {
+                                        kind: BlockMemberType::FloatVector(3),
+                                        offset: 0,
+                                        size: 12,
+                                        name: String::from("position"),
+                                    },
+                                    BlockMember {
+                                        kind: BlockMemberType::Float,
+                                        offset: 12,
+                                        size: 4,
+                                        name: String::from("lifetime"),
+                                    },
+                                ],
+                            }),
+                            stride: 16,
+                        },
+                        offset: 16,
+                        size: 0,
+                        name: String::from("particles"),
+                    },
+                ],
+            },
+        }
+    );
+    assert_eq!(
+        binding(1).data,
+        SetBindingData::StorageImage {
+            dim: ImageDimension::Two,
+            format: ImageFormat::Rgba16f,
+        }
+    );
+    assert_eq!(
+        binding(2).data,
+        SetBindingData::SampledImage {
+            dim: ImageDimension::TwoArray,
+        }
+    );
+    assert_eq!(
+        binding(3).data,
+        SetBindingData::SampledImage {
+            dim: ImageDimension::Cube,
+        }
+    );
+    assert_eq!(
+        binding(4).data,
+        SetBindingData::SampledImage {
+            dim: ImageDimension::Three,
+        }
+    );
+    assert_eq!(binding(4).count, 1);
+    assert_eq!(
+        binding(5).data,
+        SetBindingData::SampledImage {
+            dim: ImageDimension::Two,
+        }
+    );
+    assert_eq!(binding(5).count, 4);
+    assert_eq!(
+        binding(6).data,
+        SetBindingData::StorageImage {
+            dim: ImageDimension::One,
+            format: ImageFormat::R32ui,
+        }
+    );
+}
+
+#[test]
+pub fn test_vertex_inputs() {
+    let info_vert =
+        reflect_shader(&load_spv("gpass_simple_vert")).expect("Failed to reflect vertex shader");
+    let info_frag =
+        reflect_shader(&load_spv("gpass_simple_frag")).expect("Failed to reflect fragment shader");
+
+    let vertex_inputs = vec![
+        StageInput {
+            location: 0,
+            kind: BlockMemberType::FloatVector(3),
+            name: String::from("in_Position"),
+        },
+        StageInput {
+            location: 1,
+            kind: BlockMemberType::FloatVector(3),
+            name: String::from("in_Normal"),
+        },
+        StageInput {
+            location: 2,
+            kind: BlockMemberType::FloatVector(2),
+            name: String::from("in_UV"),
+        },
+    ];
+    assert_eq!(info_vert.inputs, vertex_inputs);
+
+    // the order of the modules does not matter
+    let merged = merge(info_frag.clone(), &info_vert, false).unwrap();
+    assert_eq!(merged.inputs, vertex_inputs);
+    let merged = merge(info_vert, &info_frag, false).unwrap();
+    assert_eq!(merged.inputs, vertex_inputs);
+}
+
+#[test]
+pub fn test_specialization_constants() {
+    let info = reflect_shader(&load_spv("specialization_frag")).expect("Failed to reflect shader");
+
+    let constant =
+        |id: u32, kind: BlockMemberType, default_value: u32, name: &str| SpecializationConstant {
+            id,
+            kind,
+            default_value,
+            name: String::from(name),
+        };
+    assert_eq!(
+        info.specialization_constants,
+        vec![
+            constant(0, BlockMemberType::Bool, 1, "USE_ALBEDO_TEX"),
+            constant(1, BlockMemberType::Int, 4, "SAMPLE_COUNT"),
+            constant(2, BlockMemberType::Float, 0.5f32.to_bits(), "ALPHA_CUTOFF"),
+            constant(5, BlockMemberType::UInt, 3, "LAYER"),
+        ]
+    );
+
+    // constants with the same id must agree in both modules
+    assert!(merge(info.clone(), &info, true).is_ok());
+    let mut other = info.clone();
+    other.specialization_constants[1].default_value = 8;
+    assert!(merge(info, &other, false).is_err());
+}
+
+#[test]
+pub fn test_rust_struct() {
+    let info = reflect_shader(&load_spv("block_types_frag")).expect("Failed to reflect shader");
+    let layout = match &info.set_bindings[0].data {
+        SetBindingData::UniformBuffer { layout } => layout,
+        data => panic!("Unexpected binding {data:?}"),
+    };
+
+    let options = codegen::RustStructOptions {
+        derives: vec![String::from("Clone"), String::from("Copy")],
+        types: vec![
+            (BlockMemberType::Float, String::from("Scalar")),
+            (BlockMemberType::FloatVector(3), String::from("Vec3")),
+            (BlockMemberType::FloatMatrix(4), String::from("Mat4")),
+        ],
+    };
+    let code = codegen::rust_struct("TypesData", layout, &options);
+
+    assert!(code.contains("#[repr(C)]\n#[derive(Clone, Copy)]\npub struct TypesData {"));
+    // std140 pads ivec2 to an offset of 16 and array elements to 16 bytes
+    assert!(code.contains("    pub _pad0: [u8; 4],\n    pub tile: [i32; 2],"));
+    // padded members don't use the overridden types
+    assert!(code.contains("    pub weights: [[f32; 4]; 4],"));
+    assert!(code.contains("    pub sun_color: Vec3,\n    pub sun_intensity: Scalar,"));
+    assert!(code.contains("    pub lamps_1_intensity: Scalar,"));
+    assert!(code.contains("    pub transform: Mat4,"));
+    assert!(code.contains("const _: () = assert!(std::mem::size_of::<Mat4>() == 64);"));
+    assert!(code.contains("const _: () = assert!(std::mem::size_of::<TypesData>() == 272);"));
+
+    // the generated code has to compile, including its layout assertions
+    let stubs = "#[derive(Clone, Copy)]\npub struct Scalar(f32);\n\
+                 #[derive(Clone, Copy)]\npub struct Vec3([f32; 3]);\n\
+                 #[derive(Clone, Copy)]\npub struct Mat4([f32; 16]);\n";
+    let dir = std::env::temp_dir().join(format!("ve_codegen_test_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let source = dir.join("types.rs");
+    fs::write(&source, format!("{stubs}\n{code}")).unwrap();
+    let output =
+        std::process::Command::new(std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_owned()))
+            .args(["--edition", "2021", "--crate-type", "lib", "--out-dir"])
+            .arg(&dir)
+            .arg(&source)
+            .output()
+            .expect("Failed to run rustc");
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(
+        output.status.success(),
+        "Generated code does not compile:\n{code}\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+pub fn test_malformed_spirv() {
+    // magic, version 1.0, generator, bound, schema
+    let header = [0x0723_0203, 0x0001_0000, 0, 16, 0];
+    let module = |instructions: &[u32]| [&header[..], instructions].concat();
+    let is_reflect_error =
+        |code: &[u32]| matches!(reflect_shader(code), Err(Error::ReflectError(_)));
+
+    assert!(is_reflect_error(&[]));
+    assert!(is_reflect_error(&[0x0302_2307, 0, 0, 0, 0]));
+    // OpName without operands
+    assert!(is_reflect_error(&module(&[1 << 16 | 5])));
+    // OpTypeImage with only its result id
+    assert!(is_reflect_error(&module(&[2 << 16 | 25, 1])));
+    // OpTypeVector with an undeclared component type
+    assert!(is_reflect_error(&module(&[4 << 16 | 23, 1, 2, 4])));
+    // a redeclared type, which could make the types cyclic
+    assert!(is_reflect_error(&module(&[
+        3 << 16 | 22,
+        1,
+        32,
+        4 << 16 | 23,
+        2,
+        1,
+        4,
+        4 << 16 | 23,
+        1,
+        2,
+        4
+    ])));
+    // instruction longer than the module
+    assert!(is_reflect_error(&module(&[8 << 16 | 22, 1, 32])));
+    // instruction with a word count of zero
+    assert!(is_reflect_error(&module(&[0, 1])));
+
+    // every truncation of a valid module is either reflected or rejected, but never panics
+    let code = load_spv("gpass_simple_vert");
+    for len in 0..code.len() {
+        let _ = reflect_shader(&code[..len]);
+    }
+}
@@ -0,0 +1,26 @@
+#version 450
+
+struct Light {
+    vec3 color;
+    float intensity;
+};
+
+layout (set=0, binding=0) uniform Types {
+    int count;
+    uint mask;
+    bool enabled;
+    ivec2 tile;
+    uvec3 flags;
+    bvec4 channels;
+    float weights[4];
+    vec4 colors[2];
+    Light sun;
+    Light lamps[2];
+    mat4 transform;
+} u_Types;
+
+layout (location = 0) out vec4 out_Color;
+
+void main() {
+    out_Color = vec4(1.0);
+}
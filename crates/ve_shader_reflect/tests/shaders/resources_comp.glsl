@@ -0,0 +1,23 @@
+#version 450
+
+layout (local_size_x = 8, local_size_y = 8) in;
+
+struct Particle {
+    vec3 position;
+    float lifetime;
+};
+
+layout (set=0, binding=0, std430) buffer Particles {
+    uint count;
+    Particle particles[];
+} b_Particles;
+
+layout (set=0, binding=1, rgba16f) uniform writeonly image2D i_Output;
+layout (set=0, binding=2) uniform sampler2DArray u_Layers;
+layout (set=0, binding=3) uniform samplerCube u_Environment;
+layout (set=0, binding=4) uniform sampler3D u_Volume;
+layout (set=0, binding=5) uniform sampler2D u_Textures[4];
+layout (set=0, binding=6, r32ui) uniform uimage1D i_Histogram;
+
+void main() {
+}
@@ -0,0 +1,12 @@
+#version 450
+
+layout (constant_id = 0) const bool USE_ALBEDO_TEX = true;
+layout (constant_id = 1) const int SAMPLE_COUNT = 4;
+layout (constant_id = 2) const float ALPHA_CUTOFF = 0.5;
+layout (constant_id = 5) const uint LAYER = 3u;
+
+layout (location = 0) out vec4 out_Color;
+
+void main() {
+    out_Color = vec4(1.0);
+}
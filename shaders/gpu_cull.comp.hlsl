@@ -0,0 +1,58 @@
+#include "gpass.hlslh"
+
+// matches VkDrawIndexedIndirectCommand
+struct DrawCommand {
+    uint indexCount;
+    uint instanceCount;
+    uint firstIndex;
+    int vertexOffset;
+    uint firstInstance;
+};
+
+struct CullData {
+    float4 frustumPlanes[6]; // world space, normals point inwards
+    uint commandCount;
+    uint firstCommand;
+    uint countCulled; // whether culled objects are added to u_CulledCount
+};
+
+[[vk::push_constant]] ConstantBuffer<CullData> u_Cull;
+
+[[vk::binding(0, 0)]] StructuredBuffer<Transform> u_Transforms;
+[[vk::binding(1, 0)]] StructuredBuffer<float4> u_BoundingSpheres; // local space, xyz: center, w: radius
+[[vk::binding(2, 0)]] RWStructuredBuffer<DrawCommand> u_Commands;
+[[vk::binding(3, 0)]] RWStructuredBuffer<uint> u_CulledCount;
+
+[numthreads(64, 1, 1)]
+void comp(uint3 id : SV_DISPATCHTHREADID) {
+    if (id.x >= u_Cull.commandCount) {
+        return;
+    }
+    uint command = u_Cull.firstCommand + id.x;
+
+    // the object index is passed to the vertex shader as first instance
+    uint object = u_Commands[command].firstInstance;
+    float4x4 model = u_Transforms[object].modelMatrix;
+    float4 sphere = u_BoundingSpheres[object];
+
+    float3 center = (float4(sphere.xyz, 1.0) * model).xyz;
+    float3 axisX = (float4(1.0, 0.0, 0.0, 0.0) * model).xyz;
+    float3 axisY = (float4(0.0, 1.0, 0.0, 0.0) * model).xyz;
+    float3 axisZ = (float4(0.0, 0.0, 1.0, 0.0) * model).xyz;
+    float scale = sqrt(max(max(dot(axisX, axisX), dot(axisY, axisY)), dot(axisZ, axisZ)));
+    float radius = sphere.w * scale;
+
+    bool visible = true;
+    for (uint i = 0; i < 6; i++) {
+        float4 plane = u_Cull.frustumPlanes[i];
+        visible = visible && (dot(plane.xyz, center) + plane.w >= -radius);
+    }
+
+    u_Commands[command].instanceCount = visible ? 1 : 0;
+
+    // the commands of an object are consecutive, only its first one is counted
+    bool firstOfObject = id.x == 0 || u_Commands[command - 1].firstInstance != object;
+    if (!visible && u_Cull.countCulled != 0 && firstOfObject) {
+        InterlockedAdd(u_CulledCount[0], 1);
+    }
+}
@@ -1,7 +1,7 @@
 #include "gpass.hlslh"
 
 CAM_BINDING ConstantBuffer<Cam> u_Cam;
-TRANSFORM_BINDING ConstantBuffer<Transform> u_Transform;
+TRANSFORM_BINDING StructuredBuffer<Transform> u_Transforms;
 
 struct V2F {
     float4 position : SV_POSITION;
@@ -21,11 +21,12 @@ struct MaterialData {
 
 MATERIAL_BINDING(0) ConstantBuffer<MaterialData> u_Material;
 
-V2F vert(Vert vIn) {
+V2F vert(Vert vIn, uint instance : SV_InstanceID) {
     V2F vOut;
+    Transform transform = u_Transforms[instance];
 
-    vOut.position = float4(vIn.position, 1.0) * u_Transform.modelMatrix * u_Cam.viewMatrix * u_Cam.projMatrix;
-    vOut.worldNormal = (float4(vIn.normal, 0.0) * transpose(u_Transform.invModelMatrix)).xyz;
+    vOut.position = float4(vIn.position, 1.0) * transform.modelMatrix * u_Cam.viewMatrix * u_Cam.projMatrix;
+    vOut.worldNormal = (float4(vIn.normal, 0.0) * transpose(transform.invModelMatrix)).xyz;
 
     return vOut;
 }
@@ -10,12 +10,13 @@ struct Frag {
 };
 
 CAM_BINDING ConstantBuffer<Cam> u_Cam;
-TRANSFORM_BINDING ConstantBuffer<Transform> u_Transform;
+TRANSFORM_BINDING StructuredBuffer<Transform> u_Transforms;
 
-V2F vert(Vert vIn) {
+V2F vert(Vert vIn, uint instance : SV_InstanceID) {
     V2F res;
+    Transform transform = u_Transforms[instance];
 
-    res.position = float4(vIn.position, 1.0) * u_Transform.modelMatrix * u_Cam.viewMatrix * u_Cam.projMatrix;
+    res.position = float4(vIn.position, 1.0) * transform.modelMatrix * u_Cam.viewMatrix * u_Cam.projMatrix;
     res.vertexColor = vIn.color;
 
     return res;